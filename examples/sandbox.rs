@@ -64,11 +64,14 @@ fn main() -> Result<(), Box<dyn Error>> {
         birdcage.add_exception(Exception::Networking)?;
     }
 
-    // Activate sandbox.
-    birdcage.lock().unwrap();
-
-    // Run the command.
-    let status = Command::new(cli.cmd).args(&cli.args).spawn()?.wait()?;
+    // Run the command with the sandbox applied.
+    let mut command = Command::new(cli.cmd);
+    command.args(&cli.args);
+    let (mut child, report) = birdcage.spawn(command)?;
+    for skipped in report.skipped() {
+        eprintln!("warning: sandboxing layer skipped: {skipped}");
+    }
+    let status = child.wait()?;
     let exit_code = status.code().unwrap_or(111);
 
     process::exit(exit_code);
@@ -0,0 +1,185 @@
+//! Serializable sandbox configuration.
+
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+#[cfg(target_os = "linux")]
+use crate::Clock;
+use crate::{Birdcage, Exception, Sandbox, Strictness};
+
+/// Latest sandbox configuration version.
+pub const VERSION: u32 = 1;
+
+/// Complete configuration of a sandbox.
+///
+/// Configurations are serialized with a version, to allow loading them after
+/// the format has been changed. Deserializing configurations with an unknown
+/// version will fail.
+///
+/// Settings with their default value are omitted from the serialized format.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(try_from = "VersionedConfig", into = "VersionedConfig")]
+pub struct SandboxConfig {
+    exceptions: Vec<Exception>,
+    pub(crate) strictness: Strictness,
+    #[cfg(target_os = "linux")]
+    pub(crate) require_exceptions: bool,
+    #[cfg(target_os = "linux")]
+    pub(crate) hostname: Option<String>,
+    #[cfg(target_os = "linux")]
+    pub(crate) domainname: Option<String>,
+    #[cfg(target_os = "linux")]
+    pub(crate) clock_offsets: Vec<(Clock, i64)>,
+}
+
+impl SandboxConfig {
+    /// Create a configuration from a list of exceptions.
+    ///
+    /// All other settings use their default value.
+    pub fn new(exceptions: Vec<Exception>) -> Self {
+        Self { exceptions, ..Default::default() }
+    }
+
+    /// Get all exceptions in the order they were added.
+    pub fn exceptions(&self) -> &[Exception] {
+        &self.exceptions
+    }
+
+    /// Get the handling of unsupported sandboxing layers.
+    pub fn strictness(&self) -> Strictness {
+        self.strictness
+    }
+
+    /// Check if activation fails when a filesystem exception cannot be
+    /// applied.
+    #[cfg(target_os = "linux")]
+    pub fn require_exceptions(&self) -> bool {
+        self.require_exceptions
+    }
+
+    /// Get the hostname inside the sandbox.
+    #[cfg(target_os = "linux")]
+    pub fn hostname(&self) -> Option<&str> {
+        self.hostname.as_deref()
+    }
+
+    /// Get the NIS domain name inside the sandbox.
+    #[cfg(target_os = "linux")]
+    pub fn domainname(&self) -> Option<&str> {
+        self.domainname.as_deref()
+    }
+
+    /// Get the clock offsets in seconds inside the sandbox.
+    #[cfg(target_os = "linux")]
+    pub fn clock_offsets(&self) -> &[(Clock, i64)] {
+        &self.clock_offsets
+    }
+
+    /// Create a sandbox with this configuration.
+    pub fn sandbox(&self) -> Result<Birdcage> {
+        let mut birdcage = Birdcage::new();
+        birdcage.strictness(self.strictness);
+
+        #[cfg(target_os = "linux")]
+        {
+            if self.require_exceptions {
+                birdcage.require_exceptions();
+            }
+            if let Some(hostname) = &self.hostname {
+                birdcage.hostname(hostname.clone());
+            }
+            if let Some(domainname) = &self.domainname {
+                birdcage.domainname(domainname.clone());
+            }
+            for (clock, seconds) in &self.clock_offsets {
+                birdcage.clock_offset(*clock, *seconds);
+            }
+        }
+
+        for exception in &self.exceptions {
+            birdcage.add_exception(exception.clone())?;
+        }
+        Ok(birdcage)
+    }
+}
+
+/// Serialization format of [`SandboxConfig`].
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VersionedConfig {
+    version: u32,
+    exceptions: Vec<Exception>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    strictness: Strictness,
+    #[cfg(target_os = "linux")]
+    #[serde(default, skip_serializing_if = "is_default")]
+    require_exceptions: bool,
+    #[cfg(target_os = "linux")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hostname: Option<String>,
+    #[cfg(target_os = "linux")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    domainname: Option<String>,
+    #[cfg(target_os = "linux")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    clock_offsets: Vec<(Clock, i64)>,
+}
+
+impl TryFrom<VersionedConfig> for SandboxConfig {
+    type Error = UnsupportedVersion;
+
+    fn try_from(config: VersionedConfig) -> std::result::Result<Self, Self::Error> {
+        if config.version != VERSION {
+            return Err(UnsupportedVersion(config.version));
+        }
+
+        Ok(Self {
+            exceptions: config.exceptions,
+            strictness: config.strictness,
+            #[cfg(target_os = "linux")]
+            require_exceptions: config.require_exceptions,
+            #[cfg(target_os = "linux")]
+            hostname: config.hostname,
+            #[cfg(target_os = "linux")]
+            domainname: config.domainname,
+            #[cfg(target_os = "linux")]
+            clock_offsets: config.clock_offsets,
+        })
+    }
+}
+
+impl From<SandboxConfig> for VersionedConfig {
+    fn from(config: SandboxConfig) -> Self {
+        Self {
+            version: VERSION,
+            exceptions: config.exceptions,
+            strictness: config.strictness,
+            #[cfg(target_os = "linux")]
+            require_exceptions: config.require_exceptions,
+            #[cfg(target_os = "linux")]
+            hostname: config.hostname,
+            #[cfg(target_os = "linux")]
+            domainname: config.domainname,
+            #[cfg(target_os = "linux")]
+            clock_offsets: config.clock_offsets,
+        }
+    }
+}
+
+/// Check if a setting has its default value.
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// Unsupported sandbox configuration version.
+#[derive(Debug)]
+struct UnsupportedVersion(u32);
+
+impl Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported sandbox configuration version {}, expected {VERSION}", self.0)
+    }
+}
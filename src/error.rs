@@ -1,19 +1,36 @@
 //! Sandboxing errors.
 
 use std::error::Error as StdError;
+#[cfg(target_os = "linux")]
+use std::ffi::{CStr, OsStr};
 use std::fmt::{self, Display, Formatter};
-use std::io::Error as IoError;
-use std::path::PathBuf;
+use std::io::{Error as IoError, ErrorKind};
+#[cfg(target_os = "linux")]
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
 
 #[cfg(target_os = "linux")]
 use seccompiler::{BackendError, Error as SeccompError};
 
+#[cfg(feature = "policy")]
+use crate::policy::PolicyError;
+#[cfg(target_os = "linux")]
+use crate::report::DroppedException;
+
 /// Birdcage result type.
 pub type Result<T> = StdResult<T, Error>;
 
+/// Result type for sandbox activation without allocations.
+#[cfg(target_os = "linux")]
+pub(crate) type RawResult<'a, T> = StdResult<T, RawError<'a>>;
+
 /// Sandboxing error.
+///
+/// Variants depend on the target platform and enabled features, so matching
+/// on them always requires a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Seccomp errors.
     #[cfg(target_os = "linux")]
@@ -27,6 +44,124 @@ pub enum Error {
 
     /// Sandbox activation failed.
     ActivationFailed(String),
+
+    /// Sandbox exception is not supported in this configuration.
+    InvalidException(&'static str),
+
+    /// Creating a user namespace failed.
+    #[cfg(target_os = "linux")]
+    UserNamespace(IoError),
+
+    /// Writing the UID/GID mappings of a user namespace failed.
+    #[cfg(target_os = "linux")]
+    IdMap { path: PathBuf, error: IoError },
+
+    /// Bind mounting a path into the sandbox failed.
+    #[cfg(target_os = "linux")]
+    BindMount { path: PathBuf, target: PathBuf, error: IoError },
+
+    /// Mounting a new filesystem, like a tmpfs or procfs, failed.
+    #[cfg(target_os = "linux")]
+    Mount { path: PathBuf, error: IoError },
+
+    /// Unmounting a filesystem failed.
+    #[cfg(target_os = "linux")]
+    Unmount { path: PathBuf, error: IoError },
+
+    /// Creating a directory for the sandbox's filesystem failed.
+    #[cfg(target_os = "linux")]
+    CreateDir { path: PathBuf, error: IoError },
+
+    /// Replicating a file, directory or symlink of the host under the
+    /// sandbox's root failed.
+    #[cfg(target_os = "linux")]
+    CreateNode { path: PathBuf, error: IoError },
+
+    /// Updating the attributes of a mount with `mount_setattr` failed.
+    #[cfg(target_os = "linux")]
+    MountSetattr { path: PathBuf, error: IoError },
+
+    /// Changing the root directory failed.
+    #[cfg(target_os = "linux")]
+    PivotRoot { path: PathBuf, error: IoError },
+
+    /// Setting the hostname of the UTS namespace failed.
+    #[cfg(target_os = "linux")]
+    Hostname(IoError),
+
+    /// Setting the NIS domain name of the UTS namespace failed.
+    #[cfg(target_os = "linux")]
+    Domainname(IoError),
+
+    /// Setting the clock offsets of the time namespace or joining it failed.
+    #[cfg(target_os = "linux")]
+    TimeNamespace { path: PathBuf, error: IoError },
+
+    /// Bringing up the loopback interface of the network namespace failed.
+    #[cfg(target_os = "linux")]
+    Loopback(IoError),
+
+    /// Loading the seccomp filter into the kernel failed.
+    #[cfg(target_os = "linux")]
+    SeccompLoad(IoError),
+
+    /// Setting `PR_SET_NO_NEW_PRIVS` failed.
+    #[cfg(target_os = "linux")]
+    NoNewPrivs(IoError),
+
+    /// Exceptions could not be applied.
+    #[cfg(target_os = "linux")]
+    DroppedExceptions(Vec<DroppedException>),
+
+    /// Invalid policy file.
+    #[cfg(feature = "policy")]
+    Policy(PolicyError),
+}
+
+impl Error {
+    /// Get the OS error code of the failed operation.
+    pub fn errno(&self) -> Option<i32> {
+        match self {
+            Self::Io(error) => error.raw_os_error(),
+            #[cfg(target_os = "linux")]
+            Self::UserNamespace(error)
+            | Self::IdMap { error, .. }
+            | Self::BindMount { error, .. }
+            | Self::Mount { error, .. }
+            | Self::Unmount { error, .. }
+            | Self::CreateDir { error, .. }
+            | Self::CreateNode { error, .. }
+            | Self::MountSetattr { error, .. }
+            | Self::PivotRoot { error, .. }
+            | Self::Hostname(error)
+            | Self::Domainname(error)
+            | Self::TimeNamespace { error, .. }
+            | Self::Loopback(error)
+            | Self::SeccompLoad(error)
+            | Self::NoNewPrivs(error) => error.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    /// Get the path involved in the failed operation.
+    ///
+    /// For bind mounts, this is the source of the mount.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::InvalidPath(path) => Some(path),
+            #[cfg(target_os = "linux")]
+            Self::IdMap { path, .. }
+            | Self::BindMount { path, .. }
+            | Self::Mount { path, .. }
+            | Self::Unmount { path, .. }
+            | Self::CreateDir { path, .. }
+            | Self::CreateNode { path, .. }
+            | Self::MountSetattr { path, .. }
+            | Self::PivotRoot { path, .. }
+            | Self::TimeNamespace { path, .. } => Some(path),
+            _ => None,
+        }
+    }
 }
 
 impl StdError for Error {}
@@ -41,6 +176,60 @@ impl Display for Error {
             Self::ActivationFailed(error) => {
                 write!(f, "failed to initialize a sufficient sandbox: {error}")
             },
+            Self::InvalidException(error) => write!(f, "invalid exception: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::UserNamespace(error) => write!(f, "failed to create user namespace: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::IdMap { path, error } => write!(f, "failed to write ID map {path:?}: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::BindMount { path, target, error } => {
+                write!(f, "failed to bind mount {path:?} to {target:?}: {error}")
+            },
+            #[cfg(target_os = "linux")]
+            Self::Mount { path, error } => write!(f, "failed to mount {path:?}: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::Unmount { path, error } => write!(f, "failed to unmount {path:?}: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::CreateDir { path, error } => {
+                write!(f, "failed to create directory {path:?}: {error}")
+            },
+            #[cfg(target_os = "linux")]
+            Self::CreateNode { path, error } => write!(f, "failed to create {path:?}: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::MountSetattr { path, error } => {
+                write!(f, "failed to update mount attributes of {path:?}: {error}")
+            },
+            #[cfg(target_os = "linux")]
+            Self::PivotRoot { path, error } => {
+                write!(f, "failed to pivot root to {path:?}: {error}")
+            },
+            #[cfg(target_os = "linux")]
+            Self::Hostname(error) => write!(f, "failed to set hostname: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::Domainname(error) => write!(f, "failed to set domain name: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::TimeNamespace { path, error } => {
+                write!(f, "failed to setup time namespace with {path:?}: {error}")
+            },
+            #[cfg(target_os = "linux")]
+            Self::Loopback(error) => write!(f, "failed to bring up loopback interface: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::SeccompLoad(error) => write!(f, "failed to load seccomp filter: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::NoNewPrivs(error) => write!(f, "failed to set no_new_privs: {error}"),
+            #[cfg(target_os = "linux")]
+            Self::DroppedExceptions(dropped) => {
+                write!(f, "exceptions could not be applied: ")?;
+                for (i, exception) in dropped.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{exception}")?;
+                }
+                Ok(())
+            },
+            #[cfg(feature = "policy")]
+            Self::Policy(error) => write!(f, "invalid policy: {error}"),
         }
     }
 }
@@ -59,8 +248,195 @@ impl From<BackendError> for Error {
     }
 }
 
+#[cfg(feature = "policy")]
+impl From<PolicyError> for Error {
+    fn from(error: PolicyError) -> Self {
+        Self::Policy(error)
+    }
+}
+
 impl From<IoError> for Error {
     fn from(error: IoError) -> Self {
         Self::Io(error)
     }
 }
+
+/// Sandbox activation error which can be created without allocating.
+///
+/// Sandboxes are activated in forked children of processes which might have
+/// multiple threads, where allocating memory could deadlock. These errors only
+/// borrow their paths from the prepared sandbox and are converted to an
+/// [`Error`] once allocating is safe again.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RawError<'a> {
+    /// I/O error.
+    Io(i32),
+
+    /// Sandbox activation failed.
+    ActivationFailed(&'a str),
+
+    /// Invalid sandbox exception path.
+    InvalidPath(&'a CStr),
+
+    /// Creating a user namespace failed.
+    UserNamespace(i32),
+
+    /// Writing the UID/GID mappings of a user namespace failed.
+    IdMap { path: &'a CStr, errno: i32 },
+
+    /// Bind mounting a path into the sandbox failed.
+    BindMount { path: &'a CStr, target: &'a CStr, errno: i32 },
+
+    /// Mounting a new filesystem, like a tmpfs or procfs, failed.
+    Mount { path: &'a CStr, errno: i32 },
+
+    /// Unmounting a filesystem failed.
+    Unmount { path: &'a CStr, errno: i32 },
+
+    /// Creating a directory for the sandbox's filesystem failed.
+    CreateDir { path: &'a CStr, errno: i32 },
+
+    /// Replicating a file, directory or symlink of the host under the
+    /// sandbox's root failed.
+    CreateNode { path: &'a CStr, errno: i32 },
+
+    /// Updating the attributes of a mount with `mount_setattr` failed.
+    MountSetattr { path: &'a CStr, errno: i32 },
+
+    /// Changing the root directory failed.
+    PivotRoot { path: &'a CStr, errno: i32 },
+
+    /// Setting the hostname of the UTS namespace failed.
+    Hostname(i32),
+
+    /// Setting the NIS domain name of the UTS namespace failed.
+    Domainname(i32),
+
+    /// Setting the clock offsets of the time namespace or joining it failed.
+    TimeNamespace { path: &'a CStr, errno: i32 },
+
+    /// Bringing up the loopback interface of the network namespace failed.
+    Loopback(i32),
+
+    /// Loading the seccomp filter into the kernel failed.
+    SeccompLoad(i32),
+
+    /// Setting `PR_SET_NO_NEW_PRIVS` failed.
+    NoNewPrivs(i32),
+}
+
+#[cfg(target_os = "linux")]
+impl RawError<'_> {
+    /// Create an I/O error from the last OS error.
+    pub fn last_os_error() -> Self {
+        Self::from(IoError::last_os_error())
+    }
+
+    /// Get the OS error code of the failed operation.
+    pub fn errno(&self) -> Option<i32> {
+        match self {
+            Self::Io(errno)
+            | Self::UserNamespace(errno)
+            | Self::IdMap { errno, .. }
+            | Self::BindMount { errno, .. }
+            | Self::Mount { errno, .. }
+            | Self::Unmount { errno, .. }
+            | Self::CreateDir { errno, .. }
+            | Self::CreateNode { errno, .. }
+            | Self::MountSetattr { errno, .. }
+            | Self::PivotRoot { errno, .. }
+            | Self::Hostname(errno)
+            | Self::Domainname(errno)
+            | Self::TimeNamespace { errno, .. }
+            | Self::Loopback(errno)
+            | Self::SeccompLoad(errno)
+            | Self::NoNewPrivs(errno) => Some(*errno),
+            Self::ActivationFailed(_) | Self::InvalidPath(_) => None,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<IoError> for RawError<'_> {
+    fn from(error: IoError) -> Self {
+        Self::Io(errno(&error))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<RawError<'_>> for Error {
+    fn from(error: RawError<'_>) -> Self {
+        let path = |path: &CStr| PathBuf::from(OsStr::from_bytes(path.to_bytes()));
+        let io = IoError::from_raw_os_error;
+        match error {
+            RawError::Io(errno) => Self::Io(io(errno)),
+            RawError::ActivationFailed(message) => Self::ActivationFailed(message.into()),
+            RawError::InvalidPath(invalid) => Self::InvalidPath(path(invalid)),
+            RawError::UserNamespace(errno) => Self::UserNamespace(io(errno)),
+            RawError::IdMap { path: map, errno } => {
+                Self::IdMap { path: path(map), error: io(errno) }
+            },
+            RawError::BindMount { path: source, target, errno } => {
+                Self::BindMount { path: path(source), target: path(target), error: io(errno) }
+            },
+            RawError::Mount { path: mount, errno } => {
+                Self::Mount { path: path(mount), error: io(errno) }
+            },
+            RawError::Unmount { path: mount, errno } => {
+                Self::Unmount { path: path(mount), error: io(errno) }
+            },
+            RawError::CreateDir { path: dir, errno } => {
+                Self::CreateDir { path: path(dir), error: io(errno) }
+            },
+            RawError::CreateNode { path: node, errno } => {
+                Self::CreateNode { path: path(node), error: io(errno) }
+            },
+            RawError::MountSetattr { path: mount, errno } => {
+                Self::MountSetattr { path: path(mount), error: io(errno) }
+            },
+            RawError::PivotRoot { path: new_root, errno } => {
+                Self::PivotRoot { path: path(new_root), error: io(errno) }
+            },
+            RawError::Hostname(errno) => Self::Hostname(io(errno)),
+            RawError::Domainname(errno) => Self::Domainname(io(errno)),
+            RawError::TimeNamespace { path: file, errno } => {
+                Self::TimeNamespace { path: path(file), error: io(errno) }
+            },
+            RawError::Loopback(errno) => Self::Loopback(io(errno)),
+            RawError::SeccompLoad(errno) => Self::SeccompLoad(io(errno)),
+            RawError::NoNewPrivs(errno) => Self::NoNewPrivs(io(errno)),
+        }
+    }
+}
+
+/// Convert an activation error without allocating.
+///
+/// Only the OS error code is preserved, errors without one are reported as
+/// `EINVAL`.
+#[cfg(target_os = "linux")]
+impl From<RawError<'_>> for IoError {
+    fn from(error: RawError<'_>) -> Self {
+        IoError::from_raw_os_error(error.errno().unwrap_or(libc::EINVAL))
+    }
+}
+
+/// Get the OS error code of an I/O error.
+#[cfg(target_os = "linux")]
+pub(crate) fn errno(error: &IoError) -> i32 {
+    error.raw_os_error().unwrap_or(libc::EIO)
+}
+
+impl From<Error> for IoError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(error) => error,
+            error => {
+                let kind = error
+                    .errno()
+                    .map_or(ErrorKind::Other, |errno| IoError::from_raw_os_error(errno).kind());
+                IoError::new(kind, error.to_string())
+            },
+        }
+    }
+}
@@ -26,19 +26,32 @@
 //! ```
 
 use std::env;
+use std::ffi::OsStr;
 use std::path::PathBuf;
+use std::process::{Child, Command};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::error::Result;
 #[cfg(target_os = "linux")]
 use crate::linux::LinuxSandbox;
+#[cfg(target_os = "linux")]
+pub use crate::linux::{audit, learn, overlay, probe, Clock};
 #[cfg(target_os = "macos")]
 use crate::macos::MacSandbox;
+use crate::report::Report;
 
+#[cfg(feature = "serde")]
+pub mod config;
 pub mod error;
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
+#[cfg(feature = "policy")]
+pub mod policy;
+pub mod report;
 
 /// Default platform sandbox.
 ///
@@ -54,6 +67,18 @@ pub type Birdcage = LinuxSandbox;
 #[cfg(target_os = "macos")]
 pub type Birdcage = MacSandbox;
 
+/// Sandbox prepared for activation.
+///
+/// This is the result of [`Sandbox::prepare`] for the default platform sandbox.
+#[cfg(target_os = "linux")]
+pub type PreparedSandbox = crate::linux::PreparedSandbox;
+
+/// Sandbox prepared for activation.
+///
+/// This is the result of [`Sandbox::prepare`] for the default platform sandbox.
+#[cfg(target_os = "macos")]
+pub type PreparedSandbox = crate::macos::PreparedSandbox;
+
 pub trait Sandbox: Sized {
     /// Setup the sandboxing environment.
     fn new() -> Self;
@@ -62,14 +87,49 @@ pub trait Sandbox: Sized {
     ///
     /// This exception opens up the sandbox to allow access for the specified
     /// operation. Once an exception is added, it is **not** possible to
-    /// prohibit access to this resource without creating a new sandbox.
+    /// prohibit access to this resource without creating a new sandbox, other
+    /// than hiding parts of it using [`Exception::Deny`].
     ///
     /// Exceptions added for symlinks will also automatically apply to the
     /// symlink's target.
+    ///
+    /// On Linux, exceptions for different paths resolving to the same target
+    /// are merged: their permissions are combined, [`Exception::Deny`] for
+    /// any of the paths denies access to all of them, and other exceptions
+    /// like [`Exception::Tmpfs`] replace plain access exceptions.
     fn add_exception(&mut self, exception: Exception) -> Result<&mut Self>;
 
+    /// Change how sandboxing layers unsupported by the host are handled.
+    ///
+    /// By default, the sandbox uses [`Strictness::Strict`].
+    fn strictness(&mut self, strictness: Strictness) -> &mut Self;
+
+    /// Prepare the sandbox for activation.
+    ///
+    /// This performs all work which does not modify the current process, like
+    /// resolving exception paths, compiling system call filters, and checking
+    /// which sandboxing layers are supported by the host. Most errors are
+    /// reported at this stage, while the process is still unrestricted.
+    ///
+    /// The returned [`PreparedSandbox`] only has to perform the remaining
+    /// irreversible steps when calling [`PreparedSandbox::commit`].
+    fn prepare(self) -> Result<PreparedSandbox>;
+
     /// Apply the sandbox restrictions to the current process.
     ///
+    /// To sandbox only a child process, use [`Sandbox::spawn`] instead.
+    ///
+    /// This is equivalent to calling [`Sandbox::prepare`] followed by
+    /// [`PreparedSandbox::commit`].
+    ///
+    /// The returned [`Report`] lists all sandboxing layers which were applied
+    /// and all layers which had to be skipped. Exceptions whose path no longer
+    /// exists are dropped and listed in the report as well.
+    ///
+    /// Isolating processes with a PID namespace requires a new child process,
+    /// so on Linux the PID namespace layer is always skipped by this function.
+    /// Use [`Sandbox::spawn`] to isolate processes.
+    ///
     /// # Errors
     ///
     /// Sandboxing will fail if the calling process is not single-threaded.
@@ -78,15 +138,68 @@ pub trait Sandbox: Sized {
     /// that after a failure some restrictions are still applied. While this
     /// never allows the process to do things it wasn't capable of doing
     /// before, it is still recommended to abort the sandboxing process if
-    /// you want to continue operations without a sandbox in place.
-    fn lock(self) -> Result<()>;
+    /// you want to continue operations without a sandbox in place. Use
+    /// [`Sandbox::prepare`] to handle most errors before the process is
+    /// modified.
+    fn lock(self) -> Result<Report> {
+        self.prepare()?.commit()
+    }
+
+    /// Spawn a new child process with the sandbox restrictions applied.
+    ///
+    /// The restrictions are only applied to the spawned child, the calling
+    /// process is not sandboxed and may have any number of threads.
+    ///
+    /// The returned [`Report`] lists the sandboxing layers applied to the
+    /// child, like the report of [`Sandbox::lock`].
+    ///
+    /// On Linux, the child is additionally isolated in a PID namespace, with a
+    /// minimal init process reaping zombies and forwarding signals.
+    ///
+    /// # Errors
+    ///
+    /// If the sandbox could not be applied to the child, the error is reported
+    /// the same way as any other failure to spawn the `sandboxee`.
+    fn spawn(self, sandboxee: Command) -> Result<(Child, Report)>;
+}
+
+/// Handling of sandboxing layers which are not supported by the host.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "snake_case"))]
+pub enum Strictness {
+    /// Fail if any layer required to enforce the sandbox is unavailable.
+    ///
+    /// Layers which only duplicate restrictions already enforced by another
+    /// layer are still skipped if the host does not support them.
+    #[default]
+    Strict,
+
+    /// Apply all layers which are available, skipping the others.
+    ///
+    /// This may leave the process without any restrictions. Check the
+    /// [`Report`] returned by [`Sandbox::lock`] to see which layers were
+    /// skipped.
+    BestEffort,
+}
+
+/// Access granted by an [`Exception::Bind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "snake_case"))]
+pub enum BindAccess {
+    /// Allow read access, like [`Exception::Read`].
+    Read,
+    /// Allow writing and reading, like [`Exception::WriteAndRead`].
+    WriteAndRead,
+    /// Allow executing and reading, like [`Exception::ExecuteAndRead`].
+    ExecuteAndRead,
 }
 
 /// Sandboxing exception rule.
 ///
 /// An exception excludes certain resources from the sandbox, allowing sandboxed
 /// applications to still access these resources.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "snake_case"))]
 pub enum Exception {
     /// Allow read access to the path and anything beneath it.
     Read(PathBuf),
@@ -100,6 +213,100 @@ pub enum Exception {
     /// always also require read access.
     ExecuteAndRead(PathBuf),
 
+    /// Allow read access to the entire filesystem.
+    ///
+    /// Pseudo-filesystems like `/dev` and `/sys` are excluded, unless they are
+    /// allowed by a separate exception. Other exceptions can be used to grant
+    /// additional permissions for parts of the filesystem.
+    ReadAll,
+
+    /// Deny all access to the path and anything beneath it.
+    ///
+    /// This masks the path inside of a directory which was made accessible by
+    /// another exception. Exceptions for paths beneath the denied path can be
+    /// used to grant access to parts of it again.
+    ///
+    /// The denial always overrides exceptions for its ancestors, while
+    /// exceptions beneath it always override the denial, independent of the
+    /// order exceptions are added in.
+    Deny(PathBuf),
+
+    /// Mount an empty, writable tmpfs at the path.
+    ///
+    /// The tmpfs hides the path's original content and is discarded once the
+    /// sandbox exits. Its `size` is limited to the specified number of bytes,
+    /// which must not be zero, while `mode` sets the permissions of its root
+    /// directory. Executing files inside the tmpfs is not allowed.
+    ///
+    /// The path must be an existing directory. Exceptions beneath the path
+    /// are mounted on top of the tmpfs.
+    ///
+    /// This requires a mount namespace and is not supported on macOS.
+    Tmpfs { path: PathBuf, size: u64, mode: u32 },
+
+    /// Allow writing to the path through a copy-on-write overlay.
+    ///
+    /// Reads see the content of the path, while all writes are redirected to
+    /// a separate upper directory, leaving the original files untouched.
+    /// Executing files is only allowed with an additional
+    /// [`Exception::ExecuteAndRead`] for the path.
+    ///
+    /// Changes are stored in the `changes` directory, where they can be
+    /// reviewed and applied with `overlay::ChangeSet` once the sandbox exited.
+    /// Without a `changes` directory, all writes are discarded with the
+    /// sandbox.
+    ///
+    /// Both directories must not contain `,` or `:`. This requires a mount
+    /// namespace and Linux 5.11 or newer, and is not supported on macOS.
+    Overlay { path: PathBuf, changes: Option<PathBuf> },
+
+    /// Allow reading, writing and controlling the device node at the path.
+    ///
+    /// On Linux, devices cannot be opened through other exceptions. Instead,
+    /// a minimal `/dev` with `null`, `zero`, `full`, `random`, `urandom`,
+    /// `tty` and a private `/dev/pts` is always available when a mount
+    /// namespace could be created.
+    Device(PathBuf),
+
+    /// Make the `source` path available at a different `target` path.
+    ///
+    /// The `target` must be absolute and does not need to exist on the host.
+    /// Missing parent directories of the `target` are created inside the
+    /// sandbox, which is not possible beneath the path of another exception
+    /// like [`Exception::ReadAll`].
+    ///
+    /// This requires a mount namespace and is not supported on macOS.
+    Bind { source: PathBuf, target: PathBuf, access: BindAccess },
+
+    /// Create a file with the specified `contents` and `mode` at the path.
+    ///
+    /// The file only exists inside the sandbox, hiding the host's file at the
+    /// same path, and all changes to it are discarded once the sandbox exits.
+    /// The path must be absolute, missing parent directories are created like
+    /// for [`Exception::Bind`].
+    ///
+    /// On Linux, `Exception::passwd` and `Exception::group` create files with
+    /// entries for the sandboxed user.
+    ///
+    /// This requires a mount namespace and is not supported on macOS.
+    File { path: PathBuf, contents: Vec<u8>, mode: u32 },
+
+    /// Replace the home directory with an empty, writable directory.
+    ///
+    /// The home directory is an empty tmpfs limited to 1 GiB and discarded
+    /// once the sandbox exits, or the `persistent` directory if one is
+    /// specified. Exceptions beneath the home directory are mounted on top of
+    /// it, which allows passing selected parts of the real home directory
+    /// through, like `~/.cargo/registry`. Nothing is created inside a
+    /// `persistent` directory, so their paths must already exist in it.
+    ///
+    /// The home directory is taken from the `HOME` environment variable, which
+    /// is kept inside the sandbox. Executing files inside the home directory
+    /// is only allowed through other exceptions.
+    ///
+    /// This requires a mount namespace and is not supported on macOS.
+    VirtualHome { persistent: Option<PathBuf> },
+
     /// Allow reading an environment variable.
     Environment(String),
 
@@ -108,6 +315,44 @@ pub enum Exception {
 
     /// Allow networking.
     Networking,
+
+    /// Allow networking over the loopback interface.
+    ///
+    /// Sandboxed processes can communicate with each other using local
+    /// sockets, without gaining access to the host's network.
+    ///
+    /// On Linux, this requires a network namespace. Without one, activation
+    /// fails in [`Strictness::Strict`] mode, while [`Strictness::BestEffort`]
+    /// blocks loopback networking together with all other network access.
+    LoopbackNetworking,
+
+    /// Allow network connections to a specific host and port.
+    ///
+    /// Direct network access stays blocked. Instead, the parent process runs
+    /// an HTTP CONNECT proxy which only forwards connections to allowed
+    /// destinations. The proxy's Unix socket path is exposed to the sandboxee
+    /// through the `BIRDCAGE_PROXY_SOCKET` environment variable.
+    ///
+    /// This is only supported by [`Sandbox::spawn`] on Linux.
+    NetworkHost { host: String, port: u16 },
+
+    /// Allow access to the host's System V IPC objects and POSIX message
+    /// queues.
+    ///
+    /// On Linux, IPC is isolated with an IPC namespace by default. Without
+    /// this exception, activation fails in [`Strictness::Strict`] mode if the
+    /// IPC namespace cannot be created. Opening the host's POSIX message
+    /// queues additionally requires a filesystem exception for `/dev/mqueue`.
+    /// On macOS, IPC is always allowed.
+    HostIpc,
+
+    /// Expose all procfs entries instead of only processes.
+    ///
+    /// On Linux, the procfs of a [`Sandbox::spawn`]ed sandbox only contains
+    /// the sandboxed processes by default. This adds system-wide entries like
+    /// `/proc/cpuinfo`, while kernel settings stay read-only and entries
+    /// like `/proc/kcore` are masked. On macOS, this has no effect.
+    FullProcfs,
 }
 
 /// Restrict access to environment variables.
@@ -118,3 +363,23 @@ pub(crate) fn restrict_env_variables(exceptions: &[String]) {
         env::remove_var(key);
     }
 }
+
+/// Restrict access to environment variables for a child process.
+///
+/// This removes all variables from `command` which are not part of
+/// `exceptions`, including variables explicitly set on the command.
+pub(crate) fn restrict_command_env(command: &mut Command, exceptions: &[String]) {
+    let is_exception =
+        |key: &OsStr| exceptions.iter().any(|exception| key == OsStr::new(exception));
+
+    let inherited = env::vars_os().map(|(key, _)| key);
+    let explicit = command
+        .get_envs()
+        .filter(|(_, value)| value.is_some())
+        .map(|(key, _)| key.to_os_string())
+        .collect::<Vec<_>>();
+
+    for key in inherited.chain(explicit).filter(|key| !is_exception(key)) {
+        command.env_remove(key);
+    }
+}
@@ -0,0 +1,645 @@
+//! Audit mode reporting sandbox violations.
+//!
+//! In audit mode the sandboxee is not restricted. Instead, syscalls which
+//! could violate the sandbox are suspended using seccomp user notifications,
+//! until a supervisor thread in the parent process has checked them against
+//! the sandbox exceptions and reported any violations.
+
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Error as IoError, Read, Seek, SeekFrom};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::os::raw::{c_int, c_long};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::{iter, mem, ptr, thread};
+
+use crate::linux::namespaces::{
+    self, Mount, MountAttrFlags, Mounts, NamespaceConfig, IPC_FILESYSTEMS,
+};
+
+/// Receive a seccomp user notification.
+const SECCOMP_IOCTL_NOTIF_RECV: u64 = 0xc050_2100;
+
+/// Respond to a seccomp user notification.
+const SECCOMP_IOCTL_NOTIF_SEND: u64 = 0xc018_2101;
+
+/// Check if a seccomp user notification is still valid.
+const SECCOMP_IOCTL_NOTIF_ID_VALID: u64 = 0x8008_2102;
+
+/// Continue the suspended syscall without modification.
+const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+
+/// Maximum length of paths read from the sandboxee's memory.
+const PATH_MAX: usize = libc::PATH_MAX as usize;
+
+/// Size of memory chunks which never cross a page boundary.
+const CHUNK_SIZE: usize = 4096;
+
+/// ELF class for 64-bit objects.
+const ELFCLASS64: u8 = 2;
+
+/// ELF data encoding for little-endian objects.
+const ELFDATA2LSB: u8 = 1;
+
+/// ELF program header type for the program interpreter.
+const PT_INTERP: u32 = 3;
+
+/// Sandbox violation observed in audit mode.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Violation {
+    /// Filesystem access which is not covered by any exception.
+    Path {
+        /// Thread ID of the offending process.
+        pid: u32,
+        /// Absolute path which was accessed, without resolving symlinks.
+        path: PathBuf,
+        /// Kind of access.
+        access: Access,
+    },
+
+    /// System call which is blocked by the seccomp filter.
+    Syscall {
+        /// Thread ID of the offending process.
+        pid: u32,
+        /// Architecture-specific system call number.
+        syscall: i64,
+    },
+
+    /// Network access which is not covered by any exception.
+    Network {
+        /// Thread ID of the offending process.
+        pid: u32,
+        /// Address which was connected to or bound.
+        address: SocketAddr,
+    },
+}
+
+/// Kind of filesystem access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Access {
+    /// Read a file or list a directory.
+    Read,
+    /// Create, modify, or remove a file or directory.
+    Write,
+    /// Execute a file.
+    Execute,
+}
+
+/// Sandbox exceptions checked by the audit supervisor.
+pub(crate) struct Policy {
+    mounts: Mounts,
+    namespaces: NamespaceConfig,
+    network_hosts: Vec<SocketAddr>,
+}
+
+impl Policy {
+    /// Create the policy for a sandbox.
+    ///
+    /// The `network_hosts` are resolved immediately, connections to them are
+    /// allowed since the sandbox would forward them through its egress proxy.
+    pub(crate) fn new(
+        mounts: Mounts,
+        namespaces: NamespaceConfig,
+        network_hosts: &[(String, u16)],
+    ) -> Self {
+        let network_hosts = network_hosts
+            .iter()
+            .filter_map(|(host, port)| (host.as_str(), *port).to_socket_addrs().ok())
+            .flatten()
+            .collect();
+
+        Self { mounts, namespaces, network_hosts }
+    }
+
+    /// Check if the sandbox allows accessing a path.
+    fn allows_path(&self, path: &Path, access: Access) -> bool {
+        let path = canonicalize(path);
+
+        // Mounts are sorted by depth, so the last match is the most specific.
+        let mount =
+            self.mounts.iter().filter(|(mount_path, _)| path.starts_with(mount_path)).last();
+
+        let flags = match mount {
+            // Private IPC filesystems are mounted on top of all other mounts.
+            _ if !self.namespaces.allow_host_ipc
+                && IPC_FILESYSTEMS.iter().any(|fs| path.starts_with(fs)) =>
+            {
+                MountAttrFlags::NOEXEC
+            },
+            // Default devices are mounted on top of all other mounts.
+            _ if namespaces::is_default_device(&path) => MountAttrFlags::NOEXEC,
+            Some((_, Mount::Bind(flags) | Mount::Remap { flags, .. })) => *flags,
+            Some((_, Mount::Tmpfs { .. })) => MountAttrFlags::NOEXEC,
+            Some((_, Mount::Overlay { flags, .. })) => *flags,
+            Some((_, Mount::Device)) => MountAttrFlags::NOEXEC,
+            Some((_, Mount::File { mode, .. })) if mode & 0o111 == 0 => MountAttrFlags::NOEXEC,
+            Some((_, Mount::File { .. })) => MountAttrFlags::empty(),
+            Some((_, Mount::Deny)) => return false,
+            // Procfs is always mounted inside the sandbox.
+            None if path.starts_with("/proc") => MountAttrFlags::RDONLY | MountAttrFlags::NOEXEC,
+            None => return false,
+        };
+
+        match access {
+            Access::Read => true,
+            Access::Write => !flags.contains(MountAttrFlags::RDONLY),
+            Access::Execute => !flags.contains(MountAttrFlags::NOEXEC),
+        }
+    }
+
+    /// Check if the sandbox allows connecting to or binding an address.
+    fn allows_network(&self, address: &SocketAddr, bind: bool) -> bool {
+        let namespaces = &self.namespaces;
+        namespaces.allow_networking
+            || (namespaces.allow_loopback && (bind || address.ip().is_loopback()))
+            || (!bind && self.network_hosts.contains(address))
+    }
+}
+
+/// Start the audit supervisor.
+///
+/// The supervisor waits for the sandboxee to send its seccomp listener over
+/// `socket`, then reports violations to `events` until the sandboxee and all
+/// its children have exited.
+pub(crate) fn supervise(socket: UnixStream, policy: Policy, events: Sender<Violation>) {
+    thread::spawn(move || {
+        // Abort if the sandboxee failed before sending its listener.
+        let listener = match recv_fd(&socket) {
+            Ok(listener) => listener,
+            Err(_) => return,
+        };
+        drop(socket);
+
+        let supervisor = Supervisor { listener, policy, events };
+        supervisor.run();
+    });
+}
+
+/// Send the seccomp listener to the audit supervisor.
+///
+/// This is called by the sandboxee after installing the audit filter.
+pub(crate) fn send_listener(socket: &UnixStream, listener: OwnedFd) -> io::Result<()> {
+    let mut data = [0u8; 1];
+    let mut iov = libc::iovec { iov_base: data.as_mut_ptr().cast(), iov_len: data.len() };
+    let mut control = [0u64; 4];
+
+    unsafe {
+        let mut message: libc::msghdr = mem::zeroed();
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr().cast();
+        message.msg_controllen = libc::CMSG_SPACE(mem::size_of::<c_int>() as u32) as _;
+
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(mem::size_of::<c_int>() as u32) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(header).cast(), listener.as_raw_fd());
+
+        if libc::sendmsg(socket.as_raw_fd(), &message, 0) < 0 {
+            return Err(IoError::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Receive a file descriptor sent with [`send_listener`].
+fn recv_fd(socket: &UnixStream) -> io::Result<OwnedFd> {
+    let mut data = [0u8; 1];
+    let mut iov = libc::iovec { iov_base: data.as_mut_ptr().cast(), iov_len: data.len() };
+    let mut control = [0u64; 4];
+
+    unsafe {
+        let mut message: libc::msghdr = mem::zeroed();
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr().cast();
+        message.msg_controllen = mem::size_of_val(&control) as _;
+
+        let received = libc::recvmsg(socket.as_raw_fd(), &mut message, libc::MSG_CMSG_CLOEXEC);
+        if received < 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        let header = libc::CMSG_FIRSTHDR(&message);
+        if header.is_null()
+            || (*header).cmsg_level != libc::SOL_SOCKET
+            || (*header).cmsg_type != libc::SCM_RIGHTS
+        {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let fd: RawFd = ptr::read_unaligned(libc::CMSG_DATA(header).cast());
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+}
+
+/// Seccomp user notification handler.
+struct Supervisor {
+    listener: OwnedFd,
+    policy: Policy,
+    events: Sender<Violation>,
+}
+
+impl Supervisor {
+    /// Handle notifications until all sandboxed processes have exited.
+    fn run(&self) {
+        while let Ok(true) = self.wait() {
+            let mut notification: SeccompNotif = unsafe { mem::zeroed() };
+            let result = unsafe {
+                libc::ioctl(
+                    self.listener.as_raw_fd(),
+                    SECCOMP_IOCTL_NOTIF_RECV as _,
+                    &mut notification as *mut SeccompNotif,
+                )
+            };
+            if result < 0 {
+                // Syscalls can be interrupted before they have been received.
+                match IoError::last_os_error().raw_os_error() {
+                    Some(libc::EINTR | libc::ENOENT) => continue,
+                    _ => break,
+                }
+            }
+
+            let violations = self.check(&notification);
+
+            // Ignore violations if the process was replaced while reading its memory.
+            if self.is_valid(notification.id) {
+                for violation in violations {
+                    let _ = self.events.send(violation);
+                }
+            }
+
+            // Resume the syscall, ignoring processes which have been killed.
+            let response = SeccompNotifResp {
+                id: notification.id,
+                val: 0,
+                error: 0,
+                flags: SECCOMP_USER_NOTIF_FLAG_CONTINUE,
+            };
+            unsafe {
+                libc::ioctl(
+                    self.listener.as_raw_fd(),
+                    SECCOMP_IOCTL_NOTIF_SEND as _,
+                    &response as *const SeccompNotifResp,
+                )
+            };
+        }
+    }
+
+    /// Wait for the next notification.
+    ///
+    /// Returns `false` once no process is using the filter anymore.
+    fn wait(&self) -> io::Result<bool> {
+        let mut fds =
+            [libc::pollfd { fd: self.listener.as_raw_fd(), events: libc::POLLIN, revents: 0 }];
+
+        loop {
+            let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, -1) };
+            if result >= 0 {
+                return Ok(fds[0].revents & libc::POLLIN != 0);
+            }
+
+            let error = IoError::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+
+    /// Check if a notification's process is still waiting for a response.
+    fn is_valid(&self, id: u64) -> bool {
+        let result = unsafe {
+            libc::ioctl(
+                self.listener.as_raw_fd(),
+                SECCOMP_IOCTL_NOTIF_ID_VALID as _,
+                &id as *const u64,
+            )
+        };
+        result == 0
+    }
+
+    /// Find all violations caused by a syscall.
+    fn check(&self, notification: &SeccompNotif) -> Vec<Violation> {
+        let pid = notification.pid;
+        let args = &notification.data.args;
+        let cwd = libc::AT_FDCWD as u64;
+
+        let mut violations = Vec::new();
+        let mut path = |dirfd: u64, path: u64, access: Access| {
+            let path = match read_path(pid, dirfd, path) {
+                Some(path) => path,
+                None => return,
+            };
+
+            // Executing dynamically linked binaries also executes their interpreter.
+            let interpreter = if access == Access::Execute { elf_interpreter(&path) } else { None };
+
+            for path in iter::once(path).chain(interpreter) {
+                if !self.policy.allows_path(&path, access) {
+                    violations.push(Violation::Path { pid, path, access });
+                }
+            }
+        };
+
+        match notification.data.nr as c_long {
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_open => path(cwd, args[0], open_access(args[1])),
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_creat => path(cwd, args[0], Access::Write),
+            libc::SYS_openat => path(args[0], args[1], open_access(args[2])),
+            libc::SYS_openat2 => {
+                // The flags are the first field of `struct open_how`.
+                let mut flags = [0; 8];
+                if read_memory(pid, args[2], &mut flags) {
+                    path(args[0], args[1], open_access(u64::from_ne_bytes(flags)));
+                }
+            },
+            libc::SYS_execve => path(cwd, args[0], Access::Execute),
+            libc::SYS_execveat => path(args[0], args[1], Access::Execute),
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_mkdir | libc::SYS_unlink | libc::SYS_rmdir => {
+                path(cwd, args[0], Access::Write)
+            },
+            libc::SYS_mkdirat | libc::SYS_unlinkat => path(args[0], args[1], Access::Write),
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_rename => {
+                path(cwd, args[0], Access::Write);
+                path(cwd, args[1], Access::Write);
+            },
+            libc::SYS_renameat | libc::SYS_renameat2 => {
+                path(args[0], args[1], Access::Write);
+                path(args[2], args[3], Access::Write);
+            },
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_link => {
+                path(cwd, args[0], Access::Read);
+                path(cwd, args[1], Access::Write);
+            },
+            libc::SYS_linkat => {
+                path(args[0], args[1], Access::Read);
+                path(args[2], args[3], Access::Write);
+            },
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_symlink => path(cwd, args[1], Access::Write),
+            libc::SYS_symlinkat => path(args[1], args[2], Access::Write),
+            libc::SYS_truncate => path(cwd, args[0], Access::Write),
+            libc::SYS_ftruncate => self.check_fd(&mut violations, pid, args[0], Access::Write),
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_chmod | libc::SYS_chown | libc::SYS_lchown | libc::SYS_mknod => {
+                path(cwd, args[0], Access::Write)
+            },
+            libc::SYS_setxattr | libc::SYS_lsetxattr => path(cwd, args[0], Access::Write),
+            libc::SYS_fchmodat | libc::SYS_fchownat | libc::SYS_mknodat => {
+                path(args[0], args[1], Access::Write)
+            },
+            // Without a path, `utimensat` updates the file descriptor itself.
+            libc::SYS_utimensat if args[1] == 0 => {
+                self.check_fd(&mut violations, pid, args[0], Access::Write)
+            },
+            libc::SYS_utimensat => path(args[0], args[1], Access::Write),
+            libc::SYS_connect => self.check_network(&mut violations, pid, args[1], args[2], false),
+            libc::SYS_bind => self.check_network(&mut violations, pid, args[1], args[2], true),
+            // Only `sendto` calls with a destination address establish connections.
+            libc::SYS_sendto if args[4] != 0 => {
+                self.check_network(&mut violations, pid, args[4], args[5], false)
+            },
+            libc::SYS_sendto => (),
+            // Only executable mappings of files are audited.
+            libc::SYS_mmap if args[3] & libc::MAP_ANONYMOUS as u64 == 0 => {
+                let fd = args[4] as c_int;
+                if let Ok(path) = fs::read_link(format!("/proc/{pid}/fd/{fd}")) {
+                    if !self.policy.allows_path(&path, Access::Execute) {
+                        violations.push(Violation::Path { pid, path, access: Access::Execute });
+                    }
+                }
+            },
+            libc::SYS_mmap => (),
+            syscall => violations.push(Violation::Syscall { pid, syscall }),
+        }
+
+        violations
+    }
+
+    /// Check if accessing the file behind a file descriptor is a violation.
+    ///
+    /// Anonymous and deleted files are ignored, since they cannot be accessed
+    /// through their path.
+    fn check_fd(&self, violations: &mut Vec<Violation>, pid: u32, fd: u64, access: Access) {
+        let path = match fs::read_link(format!("/proc/{pid}/fd/{}", fd as c_int)) {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        if !path.is_absolute() || path.as_os_str().as_bytes().ends_with(b" (deleted)") {
+            return;
+        }
+
+        if !self.policy.allows_path(&path, access) {
+            violations.push(Violation::Path { pid, path, access });
+        }
+    }
+
+    /// Check if accessing a socket address is a violation.
+    fn check_network(
+        &self,
+        violations: &mut Vec<Violation>,
+        pid: u32,
+        address: u64,
+        len: u64,
+        bind: bool,
+    ) {
+        if let Some(address) = read_socket_addr(pid, address, len) {
+            if !self.policy.allows_network(&address, bind) {
+                violations.push(Violation::Network { pid, address });
+            }
+        }
+    }
+}
+
+/// Get the access kind for `open` flags.
+fn open_access(flags: u64) -> Access {
+    let flags = flags as c_int;
+    if flags & libc::O_ACCMODE != libc::O_RDONLY || flags & (libc::O_CREAT | libc::O_TRUNC) != 0 {
+        Access::Write
+    } else {
+        Access::Read
+    }
+}
+
+/// Read a path from the memory of process `pid`.
+///
+/// Relative paths are resolved based on the directory `dirfd` of the process.
+fn read_path(pid: u32, dirfd: u64, address: u64) -> Option<PathBuf> {
+    let path = PathBuf::from(OsString::from_vec(read_string(pid, address)?));
+
+    // Resolve relative paths using the process' working directory or `dirfd`.
+    let path = if path.is_absolute() {
+        path
+    } else {
+        let dirfd = dirfd as c_int;
+        let base = if dirfd == libc::AT_FDCWD {
+            fs::read_link(format!("/proc/{pid}/cwd")).ok()?
+        } else {
+            fs::read_link(format!("/proc/{pid}/fd/{dirfd}")).ok()?
+        };
+
+        // Empty paths refer to `dirfd` itself.
+        if path.as_os_str().is_empty() {
+            base
+        } else {
+            base.join(path)
+        }
+    };
+
+    Some(namespaces::normalize_path(&path))
+}
+
+/// Resolve all symlinks in an absolute path.
+///
+/// For paths which do not exist yet, only the parent directory is resolved.
+fn canonicalize(path: &Path) -> PathBuf {
+    if let Ok(canonicalized) = path.canonicalize() {
+        return canonicalized;
+    }
+
+    let parent = path.parent().and_then(|parent| parent.canonicalize().ok());
+    match (parent, path.file_name()) {
+        (Some(parent), Some(file_name)) => parent.join(file_name),
+        _ => path.into(),
+    }
+}
+
+/// Get the program interpreter of an ELF executable.
+fn elf_interpreter(path: &Path) -> Option<PathBuf> {
+    let mut file = File::open(path).ok()?;
+
+    // Only 64-bit little-endian executables are supported.
+    let mut header = [0; 64];
+    file.read_exact(&mut header).ok()?;
+    if header[..4] != *b"\x7fELF" || header[4] != ELFCLASS64 || header[5] != ELFDATA2LSB {
+        return None;
+    }
+
+    let program_headers = u64::from_le_bytes(header[0x20..0x28].try_into().ok()?);
+    let entry_size = u16::from_le_bytes([header[0x36], header[0x37]]) as u64;
+    let entries = u16::from_le_bytes([header[0x38], header[0x39]]) as u64;
+
+    for index in 0..entries {
+        let mut entry = [0; 0x28];
+        file.seek(SeekFrom::Start(program_headers + index * entry_size)).ok()?;
+        file.read_exact(&mut entry).ok()?;
+
+        if u32::from_le_bytes(entry[..4].try_into().ok()?) != PT_INTERP {
+            continue;
+        }
+
+        let offset = u64::from_le_bytes(entry[0x08..0x10].try_into().ok()?);
+        let size = u64::from_le_bytes(entry[0x20..0x28].try_into().ok()?);
+        if size as usize > PATH_MAX {
+            return None;
+        }
+
+        let mut interpreter = vec![0; size as usize];
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        file.read_exact(&mut interpreter).ok()?;
+
+        // Strip the NUL terminator.
+        let end = interpreter.iter().position(|byte| *byte == 0).unwrap_or(interpreter.len());
+        interpreter.truncate(end);
+
+        return Some(PathBuf::from(OsString::from_vec(interpreter)));
+    }
+
+    None
+}
+
+/// Read a NUL-terminated string from the memory of process `pid`.
+fn read_string(pid: u32, mut address: u64) -> Option<Vec<u8>> {
+    let mut string = Vec::new();
+
+    while string.len() < PATH_MAX {
+        // Read only until the next page, since it might not be mapped.
+        let len = CHUNK_SIZE - (address as usize % CHUNK_SIZE);
+        let mut chunk = vec![0; len];
+        if !read_memory(pid, address, &mut chunk) {
+            return None;
+        }
+
+        match chunk.iter().position(|byte| *byte == 0) {
+            Some(end) => {
+                string.extend_from_slice(&chunk[..end]);
+                return Some(string);
+            },
+            None => string.extend_from_slice(&chunk),
+        }
+
+        address += len as u64;
+    }
+
+    None
+}
+
+/// Read an IP socket address from the memory of process `pid`.
+fn read_socket_addr(pid: u32, address: u64, len: u64) -> Option<SocketAddr> {
+    let mut buffer = [0; mem::size_of::<libc::sockaddr_in6>()];
+    let len = (len as usize).min(buffer.len());
+    if len < mem::size_of::<u16>() || !read_memory(pid, address, &mut buffer[..len]) {
+        return None;
+    }
+
+    let family = u16::from_ne_bytes([buffer[0], buffer[1]]);
+    let port = u16::from_be_bytes([buffer[2], buffer[3]]);
+    match c_int::from(family) {
+        libc::AF_INET if len >= mem::size_of::<libc::sockaddr_in>() => {
+            let ip = Ipv4Addr::new(buffer[4], buffer[5], buffer[6], buffer[7]);
+            Some(SocketAddr::new(IpAddr::V4(ip), port))
+        },
+        libc::AF_INET6 if len >= mem::size_of::<libc::sockaddr_in6>() => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&buffer[8..24]);
+            Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port))
+        },
+        _ => None,
+    }
+}
+
+/// Fill `buffer` from the memory of process `pid`.
+fn read_memory(pid: u32, address: u64, buffer: &mut [u8]) -> bool {
+    let local = libc::iovec { iov_base: buffer.as_mut_ptr().cast(), iov_len: buffer.len() };
+    let remote = libc::iovec { iov_base: address as *mut _, iov_len: buffer.len() };
+
+    let read = unsafe { libc::process_vm_readv(pid as i32, &local, 1, &remote, 1, 0) };
+    read == buffer.len() as isize
+}
+
+/// Seccomp user notification.
+#[repr(C)]
+struct SeccompNotif {
+    id: u64,
+    pid: u32,
+    flags: u32,
+    data: SeccompData,
+}
+
+/// Syscall which triggered a seccomp filter.
+#[repr(C)]
+struct SeccompData {
+    nr: c_int,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6],
+}
+
+/// Response to a seccomp user notification.
+#[repr(C)]
+struct SeccompNotifResp {
+    id: u64,
+    val: i64,
+    error: i32,
+    flags: u32,
+}
@@ -0,0 +1,181 @@
+//! Minimal init process for PID namespaces.
+
+use std::io::Error as IoError;
+use std::mem;
+use std::os::raw::c_int;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use crate::error::{RawError, RawResult};
+
+/// Signals forwarded to the sandboxed process.
+const FORWARDED_SIGNALS: &[c_int] = &[
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+    libc::SIGTERM,
+    libc::SIGWINCH,
+    libc::SIGCONT,
+];
+
+/// Process receiving all forwarded signals.
+static SIGNAL_TARGET: AtomicI32 = AtomicI32::new(0);
+
+/// Init process of a PID namespace.
+///
+/// Until [`Init::start`] is called, the current process is PID 1 of the new
+/// namespace.
+pub struct Init {
+    status: OwnedFd,
+}
+
+impl Init {
+    /// Create a new PID namespace.
+    ///
+    /// The current process is forked, with the child continuing as PID 1 of the
+    /// new namespace. The parent stays outside of the namespace and exits with
+    /// the exit status of the sandboxed process once the namespace is gone.
+    ///
+    /// This requires `CAP_SYS_ADMIN` in the current user namespace.
+    pub(crate) fn create() -> RawResult<'static, Self> {
+        let result = unsafe { libc::unshare(libc::CLONE_NEWPID) };
+        if result != 0 {
+            return Err(RawError::last_os_error());
+        }
+
+        // Create pipe to report the sandboxee's exit status to the parent.
+        let mut fds = [0; 2];
+        let result = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+        if result != 0 {
+            return Err(RawError::last_os_error());
+        }
+        let (reader, writer) =
+            unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+        match unsafe { libc::fork() } {
+            -1 => Err(RawError::last_os_error()),
+            0 => {
+                // Kill the entire namespace if the parent is killed.
+                unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) };
+
+                Ok(Self { status: writer })
+            },
+            init => {
+                drop(writer);
+                wait_for_namespace(init, reader)
+            },
+        }
+    }
+
+    /// Fork the sandboxed process, turning the current process into init.
+    ///
+    /// This only returns in the sandboxed process. Once it exits, init exits
+    /// too, which kills all remaining processes in the namespace.
+    pub(crate) fn start(self) -> RawResult<'static, ()> {
+        match unsafe { libc::fork() } {
+            -1 => Err(RawError::last_os_error()),
+            0 => Ok(()),
+            sandboxee => self.run(sandboxee),
+        }
+    }
+
+    /// Reap zombies until the sandboxee exits.
+    fn run(self, sandboxee: c_int) -> ! {
+        close_fds_except(self.status.as_raw_fd());
+        forward_signals(sandboxee);
+
+        loop {
+            let mut status = 0;
+            let pid = unsafe { libc::waitpid(-1, &mut status, 0) };
+
+            if pid == sandboxee {
+                let status = status.to_ne_bytes();
+                unsafe {
+                    libc::write(self.status.as_raw_fd(), status.as_ptr().cast(), status.len());
+                    libc::_exit(0);
+                }
+            } else if pid < 0 && IoError::last_os_error().raw_os_error() != Some(libc::EINTR) {
+                unsafe { libc::_exit(1) };
+            }
+        }
+    }
+}
+
+/// Wait for a PID namespace to exit and mirror its sandboxee's exit status.
+fn wait_for_namespace(init: c_int, status_reader: OwnedFd) -> ! {
+    close_fds_except(status_reader.as_raw_fd());
+    forward_signals(init);
+
+    let mut status = 0;
+    while unsafe { libc::waitpid(init, &mut status, 0) } < 0 {
+        if IoError::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            unsafe { libc::_exit(1) };
+        }
+    }
+
+    // Use the sandboxee's status, unless init was killed before reporting it.
+    let mut buffer = [0; mem::size_of::<c_int>()];
+    let read =
+        unsafe { libc::read(status_reader.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) };
+    if read == buffer.len() as isize {
+        status = c_int::from_ne_bytes(buffer);
+    }
+
+    exit_with_status(status)
+}
+
+/// Exit the current process with a `waitpid` status.
+fn exit_with_status(status: c_int) -> ! {
+    unsafe {
+        if libc::WIFSIGNALED(status) {
+            // Terminate with the same signal.
+            let signal = libc::WTERMSIG(status);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+            libc::_exit(128 + signal);
+        }
+
+        libc::_exit(libc::WEXITSTATUS(status))
+    }
+}
+
+/// Forward all signals received by the current process to `pid`.
+fn forward_signals(pid: c_int) {
+    SIGNAL_TARGET.store(pid, Ordering::Relaxed);
+
+    for signal in FORWARDED_SIGNALS {
+        unsafe {
+            libc::signal(*signal, forward_signal as extern "C" fn(c_int) as libc::sighandler_t)
+        };
+    }
+}
+
+/// Signal handler forwarding the signal to the [`SIGNAL_TARGET`].
+extern "C" fn forward_signal(signal: c_int) {
+    let pid = SIGNAL_TARGET.load(Ordering::Relaxed);
+    if pid > 0 {
+        unsafe { libc::kill(pid, signal) };
+    }
+}
+
+/// Close all file descriptors except stdio and `fd`.
+///
+/// This ensures no pipes, like the one used by `Command::spawn` to detect
+/// `exec` failures, are kept open by processes which will never `exec`.
+fn close_fds_except(fd: RawFd) {
+    let fd = fd as u32;
+    let ranges = [(3, fd.saturating_sub(1)), ((fd + 1).max(3), u32::MAX)];
+    for (first, last) in ranges.into_iter().filter(|(first, last)| first <= last) {
+        let result = unsafe { libc::syscall(libc::SYS_close_range, first, last, 0) };
+
+        // Fall back to closing each descriptor individually.
+        if result != 0 {
+            let max_fd = unsafe { libc::sysconf(libc::_SC_OPEN_MAX) }.max(1024) as u32;
+            for fd in first..=last.min(max_fd) {
+                unsafe { libc::close(fd as c_int) };
+            }
+        }
+    }
+}
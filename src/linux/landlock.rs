@@ -0,0 +1,370 @@
+//! Landlock filesystem and network restrictions.
+
+use std::ffi::{CStr, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::PathBuf;
+use std::{fs, mem, ptr};
+
+use bitflags::bitflags;
+
+use crate::error::{RawError, RawResult};
+use crate::linux::namespaces::{Mount, MountAttrFlags, Mounts, DEVICES, DEVPTS, IPC_FILESYSTEMS};
+use crate::linux::sys;
+
+/// Flag for `landlock_create_ruleset` to query the supported ABI version.
+const CREATE_RULESET_VERSION: u32 = 1 << 0;
+
+/// Rule type for filesystem hierarchy rules.
+const RULE_PATH_BENEATH: u32 = 1;
+
+/// Minimum ABI version for TCP network restrictions.
+const NET_ABI: u32 = 4;
+
+/// Landlock ruleset.
+///
+/// All rules are collected ahead of time, only opening the paths and
+/// restricting the process happens once the ruleset is applied.
+pub struct Ruleset {
+    rules: Vec<(CString, AccessFs)>,
+    private_rules: Vec<(CString, AccessFs)>,
+    enforces_denies: bool,
+}
+
+impl Ruleset {
+    /// Create Landlock rules mirroring the sandbox's mounts.
+    pub fn new(mounts: &Mounts) -> Self {
+        let denied: Vec<_> = mounts
+            .iter()
+            .filter(|(_, mount)| matches!(mount, Mount::Deny))
+            .map(|(path, _)| path)
+            .collect();
+
+        let mut rules = Vec::new();
+        let mut private_rules: Vec<_> = IPC_FILESYSTEMS
+            .iter()
+            .chain(DEVICES)
+            .chain([&DEVPTS])
+            .map(|path| (PathBuf::from(path), AccessFs::READ | AccessFs::WRITE))
+            .collect();
+        private_rules.push((PathBuf::from("/dev"), AccessFs::READ_DIR));
+        let mut binds = Vec::new();
+        for (path, mount) in mounts.iter() {
+            let flags = match mount {
+                Mount::Bind(flags) => flags,
+                Mount::Tmpfs { .. } => {
+                    private_rules.push((path.to_path_buf(), AccessFs::READ | AccessFs::WRITE));
+                    continue;
+                },
+                Mount::Overlay { flags, .. } => {
+                    let mut access = AccessFs::READ | AccessFs::WRITE;
+                    if !flags.contains(MountAttrFlags::NOEXEC) {
+                        access |= AccessFs::EXECUTE;
+                    }
+                    private_rules.push((path.to_path_buf(), access));
+                    continue;
+                },
+                Mount::Device => {
+                    rules.push((path.to_path_buf(), AccessFs::READ | AccessFs::WRITE));
+                    continue;
+                },
+                Mount::Remap { flags, .. } => {
+                    private_rules.push((path.to_path_buf(), bind_access(*flags)));
+                    continue;
+                },
+                Mount::File { mode, .. } => {
+                    let mut access = AccessFs::READ | AccessFs::WRITE;
+                    if mode & 0o111 != 0 {
+                        access |= AccessFs::EXECUTE;
+                    }
+                    private_rules.push((path.to_path_buf(), access));
+                    continue;
+                },
+                Mount::Deny => continue,
+            };
+            binds.push(path);
+            let access = bind_access(*flags);
+
+            // Landlock cannot deny access beneath an allowed path, so access is
+            // granted to all siblings of denied children instead.
+            let masked: Vec<_> = denied.iter().filter(|deny| deny.parent() == Some(path)).collect();
+            if masked.is_empty() {
+                rules.push((path.to_path_buf(), access));
+                continue;
+            }
+
+            for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+                let entry_path = entry.path();
+                if !masked.contains(&&entry_path.as_path()) {
+                    rules.push((entry_path, access));
+                }
+            }
+        }
+
+        // Denied paths can only be enforced if no indirect parent is accessible.
+        let enforces_denies = denied.iter().all(|deny| {
+            binds.iter().all(|bind| !deny.starts_with(bind) || deny.parent() == Some(bind))
+        });
+
+        // Mirror the procfs bind mount.
+        rules.push((PathBuf::from("/proc"), AccessFs::READ));
+
+        // Convert paths ahead of time, since applying rules must not allocate.
+        let to_cstring = |rules: Vec<(PathBuf, AccessFs)>| {
+            rules
+                .into_iter()
+                .map(|(path, access)| (CString::new(path.as_os_str().as_bytes()).unwrap(), access))
+                .collect()
+        };
+
+        Self { rules: to_cstring(rules), private_rules: to_cstring(private_rules), enforces_denies }
+    }
+
+    /// Restrict the current thread to the ruleset.
+    ///
+    /// TCP connections are blocked if `isolate_network` is `true`.
+    ///
+    /// Filesystems which only exist inside the mount namespace, like tmpfs and
+    /// overlay mounts, the minimal `/dev` or the IPC namespace's `/dev/shm`,
+    /// are writable if `private_mounts` is `true`. This includes `/dev/mqueue`,
+    /// since opening a POSIX message queue is checked like opening a file in
+    /// it.
+    ///
+    /// This does nothing if Landlock is not supported by the kernel.
+    ///
+    /// This requires `PR_SET_NO_NEW_PRIVS` to be set beforehand.
+    pub(crate) fn restrict_self(
+        &self,
+        isolate_network: bool,
+        private_mounts: bool,
+    ) -> RawResult<'_, ()> {
+        let abi = match abi_version() {
+            Some(abi) => abi,
+            None => return Ok(()),
+        };
+
+        let handled_access_fs = AccessFs::handled(abi);
+        let mut attr =
+            RulesetAttr { handled_access_fs: handled_access_fs.bits(), handled_access_net: 0 };
+
+        // Block all TCP traffic.
+        if isolate_network && abi >= NET_ABI {
+            attr.handled_access_net = AccessNet::all().bits();
+        }
+
+        let ruleset = create_ruleset(&attr)?;
+
+        for (path, access) in &self.rules {
+            add_path_rule(&ruleset, path, *access & handled_access_fs)?;
+        }
+
+        if private_mounts {
+            for (path, access) in &self.private_rules {
+                add_path_rule(&ruleset, path, *access & handled_access_fs)?;
+            }
+        }
+
+        let result =
+            unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0) };
+        if result != 0 {
+            return Err(RawError::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Check whether the ruleset can isolate network access.
+    ///
+    /// Landlock network restrictions only cover TCP connections, so other
+    /// internet sockets must be blocked separately.
+    pub fn supports_networking(&self) -> bool {
+        abi_version().map_or(false, |abi| abi >= NET_ABI)
+    }
+
+    /// Check whether all denied paths are inaccessible with this ruleset.
+    pub fn enforces_denies(&self) -> bool {
+        self.enforces_denies
+    }
+}
+
+/// Get the access rights matching a bind mount's flags.
+fn bind_access(flags: MountAttrFlags) -> AccessFs {
+    let mut access = AccessFs::READ;
+    if !flags.contains(MountAttrFlags::RDONLY) {
+        access |= AccessFs::WRITE;
+    }
+    if !flags.contains(MountAttrFlags::NOEXEC) {
+        access |= AccessFs::EXECUTE;
+    }
+    access
+}
+
+/// Get the Landlock ABI version supported by the kernel.
+pub fn abi_version() -> Option<u32> {
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            ptr::null::<RulesetAttr>(),
+            0,
+            CREATE_RULESET_VERSION,
+        )
+    };
+
+    (result > 0).then_some(result as u32)
+}
+
+/// Create a new Landlock ruleset.
+fn create_ruleset(attr: &RulesetAttr) -> RawResult<'static, OwnedFd> {
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            attr as *const _,
+            mem::size_of::<RulesetAttr>(),
+            0,
+        )
+    };
+
+    if fd < 0 {
+        return Err(RawError::last_os_error());
+    }
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+/// Allow access to a path and everything beneath it.
+fn add_path_rule<'a>(ruleset: &OwnedFd, path: &'a CStr, mut access: AccessFs) -> RawResult<'a, ()> {
+    // Ignore paths which have been removed since the rule was created.
+    let parent = match sys::open(path, libc::O_PATH, 0) {
+        Ok(parent) => parent,
+        Err(_) => return Ok(()),
+    };
+
+    // Directory access rights are invalid for files.
+    let metadata = sys::fstat(&parent).map_err(|_| RawError::InvalidPath(path))?;
+    if !sys::is_dir(&metadata) {
+        access &= AccessFs::FILE;
+    }
+
+    let attr = PathBeneathAttr { allowed_access: access.bits(), parent_fd: parent.as_raw_fd() };
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            RULE_PATH_BENEATH,
+            &attr as *const _,
+            0,
+        )
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(RawError::last_os_error())
+    }
+}
+
+/// Parameter for the `landlock_create_ruleset` syscall.
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+    handled_access_net: u64,
+}
+
+/// Parameter for the `landlock_add_rule` syscall with path beneath rules.
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+bitflags! {
+    /// Landlock filesystem access rights.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct AccessFs: u64 {
+        /// Execute a file.
+        const EXECUTE     = 1 << 0;
+        /// Open a file with write access.
+        const WRITE_FILE  = 1 << 1;
+        /// Open a file with read access.
+        const READ_FILE   = 1 << 2;
+        /// Open a directory or list its content.
+        const READ_DIR    = 1 << 3;
+        /// Remove an empty directory or rename one.
+        const REMOVE_DIR  = 1 << 4;
+        /// Unlink (or rename) a file.
+        const REMOVE_FILE = 1 << 5;
+        /// Create (or rename or link) a character device.
+        const MAKE_CHAR   = 1 << 6;
+        /// Create (or rename) a directory.
+        const MAKE_DIR    = 1 << 7;
+        /// Create (or rename or link) a regular file.
+        const MAKE_REG    = 1 << 8;
+        /// Create (or rename or link) a UNIX domain socket.
+        const MAKE_SOCK   = 1 << 9;
+        /// Create (or rename or link) a named pipe.
+        const MAKE_FIFO   = 1 << 10;
+        /// Create (or rename or link) a block device.
+        const MAKE_BLOCK  = 1 << 11;
+        /// Create (or rename or link) a symbolic link.
+        const MAKE_SYM    = 1 << 12;
+        /// Link or rename a file from or to a different directory (ABI 2).
+        const REFER       = 1 << 13;
+        /// Truncate a file (ABI 3).
+        const TRUNCATE    = 1 << 14;
+        /// Invoke ioctl commands on an opened character or block device (ABI 5).
+        const IOCTL_DEV   = 1 << 15;
+
+        /// Read access to files and directories.
+        const READ = Self::READ_FILE.bits() | Self::READ_DIR.bits();
+        /// Write access to files and directories.
+        const WRITE = Self::WRITE_FILE.bits()
+            | Self::REMOVE_DIR.bits()
+            | Self::REMOVE_FILE.bits()
+            | Self::MAKE_CHAR.bits()
+            | Self::MAKE_DIR.bits()
+            | Self::MAKE_REG.bits()
+            | Self::MAKE_SOCK.bits()
+            | Self::MAKE_FIFO.bits()
+            | Self::MAKE_BLOCK.bits()
+            | Self::MAKE_SYM.bits()
+            | Self::REFER.bits()
+            | Self::TRUNCATE.bits()
+            | Self::IOCTL_DEV.bits();
+        /// Access rights which can be granted on files.
+        const FILE = Self::EXECUTE.bits()
+            | Self::WRITE_FILE.bits()
+            | Self::READ_FILE.bits()
+            | Self::TRUNCATE.bits()
+            | Self::IOCTL_DEV.bits();
+    }
+}
+
+impl AccessFs {
+    /// Get all access rights supported by a Landlock ABI version.
+    fn handled(abi: u32) -> Self {
+        let mut access = Self::all() - Self::REFER - Self::TRUNCATE - Self::IOCTL_DEV;
+
+        if abi >= 2 {
+            access |= Self::REFER;
+        }
+        if abi >= 3 {
+            access |= Self::TRUNCATE;
+        }
+        if abi >= 5 {
+            access |= Self::IOCTL_DEV;
+        }
+
+        access
+    }
+}
+
+bitflags! {
+    /// Landlock network access rights (ABI 4).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct AccessNet: u64 {
+        /// Bind a TCP socket to a local port.
+        const BIND_TCP    = 1 << 0;
+        /// Connect an active TCP socket to a remote port.
+        const CONNECT_TCP = 1 << 1;
+    }
+}
@@ -0,0 +1,211 @@
+//! Learn mode generating sandbox exceptions.
+//!
+//! Learning runs a command in [audit mode](crate::audit) without any
+//! exceptions, so every access it performs is reported as a violation. These
+//! violations are then reduced to the exceptions necessary to run the same
+//! command inside the sandbox.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use crate::audit::{Access, Violation};
+use crate::error::Result;
+use crate::linux::LinuxSandbox;
+use crate::{Exception, Sandbox};
+
+/// Run a command and learn the sandbox exceptions it requires.
+///
+/// The command is run without any restrictions. Once the command and all of
+/// its children have exited, the exit status and learned profile are
+/// returned.
+///
+/// Reading environment variables does not involve any system calls, so it is
+/// impossible to observe which variables are used. The command is run with
+/// the full environment, but the learned profile never includes any
+/// [`Exception::Environment`] exceptions; these must be added manually.
+pub fn learn(sandboxee: Command) -> Result<(ExitStatus, Profile)> {
+    let mut birdcage = LinuxSandbox::new();
+    birdcage.add_exception(Exception::FullEnvironment)?;
+    let events = birdcage.audit();
+
+    let status = birdcage.spawn(sandboxee)?.0.wait()?;
+    let profile = events.iter().collect();
+
+    Ok((status, profile))
+}
+
+/// Sandbox requirements learned from audit violations.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Profile {
+    paths: BTreeMap<PathBuf, BTreeSet<Access>>,
+    syscalls: BTreeSet<i64>,
+    networking: bool,
+    loopback_networking: bool,
+}
+
+impl Profile {
+    /// Record the requirements for a violation.
+    pub fn record(&mut self, violation: Violation) {
+        match violation {
+            Violation::Path { path, access, .. } => {
+                self.paths.entry(path).or_default().insert(access);
+            },
+            Violation::Syscall { syscall, .. } => {
+                self.syscalls.insert(syscall);
+            },
+            Violation::Network { address, .. } if address.ip().is_loopback() => {
+                self.loopback_networking = true;
+            },
+            Violation::Network { .. } => self.networking = true,
+        }
+    }
+
+    /// Get the minimal set of exceptions covering all recorded violations.
+    ///
+    /// Paths which no longer exist are ignored, unless they were written to,
+    /// in which case write access is granted to their parent directory
+    /// instead. This is only done if the parent directory still exists and
+    /// was accessed itself, otherwise the path is listed in
+    /// [`Profile::unresolved`].
+    ///
+    /// Directories which were only read are omitted if access to a path
+    /// beneath them is granted, since the directory will exist inside the
+    /// sandbox regardless.
+    ///
+    /// Device nodes are always granted with [`Exception::Device`], since other
+    /// exceptions do not allow opening them.
+    pub fn exceptions(&self) -> Vec<Exception> {
+        // Resolve paths which do not exist anymore.
+        let mut paths: BTreeMap<PathBuf, PathAccess> = BTreeMap::new();
+        for (path, access) in &self.paths {
+            let write = access.contains(&Access::Write);
+            let execute = access.contains(&Access::Execute);
+
+            if path.exists() {
+                let entry = paths.entry(path.clone()).or_default();
+                entry.write |= write;
+                entry.execute |= execute;
+            } else if let Some(parent) = self.writable_parent(path).filter(|_| write) {
+                paths.entry(parent.into()).or_default().write = true;
+            }
+        }
+
+        // Ignore directories which are part of another path.
+        let is_implied = |path: &Path, access: &PathAccess| {
+            access.is_read_only()
+                && path.is_dir()
+                && paths.keys().any(|other| other != path && other.starts_with(path))
+        };
+        let paths: Vec<_> =
+            paths.iter().filter(|(path, access)| !is_implied(path, access)).collect();
+
+        // Remove paths with an ancestor granting the same access.
+        //
+        // Since paths are sorted, ancestors are always processed first.
+        let mut exceptions = Vec::new();
+        let mut granted: Vec<(&PathBuf, &PathAccess)> = Vec::new();
+        for (path, access) in paths {
+            let file_type = path.metadata().map(|metadata| metadata.file_type());
+            if file_type.map_or(false, |ty| ty.is_char_device() || ty.is_block_device()) {
+                exceptions.push(Exception::Device(path.clone()));
+                continue;
+            }
+
+            let is_covered = granted.iter().any(|(granted, granted_access)| {
+                path.starts_with(granted) && granted_access.covers(access)
+            });
+            if is_covered {
+                continue;
+            }
+            granted.push((path, access));
+
+            if access.write {
+                exceptions.push(Exception::WriteAndRead(path.clone()));
+            }
+            if access.execute {
+                exceptions.push(Exception::ExecuteAndRead(path.clone()));
+            }
+            if access.is_read_only() {
+                exceptions.push(Exception::Read(path.clone()));
+            }
+        }
+
+        if self.networking {
+            exceptions.push(Exception::Networking);
+        } else if self.loopback_networking {
+            exceptions.push(Exception::LoopbackNetworking);
+        }
+
+        exceptions
+    }
+
+    /// Written paths which no longer exist and could not be granted.
+    ///
+    /// Granting access to these paths would require write access to a
+    /// directory the command never accessed, so they have to be reviewed
+    /// manually.
+    pub fn unresolved(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.paths
+            .iter()
+            .filter(|(path, access)| {
+                access.contains(&Access::Write)
+                    && !path.exists()
+                    && self.writable_parent(path).is_none()
+            })
+            .map(|(path, _)| path.as_path())
+    }
+
+    /// Get the parent directory granting write access to a missing `path`.
+    ///
+    /// The parent must exist and must have been accessed itself.
+    fn writable_parent<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.parent().filter(|parent| parent.is_dir() && self.paths.contains_key(*parent))
+    }
+
+    /// System calls which were blocked by the seccomp filter.
+    ///
+    /// No exception can allow these system calls, so commands using them
+    /// might fail inside the sandbox.
+    pub fn syscalls(&self) -> impl Iterator<Item = i64> + '_ {
+        self.syscalls.iter().copied()
+    }
+}
+
+impl Extend<Violation> for Profile {
+    fn extend<T: IntoIterator<Item = Violation>>(&mut self, violations: T) {
+        for violation in violations {
+            self.record(violation);
+        }
+    }
+}
+
+impl FromIterator<Violation> for Profile {
+    fn from_iter<T: IntoIterator<Item = Violation>>(violations: T) -> Self {
+        let mut profile = Self::default();
+        profile.extend(violations);
+        profile
+    }
+}
+
+/// Access required for a single path.
+///
+/// Read access is always granted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct PathAccess {
+    write: bool,
+    execute: bool,
+}
+
+impl PathAccess {
+    /// Check if no access other than reading is required.
+    fn is_read_only(&self) -> bool {
+        !self.write && !self.execute
+    }
+
+    /// Check if this grants all access required by `other`.
+    fn covers(&self, other: &Self) -> bool {
+        (self.write || !other.write) && (self.execute || !other.execute)
+    }
+}
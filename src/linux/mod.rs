@@ -1,24 +1,77 @@
 //! Linux sandboxing.
 
 use std::collections::HashMap;
+use std::ffi::CStr;
 use std::io::Error as IoError;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
 use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::{env, iter, mem, ptr};
 
-use crate::error::{Error, Result};
-use crate::linux::namespaces::MountAttrFlags;
+#[cfg(feature = "serde")]
+use crate::config::SandboxConfig;
+use crate::error::{errno, Error, RawError, RawResult, Result};
+use crate::linux::audit::{Policy, Violation};
+use crate::linux::landlock::Ruleset;
+pub use crate::linux::namespaces::Clock;
+use crate::linux::namespaces::{Mount, MountAttrFlags, MountPlan, Mounts, NamespaceConfig};
+use crate::linux::probe::Capabilities;
+use crate::linux::proxy::EgressProxy;
 use crate::linux::seccomp::SyscallFilter;
-use crate::{Exception, Sandbox};
+use crate::linux::status::Status;
+use crate::report::{Layer, Report};
+use crate::{BindAccess, Exception, Sandbox, Strictness};
 
+pub mod audit;
+mod init;
+mod landlock;
+pub mod learn;
 mod namespaces;
+mod netlink;
+pub mod overlay;
+pub mod probe;
+mod proxy;
 mod seccomp;
+mod status;
+mod sys;
+
+/// User and group ID of files owned by IDs without a namespace mapping.
+const OVERFLOW_ID: u32 = 65534;
+
+/// Buffer size for user and group database lookups.
+const PASSWD_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Size limit of the tmpfs used by [`Exception::VirtualHome`].
+const VIRTUAL_HOME_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Pseudo-filesystems hidden by [`Exception::ReadAll`].
+const PSEUDO_FILESYSTEMS: &[&str] = &["/dev", "/sys"];
 
 /// Linux sandboxing.
+///
+/// Filesystem and network access is isolated using namespaces, with Landlock
+/// stacked on top where the kernel supports it. If unprivileged user
+/// namespaces are unavailable, Landlock is used on its own.
+///
+/// Without a network namespace, Landlock can only restrict TCP. All other
+/// internet sockets are blocked by seccomp instead, so UDP and raw sockets
+/// cannot be used at all.
 #[derive(Default)]
 pub struct LinuxSandbox {
-    bind_mounts: HashMap<PathBuf, MountAttrFlags>,
+    mounts: HashMap<PathBuf, Mount>,
     env_exceptions: Vec<String>,
-    allow_networking: bool,
+    network_hosts: Vec<(String, u16)>,
+    namespaces: NamespaceConfig,
+    audit: Option<Sender<Violation>>,
     full_env: bool,
+    read_all: bool,
+    require_exceptions: bool,
+    strictness: Strictness,
+    #[cfg(feature = "serde")]
+    exceptions: Vec<Exception>,
 }
 
 impl LinuxSandbox {
@@ -29,9 +82,23 @@ impl LinuxSandbox {
     ///
     /// If the bind mount already exists, it will *ADD* the additional
     /// permissions.
+    ///
+    /// Denied paths, tmpfs mounts, devices, remapped bind mounts and files are
+    /// never modified, while overlay mounts only gain the additional
+    /// permissions.
     fn update_bind_mount(&mut self, path: PathBuf, write: bool, execute: bool) {
-        let flags =
-            self.bind_mounts.entry(path).or_insert(MountAttrFlags::RDONLY | MountAttrFlags::NOEXEC);
+        let mount = self
+            .mounts
+            .entry(path)
+            .or_insert(Mount::Bind(MountAttrFlags::RDONLY | MountAttrFlags::NOEXEC));
+        let flags = match mount {
+            Mount::Bind(flags) | Mount::Overlay { flags, .. } => flags,
+            Mount::Deny
+            | Mount::Tmpfs { .. }
+            | Mount::Device
+            | Mount::Remap { .. }
+            | Mount::File { .. } => return,
+        };
 
         if write {
             flags.remove(MountAttrFlags::RDONLY);
@@ -41,6 +108,140 @@ impl LinuxSandbox {
             flags.remove(MountAttrFlags::NOEXEC);
         }
     }
+
+    /// Check which isolation layers are supported by the current host.
+    pub fn probe() -> Capabilities {
+        Capabilities::detect()
+    }
+
+    /// Fail activation if any filesystem exception cannot be applied.
+    ///
+    /// By default, exceptions whose path cannot be resolved anymore when the
+    /// sandbox is activated are dropped and listed in the [`Report`].
+    pub fn require_exceptions(&mut self) -> &mut Self {
+        self.require_exceptions = true;
+        self
+    }
+
+    /// Set the hostname inside the sandbox.
+    ///
+    /// This isolates the sandbox with a UTS namespace, hiding the host's
+    /// hostname and domain name.
+    pub fn hostname(&mut self, hostname: impl Into<String>) -> &mut Self {
+        self.namespaces.hostname = Some(hostname.into());
+        self
+    }
+
+    /// Set the NIS domain name inside the sandbox.
+    ///
+    /// This isolates the sandbox with a UTS namespace, hiding the host's
+    /// hostname and domain name.
+    pub fn domainname(&mut self, domainname: impl Into<String>) -> &mut Self {
+        self.namespaces.domainname = Some(domainname.into());
+        self
+    }
+
+    /// Offset a clock inside the sandbox by `seconds`.
+    ///
+    /// This isolates the sandbox with a time namespace. Negative offsets must
+    /// not move the clock before zero.
+    ///
+    /// Clock offsets require Linux 5.6 or newer.
+    pub fn clock_offset(&mut self, clock: Clock, seconds: i64) -> &mut Self {
+        self.namespaces.clock_offsets.retain(|(offset_clock, _)| *offset_clock != clock);
+        self.namespaces.clock_offsets.push((clock, seconds));
+        self
+    }
+
+    /// Get the configuration of this sandbox.
+    ///
+    /// Audit mode is not part of the configuration.
+    #[cfg(feature = "serde")]
+    pub fn config(&self) -> SandboxConfig {
+        let mut config = SandboxConfig::new(self.exceptions.clone());
+        config.strictness = self.strictness;
+        config.require_exceptions = self.require_exceptions;
+        config.hostname = self.namespaces.hostname.clone();
+        config.domainname = self.namespaces.domainname.clone();
+        config.clock_offsets = self.namespaces.clock_offsets.clone();
+        config
+    }
+
+    /// Enable audit mode.
+    ///
+    /// Instead of denying access, the sandboxee is run without restrictions
+    /// and every access which the sandbox would have blocked is reported
+    /// through the returned channel. This covers filesystem paths, network
+    /// addresses, and system calls. Environment variables are still removed.
+    ///
+    /// Access to a path is only checked for syscalls which resolve it, so
+    /// metadata queries like `stat` are not reported.
+    ///
+    /// Audit mode is only supported by [`Sandbox::spawn`].
+    pub fn audit(&mut self) -> Receiver<Violation> {
+        let (sender, receiver) = mpsc::channel();
+        self.audit = Some(sender);
+        receiver
+    }
+
+    /// Resolve all filesystem mounts.
+    fn mounts(&mut self) -> Mounts {
+        // Hide pseudo-filesystems which were not explicitly allowed.
+        if self.read_all {
+            for path in PSEUDO_FILESYSTEMS {
+                self.mounts.entry(PathBuf::from(path)).or_insert(Mount::Deny);
+            }
+        }
+
+        Mounts::new(mem::take(&mut self.mounts))
+    }
+
+    /// Prepare all sandboxing layers for activation.
+    ///
+    /// In strict mode, this fails if the host does not support all layers
+    /// required by the sandbox.
+    fn layers(mut self) -> Result<Layers> {
+        let mounts = self.mounts();
+        if self.require_exceptions && !mounts.dropped().is_empty() {
+            return Err(Error::DroppedExceptions(mounts.dropped().to_vec()));
+        }
+
+        let plan = MountPlan::new(&mounts)?;
+        let ruleset = Ruleset::new(&mounts);
+        let filter = SyscallFilter::new()?;
+
+        let layers = Layers {
+            mounts,
+            plan,
+            ruleset,
+            filter,
+            namespaces: self.namespaces.clone(),
+            strictness: self.strictness,
+        };
+
+        // Check host support before any changes are made to the process.
+        if self.strictness == Strictness::Strict {
+            let capabilities = Capabilities::detect();
+            let isolates_filesystem = capabilities.mount_namespace && capabilities.mount_setattr;
+            let isolates_network =
+                self.namespaces.allow_networking || capabilities.network_namespace;
+            let isolates_uts = !self.namespaces.isolates_uts() || capabilities.uts_namespace;
+            let isolates_time = !self.namespaces.isolates_time() || capabilities.time_namespace;
+            let isolates_processes = !self.namespaces.pid_namespace
+                || !isolates_filesystem
+                || capabilities.pid_namespace;
+            let isolates_ipc = self.namespaces.allow_host_ipc || capabilities.ipc_namespace;
+            layers.ensure_supported(
+                isolates_filesystem,
+                isolates_network,
+                isolates_uts && isolates_time,
+                isolates_processes,
+                isolates_ipc,
+            )?;
+        }
+
+        Ok(layers)
+    }
 }
 
 impl Sandbox for LinuxSandbox {
@@ -52,53 +253,503 @@ impl Sandbox for LinuxSandbox {
         // Report error if exception is added for an invalid path.
         if let Exception::Read(path)
         | Exception::WriteAndRead(path)
-        | Exception::ExecuteAndRead(path) = &exception
+        | Exception::ExecuteAndRead(path)
+        | Exception::Deny(path) = &exception
         {
             if !path.exists() {
                 return Err(Error::InvalidPath(path.into()));
             }
         }
 
+        // Tmpfs mounts are only supported for directories, with a size limit.
+        if let Exception::Tmpfs { path, size, .. } = &exception {
+            if !path.is_dir() {
+                return Err(Error::InvalidPath(path.into()));
+            } else if *size == 0 {
+                return Err(Error::InvalidException("tmpfs size must not be zero"));
+            }
+        }
+
+        // Bind mount targets are resolved inside the sandbox.
+        if let Exception::Bind { source, target, .. } = &exception {
+            if !source.exists() {
+                return Err(Error::InvalidPath(source.into()));
+            } else if !target.is_absolute() {
+                return Err(Error::InvalidPath(target.into()));
+            }
+        }
+
+        // Virtual home directories replace the existing home directory.
+        let home = match &exception {
+            Exception::VirtualHome { persistent } => {
+                let home = env::var_os("HOME")
+                    .map(PathBuf::from)
+                    .ok_or(Error::InvalidException("virtual home requires `HOME` to be set"))?;
+                for path in iter::once(&home).chain(persistent) {
+                    if !path.is_dir() {
+                        return Err(Error::InvalidPath(path.into()));
+                    }
+                }
+                Some(home)
+            },
+            _ => None,
+        };
+
+        // Files are created inside the sandbox.
+        if let Exception::File { path, .. } = &exception {
+            if !path.is_absolute() {
+                return Err(Error::InvalidPath(path.into()));
+            }
+        }
+
+        // Device exceptions are only supported for device nodes.
+        if let Exception::Device(path) = &exception {
+            let file_type = path.metadata().map(|metadata| metadata.file_type());
+            if !file_type.map_or(false, |ty| ty.is_char_device() || ty.is_block_device()) {
+                return Err(Error::InvalidPath(path.into()));
+            }
+        }
+
+        // Overlay paths are passed to the kernel as comma-separated list.
+        if let Exception::Overlay { path, changes } = &exception {
+            for path in iter::once(path).chain(changes) {
+                if !path.is_dir() || !overlay::is_valid_path(path) {
+                    return Err(Error::InvalidPath(path.into()));
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        self.exceptions.push(exception.clone());
+
         match exception {
             Exception::Read(path) => self.update_bind_mount(path, false, false),
             Exception::WriteAndRead(path) => self.update_bind_mount(path, true, false),
             Exception::ExecuteAndRead(path) => self.update_bind_mount(path, false, true),
+            Exception::Deny(path) => {
+                self.mounts.insert(path, Mount::Deny);
+            },
+            Exception::Tmpfs { path, size, mode } => {
+                self.mounts.insert(path, Mount::Tmpfs { size, mode });
+            },
+            Exception::Overlay { path, changes } => {
+                // Keep execute permissions of existing bind mounts.
+                let flags = match self.mounts.get(&path) {
+                    Some(Mount::Bind(flags)) => *flags & MountAttrFlags::NOEXEC,
+                    _ => MountAttrFlags::NOEXEC,
+                };
+                self.mounts.insert(path, Mount::Overlay { flags, changes });
+            },
+            Exception::Device(path) => {
+                self.mounts.insert(path, Mount::Device);
+            },
+            Exception::Bind { source, target, access } => {
+                let flags = match access {
+                    BindAccess::Read => MountAttrFlags::RDONLY | MountAttrFlags::NOEXEC,
+                    BindAccess::WriteAndRead => MountAttrFlags::NOEXEC,
+                    BindAccess::ExecuteAndRead => MountAttrFlags::RDONLY,
+                };
+                self.mounts.insert(target, Mount::Remap { source, flags });
+            },
+            Exception::File { path, contents, mode } => {
+                self.mounts.insert(path, Mount::File { contents, mode });
+            },
+            Exception::VirtualHome { persistent } => {
+                let mount = match persistent {
+                    Some(source) => Mount::Remap { source, flags: MountAttrFlags::NOEXEC },
+                    None => Mount::Tmpfs { size: VIRTUAL_HOME_SIZE, mode: 0o700 },
+                };
+                self.mounts.insert(home.unwrap(), mount);
+                self.env_exceptions.push("HOME".into());
+            },
+            Exception::ReadAll => {
+                self.update_bind_mount(PathBuf::from("/"), false, false);
+                self.read_all = true;
+            },
             Exception::Environment(key) => self.env_exceptions.push(key),
             Exception::FullEnvironment => self.full_env = true,
-            Exception::Networking => self.allow_networking = true,
+            Exception::Networking => self.namespaces.allow_networking = true,
+            Exception::LoopbackNetworking => self.namespaces.allow_loopback = true,
+            Exception::NetworkHost { host, port } => self.network_hosts.push((host, port)),
+            Exception::HostIpc => self.namespaces.allow_host_ipc = true,
+            Exception::FullProcfs => self.namespaces.full_procfs = true,
         }
 
         Ok(self)
     }
 
-    fn lock(self) -> Result<()> {
+    fn strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
+    fn prepare(mut self) -> Result<PreparedSandbox> {
+        // The audit supervisor must run outside of the sandbox.
+        if self.audit.is_some() {
+            return Err(Error::InvalidException("audit mode requires `Sandbox::spawn`"));
+        }
+
+        // The egress proxy must run outside of the sandbox.
+        if !self.namespaces.allow_networking && !self.network_hosts.is_empty() {
+            return Err(Error::InvalidException(
+                "network host exceptions require `Sandbox::spawn`",
+            ));
+        }
+
+        let env_exceptions = mem::take(&mut self.env_exceptions);
+        let full_env = self.full_env;
+
+        Ok(PreparedSandbox { layers: self.layers()?, env_exceptions, full_env })
+    }
+
+    fn spawn(mut self, mut sandboxee: Command) -> Result<(Child, Report)> {
+        // Remove environment variables.
+        if !self.full_env {
+            crate::restrict_command_env(&mut sandboxee, &self.env_exceptions);
+        }
+
+        if let Some(events) = self.audit.take() {
+            return self.spawn_audited(sandboxee, events);
+        }
+
+        // Setup the egress proxy for allowed network destinations.
+        let proxy = if !self.namespaces.allow_networking && !self.network_hosts.is_empty() {
+            let proxy = EgressProxy::new(mem::take(&mut self.network_hosts))?;
+            let socket_path = proxy.socket_path();
+            sandboxee.env(proxy::SOCKET_ENV, &socket_path);
+            self.update_bind_mount(socket_path, true, false);
+            Some(proxy)
+        } else {
+            None
+        };
+
+        // Isolate the child's processes, which requires forking in the child.
+        self.namespaces.pid_namespace = true;
+
+        // Resolve paths and compile the seccomp filter in the parent, so errors
+        // are reported before forking and the child has less work to do.
+        let layers = self.layers()?;
+        let dropped = layers.mounts.dropped().to_vec();
+
+        // Apply the sandbox restrictions in the child before executing the
+        // sandboxee.
+        //
+        // The child of a multithreaded parent must not allocate, since another
+        // thread might have held the allocator's lock while forking. So all
+        // allocations happen ahead of time and the activation status is sent
+        // back through a pipe.
+        let (status_reader, status_writer) = status::pipe()?;
+        unsafe {
+            sandboxee.pre_exec(move || {
+                let mut status = Status::new();
+                let result = layers.activate(&mut status);
+                status.send(&status_writer, result);
+                Ok(result?)
+            });
+        }
+
+        let result = sandboxee.spawn();
+
+        // Close the writer held by the `pre_exec` closure, to receive the status.
+        drop(sandboxee);
+        let status = status::receive(status_reader, dropped);
+
+        let (child, report) = match (result, status) {
+            (Ok(child), Ok(report)) => (child, report),
+            (Ok(mut child), Err(error)) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(error);
+            },
+            (Err(_), Err(error)) => return Err(error),
+            (Err(error), Ok(_)) => return Err(error.into()),
+        };
+
+        if let Some(proxy) = proxy {
+            proxy.serve(child.id());
+        }
+
+        Ok((child, report))
+    }
+}
+
+impl LinuxSandbox {
+    /// Spawn the sandboxee in audit mode.
+    ///
+    /// No restrictions are applied in audit mode, so the report is empty.
+    fn spawn_audited(
+        mut self,
+        mut sandboxee: Command,
+        events: Sender<Violation>,
+    ) -> Result<(Child, Report)> {
+        let policy = Policy::new(self.mounts(), self.namespaces.clone(), &self.network_hosts);
+        let filter = SyscallFilter::audit()?;
+
+        // Start the supervisor before spawning, since the child's `execve`
+        // blocks until its notification has been handled.
+        let (supervisor_socket, sandboxee_socket) = UnixStream::pair()?;
+        audit::supervise(supervisor_socket, policy, events);
+
+        unsafe {
+            sandboxee.pre_exec(move || {
+                no_new_privs()?;
+                let listener = filter.apply_audit()?;
+                audit::send_listener(&sandboxee_socket, listener)?;
+                Ok(())
+            });
+        }
+
+        Ok((sandboxee.spawn()?, Report::default()))
+    }
+}
+
+impl Exception {
+    /// Create an `/etc/passwd` with an entry for the sandboxed user.
+    ///
+    /// Besides the user and group IDs the sandbox is running as, this only
+    /// contains the `nobody` user owning files of all unmapped users.
+    pub fn passwd() -> Self {
+        let (uid, gid) = namespaces::mapped_ids();
+        let (name, home, shell) = lookup_user(uid).unwrap_or_else(|| {
+            let name = env::var("USER").unwrap_or_else(|_| "user".into());
+            (name, "/".into(), "/bin/sh".into())
+        });
+
+        let mut contents = format!("{name}:x:{uid}:{gid}:{name}:{home}:{shell}\n");
+        if uid != OVERFLOW_ID {
+            contents.push_str("nobody:x:65534:65534:nobody:/:/usr/sbin/nologin\n");
+        }
+
+        Self::File { path: "/etc/passwd".into(), contents: contents.into_bytes(), mode: 0o644 }
+    }
+
+    /// Create an `/etc/group` with an entry for the sandboxed group.
+    ///
+    /// Besides the group ID the sandbox is running as, this only contains the
+    /// `nogroup` group owning files of all unmapped groups.
+    pub fn group() -> Self {
+        let (_, gid) = namespaces::mapped_ids();
+        let name = lookup_group(gid).unwrap_or_else(|| "user".into());
+
+        let mut contents = format!("{name}:x:{gid}:\n");
+        if gid != OVERFLOW_ID {
+            contents.push_str("nogroup:x:65534:\n");
+        }
+
+        Self::File { path: "/etc/group".into(), contents: contents.into_bytes(), mode: 0o644 }
+    }
+}
+
+/// Linux sandbox prepared for activation.
+///
+/// See [`Sandbox::prepare`].
+pub struct PreparedSandbox {
+    layers: Layers,
+    env_exceptions: Vec<String>,
+    full_env: bool,
+}
+
+impl PreparedSandbox {
+    /// Apply the sandbox restrictions to the current process.
+    ///
+    /// This has the same requirements as [`Sandbox::lock`].
+    pub fn commit(self) -> Result<Report> {
         // Remove environment variables.
         if !self.full_env {
             crate::restrict_env_variables(&self.env_exceptions);
         }
 
-        // Setup namespaces.
-        namespaces::create_namespaces(self.allow_networking, self.bind_mounts)?;
+        let mut status = Status::new();
+        self.layers.activate(&mut status)?;
 
-        // Setup system call filters.
-        SyscallFilter::apply()?;
+        Ok(status.into_report(self.layers.mounts.dropped().to_vec()))
+    }
+}
+
+/// Sandboxing layers prepared for activation.
+struct Layers {
+    mounts: Mounts,
+    plan: MountPlan,
+    ruleset: Ruleset,
+    filter: SyscallFilter,
+    namespaces: NamespaceConfig,
+    strictness: Strictness,
+}
+
+impl Layers {
+    /// Apply all sandboxing layers to the current process.
+    ///
+    /// All layers are recorded in the `status`. This does not allocate, so it
+    /// is safe to call in a forked child.
+    fn activate<'a>(&'a self, status: &mut Status<'a>) -> RawResult<'a, ()> {
+        // Layers are only skipped in strict mode if their restrictions can be
+        // enforced by another layer.
+        let best_effort = self.strictness == Strictness::BestEffort;
+        let skippable = |_: &RawError<'_>| best_effort;
+
+        // Setup namespaces, falling back to Landlock if user namespaces are not
+        // available.
+        let init = namespaces::create_namespaces(&self.namespaces, &self.plan, status, |error| {
+            best_effort || namespaces::is_unsupported(error)
+        })?;
+        let isolates_filesystem = status.is_applied(Layer::MountNamespace);
+        let isolates_network =
+            self.namespaces.allow_networking || status.is_applied(Layer::NetworkNamespace);
+        let isolates_host_identity =
+            !status.is_skipped(Layer::UtsNamespace) && !status.is_skipped(Layer::TimeNamespace);
+        let isolates_processes =
+            !self.namespaces.pid_namespace || !status.is_skipped(Layer::PidNamespace);
+        let isolates_ipc = !status.is_skipped(Layer::IpcNamespace);
+
+        // Ensure Landlock can provide the isolation missing without namespaces.
+        if !best_effort {
+            self.ensure_supported(
+                isolates_filesystem,
+                isolates_network,
+                isolates_host_identity,
+                isolates_processes,
+                isolates_ipc,
+            )?;
+        }
 
         // Block suid/sgid.
         //
         // This is also blocked by our bind mount's MS_NOSUID flag, so we're just
-        // doubling-down here.
-        no_new_privs()?;
+        // doubling-down here. It is also required for Landlock.
+        status.record(Layer::NoNewPrivileges, no_new_privs(), skippable)?;
+
+        // Setup Landlock rules.
+        //
+        // Without a network namespace, Landlock is used to block TCP traffic.
+        let landlock_applied = match landlock::abi_version() {
+            Some(_) => {
+                let result = self.ruleset.restrict_self(!isolates_network, isolates_filesystem);
+                status.record(Layer::Landlock, result, skippable)?
+            },
+            None => {
+                status.skip(Layer::Landlock, "landlock is not supported by the kernel");
+                false
+            },
+        };
+        if !isolates_network {
+            if !landlock_applied {
+                status.skip(Layer::LandlockNetwork, "landlock could not be applied");
+            } else if self.ruleset.supports_networking() {
+                status.apply(Layer::LandlockNetwork);
+            } else {
+                status.skip(Layer::LandlockNetwork, "landlock ABI v4 is required");
+            }
+        }
+
+        // Setup system call filters.
+        //
+        // Without a network namespace, all sockets Landlock cannot restrict are
+        // blocked.
+        status.record(Layer::Seccomp, self.filter.apply(!isolates_network), skippable)?;
+
+        // Continue in a child process of the PID namespace's init.
+        if let Some(init) = init {
+            init.start()?;
+        }
+
+        Ok(())
+    }
+
+    /// Ensure the isolation missing without namespaces is provided by Landlock.
+    ///
+    /// The hostname, clock offsets and IPC isolation cannot be provided by
+    /// Landlock, so this fails if `isolates_host_identity` or `isolates_ipc`
+    /// is `false`. The same applies to `isolates_processes`, which is only
+    /// `false` if a spawned sandbox has a mount namespace without a PID
+    /// namespace.
+    fn ensure_supported(
+        &self,
+        isolates_filesystem: bool,
+        isolates_network: bool,
+        isolates_host_identity: bool,
+        isolates_processes: bool,
+        isolates_ipc: bool,
+    ) -> RawResult<'static, ()> {
+        if !isolates_filesystem {
+            if landlock::abi_version().is_none() {
+                return Err(RawError::ActivationFailed(
+                    "neither user namespaces nor landlock are available",
+                ));
+            } else if !self.ruleset.enforces_denies() {
+                return Err(RawError::ActivationFailed("denying paths requires user namespaces"));
+            } else if self.mounts.iter().any(|(_, mount)| mount.is_private()) {
+                return Err(RawError::ActivationFailed(
+                    "exceptions creating new mounts require user namespaces",
+                ));
+            }
+        }
+
+        // Landlock cannot distinguish loopback from other TCP connections.
+        if !isolates_network && self.namespaces.allow_loopback {
+            return Err(RawError::ActivationFailed("loopback networking requires user namespaces"));
+        }
+
+        if !isolates_network && !self.ruleset.supports_networking() {
+            return Err(RawError::ActivationFailed(
+                "network isolation requires user namespaces or landlock ABI v4",
+            ));
+        }
+
+        if !isolates_host_identity {
+            return Err(RawError::ActivationFailed(
+                "hostname and clock offsets require user namespaces",
+            ));
+        }
+
+        if !isolates_processes {
+            return Err(RawError::ActivationFailed("process isolation requires PID namespaces"));
+        }
+
+        if !isolates_ipc {
+            return Err(RawError::ActivationFailed("IPC isolation requires user namespaces"));
+        }
 
         Ok(())
     }
 }
 
+/// Look up the name, home directory and shell of a user.
+fn lookup_user(uid: libc::uid_t) -> Option<(String, String, String)> {
+    let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut buffer = vec![0; PASSWD_BUFFER_SIZE];
+    let mut result = ptr::null_mut();
+    let ret = unsafe {
+        libc::getpwuid_r(uid, &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result)
+    };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+
+    let field = |field| unsafe { CStr::from_ptr(field) }.to_string_lossy().into_owned();
+    Some((field(passwd.pw_name), field(passwd.pw_dir), field(passwd.pw_shell)))
+}
+
+/// Look up the name of a group.
+fn lookup_group(gid: libc::gid_t) -> Option<String> {
+    let mut group: libc::group = unsafe { mem::zeroed() };
+    let mut buffer = vec![0; PASSWD_BUFFER_SIZE];
+    let mut result = ptr::null_mut();
+    let ret = unsafe {
+        libc::getgrgid_r(gid, &mut group, buffer.as_mut_ptr(), buffer.len(), &mut result)
+    };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+
+    Some(unsafe { CStr::from_ptr(group.gr_name) }.to_string_lossy().into_owned())
+}
+
 /// Prevent suid/sgid.
-fn no_new_privs() -> Result<()> {
+fn no_new_privs() -> RawResult<'static, ()> {
     let result = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
 
     match result {
         0 => Ok(()),
-        _ => Err(IoError::last_os_error().into()),
+        _ => Err(RawError::NoNewPrivs(errno(&IoError::last_os_error()))),
     }
 }
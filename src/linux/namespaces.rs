@@ -3,136 +3,791 @@
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
-use std::fs::{self, File};
+use std::fmt::Write;
+use std::fs::Metadata;
 use std::io::Error as IoError;
+use std::os::raw::{c_int, c_ulong};
 use std::os::unix::ffi::OsStrExt;
-use std::os::unix::fs as unixfs;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{AsRawFd, OwnedFd};
 use std::path::{Component, Path, PathBuf};
-use std::{env, io, mem, ptr};
+use std::result::Result as StdResult;
+use std::{env, io, mem, ptr, slice};
 
 use bitflags::bitflags;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use crate::error::Result;
+use crate::error::{errno, Error, RawError, RawResult, Result};
+use crate::linux::init::Init;
+use crate::linux::status::Status;
+use crate::linux::sys::{self, FormatBuffer};
+use crate::linux::{netlink, overlay, probe};
+use crate::report::{DroppedException, Layer};
 
 /// Path for mount namespace's new root.
 const NEW_ROOT: &str = "/tmp/birdcage-root";
 
+/// Filesystems replaced by private instances when IPC is isolated.
+pub const IPC_FILESYSTEMS: &[&str] = &["/dev/shm", "/dev/mqueue"];
+
+/// Devices bind mounted into the sandbox's minimal `/dev`.
+pub const DEVICES: &[&str] =
+    &["/dev/null", "/dev/zero", "/dev/full", "/dev/random", "/dev/urandom", "/dev/tty"];
+
+/// Mount point of the sandbox's private devpts instance.
+pub const DEVPTS: &str = "/dev/pts";
+
+/// Mount options for private devpts instances.
+///
+/// The `newinstance` option is implied since Linux 4.7, but required to keep
+/// host terminals hidden on older kernels.
+const DEVPTS_OPTIONS: &[u8] = b"newinstance,ptmxmode=0666,mode=0620\0";
+
+/// Symlinks created in the sandbox's minimal `/dev`.
+const DEV_SYMLINKS: &[(&str, &str)] = &[
+    ("fd", "/proc/self/fd"),
+    ("stdin", "/proc/self/fd/0"),
+    ("stdout", "/proc/self/fd/1"),
+    ("stderr", "/proc/self/fd/2"),
+    ("ptmx", "pts/ptmx"),
+];
+
+/// Mount point for temporary tmpfs mounts, like the ones holding overlay
+/// changes and file contents.
+const STAGING: &str = "/tmp/birdcage-staging";
+
+/// Mount options for private procfs instances.
+///
+/// This hides all processes which cannot be accessed by the sandbox and all
+/// procfs entries unrelated to processes, like `/proc/sys`.
+const PROCFS_OPTIONS: &[u8] = b"hidepid=invisible,subset=pid\0";
+
+/// Mount options for private procfs instances with [`Exception::FullProcfs`].
+///
+/// [`Exception::FullProcfs`]: crate::Exception::FullProcfs
+const FULL_PROCFS_OPTIONS: &[u8] = b"hidepid=invisible\0";
+
+/// Procfs entries replaced with an empty file.
+const PROCFS_MASKED: &[&str] = &["kcore", "keys", "timer_list", "sched_debug"];
+
+/// Procfs entries mounted read-only.
+const PROCFS_READ_ONLY: &[&str] = &["sys", "sysrq-trigger", "irq", "bus", "fs"];
+
+/// Device used to mask denied files.
+const DEV_NULL: &[u8] = b"/dev/null\0";
+
+/// User namespace UID mapping of the current process.
+const UID_MAP: &[u8] = b"/proc/self/uid_map\0";
+
+/// User namespace GID mapping of the current process.
+const GID_MAP: &[u8] = b"/proc/self/gid_map\0";
+
+/// Control for `setgroups` in the current process's user namespace.
+const SETGROUPS: &[u8] = b"/proc/self/setgroups\0";
+
+/// Maximum length of a single user namespace ID mapping.
+const ID_MAP_SIZE: usize = 32;
+
+/// Clock offsets of a new time namespace.
+const TIMENS_OFFSETS: &[u8] = b"/proc/self/timens_offsets\0";
+
+/// Maximum length of all clock offsets.
+const TIMENS_OFFSETS_SIZE: usize = 128;
+
+/// Time namespace of the current process's children.
+const TIME_FOR_CHILDREN: &[u8] = b"/proc/self/ns/time_for_children\0";
+
+/// Clock which can be offset inside the sandbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "snake_case"))]
+pub enum Clock {
+    /// `CLOCK_MONOTONIC`, including `CLOCK_MONOTONIC_RAW` and
+    /// `CLOCK_MONOTONIC_COARSE`.
+    Monotonic,
+    /// `CLOCK_BOOTTIME`, which also determines the system uptime.
+    Boottime,
+}
+
+impl Clock {
+    /// Name of the clock in `/proc/self/timens_offsets`.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Monotonic => "monotonic",
+            Self::Boottime => "boottime",
+        }
+    }
+}
+
+/// Configuration of the namespaces isolating the sandbox.
+#[derive(Clone, Default)]
+pub struct NamespaceConfig {
+    /// Share the host's network namespace.
+    pub allow_networking: bool,
+    /// Bring up the loopback interface of an isolated network namespace.
+    pub allow_loopback: bool,
+    /// Share the host's IPC namespace.
+    pub allow_host_ipc: bool,
+    /// Expose all procfs entries instead of only processes.
+    pub full_procfs: bool,
+    /// Isolate processes with a PID namespace, which forks the current
+    /// process.
+    pub pid_namespace: bool,
+    /// Hostname inside the UTS namespace.
+    pub hostname: Option<String>,
+    /// NIS domain name inside the UTS namespace.
+    pub domainname: Option<String>,
+    /// Clock offsets in seconds inside the time namespace.
+    pub clock_offsets: Vec<(Clock, i64)>,
+}
+
+impl NamespaceConfig {
+    /// Check if a UTS namespace is required.
+    pub fn isolates_uts(&self) -> bool {
+        self.hostname.is_some() || self.domainname.is_some()
+    }
+
+    /// Check if a time namespace is required.
+    pub fn isolates_time(&self) -> bool {
+        !self.clock_offsets.is_empty()
+    }
+}
+
 /// Isolate process using Linux namespaces.
 ///
-/// If successful, this will always clear the abstract namespace.
+/// If any namespace was created, this will always clear the abstract namespace.
+///
+/// If a PID namespace was created, its [`Init`] must be started once all other
+/// sandboxing layers are applied.
+///
+/// Additionally it will isolate network access unless networking is allowed
+/// by the `config`, bringing up the isolated network namespace's loopback
+/// interface if requested. System V IPC and POSIX message queues are isolated
+/// with an IPC namespace, unless host IPC is allowed.
+///
+/// UTS and time namespaces are only created if the `config` sets a hostname
+/// or clock offsets.
+///
+/// All namespaces are recorded in the `status`. Namespaces which could not be
+/// created are skipped if `skippable` returns `true` for their error.
 ///
-/// Additionally it will isolate network access if `allow_networking` is
-/// `false`.
-pub fn create_namespaces(
-    allow_networking: bool,
-    bind_mounts: HashMap<PathBuf, MountAttrFlags>,
-) -> Result<()> {
+/// This does not allocate, so it is safe to call in a forked child.
+pub(crate) fn create_namespaces<'a>(
+    config: &'a NamespaceConfig,
+    plan: &'a MountPlan,
+    status: &mut Status<'a>,
+    skippable: impl Fn(&RawError<'a>) -> bool,
+) -> RawResult<'a, Option<Init>> {
     // Get EUID/EGID outside of the namespace.
-    let uid = unsafe { libc::geteuid() };
-    let gid = unsafe { libc::getegid() };
+    let (uid, gid) = mapped_ids();
 
     // Setup the network namespace.
-    if !allow_networking {
-        create_user_namespace(0, 0, Namespaces::NETWORK)?;
+    let mut isolated = false;
+    if !config.allow_networking {
+        let result = create_network_namespace(config.allow_loopback);
+        isolated |= status.record(Layer::NetworkNamespace, result, &skippable)?;
     }
 
-    // Isolate filesystem and procfs.
-    create_mount_namespace(bind_mounts)?;
+    // Setup hostname and domain name.
+    if config.isolates_uts() {
+        let result = create_uts_namespace(config);
+        isolated |= status.record(Layer::UtsNamespace, result, &skippable)?;
+    }
+
+    // Setup clock offsets, which must happen before the PID namespace's init
+    // is forked to apply to all sandboxed processes.
+    if config.isolates_time() {
+        let result = create_time_namespace(&config.clock_offsets);
+        isolated |= status.record(Layer::TimeNamespace, result, &skippable)?;
+    }
+
+    // Isolate filesystem, processes, procfs and IPC.
+    let allow_host_ipc = config.allow_host_ipc;
+    let mut init = None;
+    if !probe::mount_setattr_missing() {
+        let result = create_mount_namespace(plan, config, status, &skippable, &mut init);
+        isolated |= status.record(Layer::MountNamespace, result, &skippable)?;
+    } else {
+        status.skip(Layer::MountNamespace, "mount_setattr is not supported by the kernel");
+    }
+
+    // Isolate IPC on its own if it wasn't isolated with the mount namespace.
+    if !allow_host_ipc && !status.is_applied(Layer::IpcNamespace) {
+        let result = create_user_namespace(0, 0, Namespaces::IPC);
+        isolated |= status.record(Layer::IpcNamespace, result, &skippable)?;
+    }
 
     // Drop root user mapping and ensure abstract namespace is cleared.
-    create_user_namespace(uid, gid, Namespaces::empty())?;
+    if isolated {
+        create_user_namespace(uid, gid, Namespaces::empty())?;
+    }
+
+    Ok(init)
+}
+
+/// Create a network namespace to isolate network access.
+fn create_network_namespace(allow_loopback: bool) -> RawResult<'static, ()> {
+    create_user_namespace(0, 0, Namespaces::NETWORK)?;
+
+    if allow_loopback {
+        netlink::set_loopback_up()?;
+    }
 
     Ok(())
 }
 
-/// Create a mount namespace to isolate filesystem access.
+/// Create a UTS namespace with the configured hostname and domain name.
+fn create_uts_namespace(config: &NamespaceConfig) -> RawResult<'static, ()> {
+    create_user_namespace(0, 0, Namespaces::UTS)?;
+
+    if let Some(hostname) = &config.hostname {
+        let result = unsafe { libc::sethostname(hostname.as_ptr().cast(), hostname.len()) };
+        if result != 0 {
+            return Err(RawError::Hostname(errno(&IoError::last_os_error())));
+        }
+    }
+
+    if let Some(domainname) = &config.domainname {
+        let result = unsafe { libc::setdomainname(domainname.as_ptr().cast(), domainname.len()) };
+        if result != 0 {
+            return Err(RawError::Domainname(errno(&IoError::last_os_error())));
+        }
+    }
+
+    Ok(())
+}
+
+/// Create a time namespace with offset clocks and enter it.
+fn create_time_namespace(offsets: &[(Clock, i64)]) -> RawResult<'static, ()> {
+    create_user_namespace(0, 0, Namespaces::TIME)?;
+
+    // Offsets can only be set before any process entered the namespace.
+    let mut buffer = FormatBuffer::<TIMENS_OFFSETS_SIZE>::new();
+    for (clock, offset) in offsets {
+        writeln!(buffer, "{} {offset} 0", clock.name())
+            .map_err(|_| RawError::ActivationFailed("too many clock offsets"))?;
+    }
+    let offsets_path = cstr(TIMENS_OFFSETS);
+    sys::write_file(offsets_path, buffer.as_bytes())
+        .map_err(|error| RawError::TimeNamespace { path: offsets_path, errno: errno(&error) })?;
+
+    // Unlike other namespaces, only children are moved into the new time
+    // namespace, so the current process has to join it explicitly.
+    let namespace_path = cstr(TIME_FOR_CHILDREN);
+    let time_error =
+        |error: IoError| RawError::TimeNamespace { path: namespace_path, errno: errno(&error) };
+    let namespace = sys::open(namespace_path, libc::O_RDONLY, 0).map_err(time_error)?;
+    let result = unsafe { libc::setns(namespace.as_raw_fd(), Namespaces::TIME.bits()) };
+    if result != 0 {
+        return Err(time_error(IoError::last_os_error()));
+    }
+
+    Ok(())
+}
+
+/// Check if a namespace creation error is caused by missing support.
 ///
-/// This will deny access to any path which isn't part of `bind_mounts`. Allowed
-/// paths are mounted according to their bind mount flags.
-fn create_mount_namespace(bind_mounts: HashMap<PathBuf, MountAttrFlags>) -> Result<()> {
-    // Create mount namespace to allow creation of new mounts.
-    create_user_namespace(0, 0, Namespaces::MOUNT)?;
+/// This is the case when unprivileged user namespaces are disabled or blocked
+/// by a container's seccomp profile. Failures after the namespace was created
+/// are never considered unsupported.
+pub(crate) fn is_unsupported(error: &RawError<'_>) -> bool {
+    matches!(
+        error,
+        RawError::UserNamespace(
+            libc::EPERM | libc::EACCES | libc::ENOSPC | libc::EUSERS | libc::ENOSYS
+        )
+    )
+}
 
-    // Get target paths for new and old root.
-    let new_root = PathBuf::from(NEW_ROOT);
+/// Filesystem mounts with resolved paths.
+///
+/// Resolving paths happens ahead of time, which makes it possible to perform
+/// all path lookups before the sandbox is applied to a forked child process.
+#[derive(Default)]
+pub struct Mounts {
+    mounts: Vec<(PathBuf, Mount)>,
+    symlinks: Vec<(PathBuf, PathBuf)>,
+    dropped: Vec<DroppedException>,
+}
 
-    // Ensure new root is available as an empty directory.
-    if !new_root.exists() {
-        fs::create_dir_all(&new_root)?;
+impl Mounts {
+    /// Canonicalize paths and resolve symlinks.
+    ///
+    /// Paths which cannot be resolved anymore are dropped, like relative paths
+    /// when the working directory cannot be accessed.
+    pub fn new(mounts: HashMap<PathBuf, Mount>) -> Self {
+        let mut symlinks = Vec::new();
+        let mut dropped = Vec::new();
+        let mut mounts = mounts
+            .into_iter()
+            .filter_map(|(path, mut mount)| match resolve(&path, &mut mount, &mut symlinks) {
+                Ok(canonicalized) => Some((canonicalized, mount)),
+                Err(reason) => {
+                    dropped.push(DroppedException { path, reason });
+                    None
+                },
+            })
+            .collect::<Vec<_>>();
+
+        // Sort mounts by shortest length, to create parents before their children.
+        mounts.sort_unstable_by(|(a_path, a_mount), (b_path, b_mount)| {
+            match a_path.components().count().cmp(&b_path.components().count()) {
+                Ordering::Equal => (a_path, a_mount).cmp(&(b_path, b_mount)),
+                ord => ord,
+            }
+        });
+
+        // Merge exceptions for different paths resolving to the same target.
+        mounts.dedup_by(|(path, mount), (kept_path, kept_mount)| {
+            if path != kept_path {
+                return false;
+            }
+
+            *kept_mount = match (&*kept_mount, &*mount) {
+                (Mount::Bind(kept_flags), Mount::Bind(flags)) => Mount::Bind(*kept_flags & *flags),
+                (Mount::Deny, _) | (_, Mount::Deny) => Mount::Deny,
+                // Other mounts replace bind mounts.
+                (Mount::Bind(_), mount) | (mount, _) => mount.clone(),
+            };
+
+            true
+        });
+
+        Self { mounts, symlinks, dropped }
     }
 
-    // Create C-friendly versions for our paths.
-    let new_root_c = CString::new(new_root.as_os_str().as_bytes()).unwrap();
+    /// Iterate over all mount paths.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Mount)> {
+        self.mounts.iter().map(|(path, mount)| (path.as_path(), mount))
+    }
 
-    // Create tmpfs mount for the new root, allowing pivot and ensuring directories
-    // aren't created outside the sandbox.
-    mount_tmpfs(&new_root_c)?;
+    /// Get all exceptions which were dropped while resolving their paths.
+    pub fn dropped(&self) -> &[DroppedException] {
+        &self.dropped
+    }
+}
 
-    // Canonicalize paths and resolve symlinks.
-    //
-    // If the working directory cannot be accessed, we ignore relative paths.
-    let mut symlinks = Vec::new();
-    let mut bind_mounts = bind_mounts
-        .into_iter()
-        .filter_map(|(path, exception)| {
-            let canonicalized = path.canonicalize().ok()?;
-
-            // Store original symlink path to create it if necessary.
-            if path_has_symlinks(&path) {
-                // Normalize symlink's path.
-                let absolute = absolute(&path).ok()?;
-                let normalized = normalize_path(&absolute);
-
-                symlinks.push((normalized, canonicalized.clone()));
+/// Canonicalize a mount's path.
+///
+/// The normalized path of symlinks is stored in `symlinks`, to create them if
+/// necessary.
+fn resolve(
+    path: &Path,
+    mount: &mut Mount,
+    symlinks: &mut Vec<(PathBuf, PathBuf)>,
+) -> StdResult<PathBuf, String> {
+    // Remapped targets and files might not exist on the host, so only the
+    // source is resolved.
+    match mount {
+        Mount::Remap { source, .. } => {
+            *source =
+                source.canonicalize().map_err(|err| format!("could not resolve source: {err}"))?;
+            return Ok(normalize_path(path));
+        },
+        Mount::File { .. } => return Ok(normalize_path(path)),
+        _ => (),
+    }
+
+    let canonicalized =
+        path.canonicalize().map_err(|err| format!("could not resolve path: {err}"))?;
+
+    // Store original symlink path to create it if necessary.
+    if !matches!(mount, Mount::Deny) && path_has_symlinks(path) {
+        // Normalize symlink's path.
+        let absolute =
+            absolute(path).map_err(|err| format!("could not resolve relative path: {err}"))?;
+        let normalized = normalize_path(&absolute);
+
+        symlinks.push((normalized, canonicalized.clone()));
+    }
+
+    Ok(canonicalized)
+}
+
+/// Filesystem mount inside the sandbox.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Mount {
+    /// Bind mount the path with the specified flags.
+    Bind(MountAttrFlags),
+    /// Hide the path and everything beneath it.
+    Deny,
+    /// Replace the path with an empty tmpfs.
+    Tmpfs { size: u64, mode: u32 },
+    /// Mount a writable overlay on top of the path with the specified flags.
+    ///
+    /// Changes are stored in `changes`, or discarded if it is `None`.
+    Overlay { flags: MountAttrFlags, changes: Option<PathBuf> },
+    /// Bind mount a device node with read and write access.
+    Device,
+    /// Bind mount `source` at the path with the specified flags.
+    Remap { source: PathBuf, flags: MountAttrFlags },
+    /// Mount a file with the specified contents and permissions.
+    File { contents: Vec<u8>, mode: u32 },
+}
+
+impl Mount {
+    /// Check if the mount only exists inside the mount namespace.
+    pub fn is_private(&self) -> bool {
+        matches!(
+            self,
+            Self::Tmpfs { .. } | Self::Overlay { .. } | Self::Remap { .. } | Self::File { .. }
+        )
+    }
+}
+
+/// Mount namespace prepared for creation.
+///
+/// All paths, mount options and file contents are converted to their C
+/// representation ahead of time, so the mount namespace can be created without
+/// allocating. This is required for sandboxing forked children of processes
+/// with multiple threads.
+pub struct MountPlan {
+    new_root: CString,
+    new_root_dirs: Vec<CString>,
+    staging: CString,
+    staging_dirs: Vec<CString>,
+    staging_file: CString,
+    mounts: Vec<PlannedMount>,
+    dev_index: usize,
+    dev: PlannedDev,
+    ipc: Vec<(CString, bool)>,
+    symlinks: Vec<PlannedSymlink>,
+    proc: PlannedProc,
+}
+
+impl MountPlan {
+    /// Prepare all `mounts` for creation under the new root.
+    pub fn new(mounts: &Mounts) -> Result<Self> {
+        let tree = Tree::plan(mounts)?;
+        let staging = Path::new(STAGING);
+
+        let mut planned = Vec::new();
+        let mut dev_index = None;
+        for (path, mount) in &mounts.mounts {
+            // Replace `/dev` after mounting its parents, but before its children.
+            if dev_index.is_none() && path.starts_with("/dev") && path != Path::new("/dev") {
+                dev_index = Some(planned.len());
             }
 
-            Some((canonicalized, exception))
-        })
-        .collect::<Vec<_>>();
+            // Default devices are always available with read and write access.
+            if matches!(mount, Mount::Bind(_)) && is_default_device(path) {
+                continue;
+            }
+
+            let operation = match mount {
+                Mount::Bind(flags) | Mount::Remap { flags, .. } => Operation::Bind(*flags),
+                Mount::Deny => Operation::Deny,
+                Mount::Tmpfs { size, mode } => {
+                    Operation::Tmpfs(CString::new(format!("size={size},mode={mode:o}")).unwrap())
+                },
+                Mount::Overlay { flags, changes } => {
+                    let upper = changes.as_deref().unwrap_or(staging).join(overlay::UPPER_DIR);
+                    let work = changes.as_deref().unwrap_or(staging).join(overlay::WORK_DIR);
+
+                    // Extended attributes in the `user` namespace are required without root.
+                    let mut options = Vec::new();
+                    for (key, path) in
+                        [("lowerdir", path), ("upperdir", &upper), ("workdir", &work)]
+                    {
+                        options.extend_from_slice(format!("{key}=").as_bytes());
+                        options.extend_from_slice(path.as_os_str().as_bytes());
+                        options.push(b',');
+                    }
+                    options.extend_from_slice(b"userxattr");
+
+                    Operation::Overlay {
+                        flags: *flags,
+                        options: CString::new(options).unwrap(),
+                        upper: path_cstring(&upper),
+                        work: path_cstring(&work),
+                        staged: changes.is_none(),
+                    }
+                },
+                Mount::Device => Operation::Device,
+                Mount::File { contents, mode } => {
+                    Operation::File { contents: contents.clone(), mode: *mode }
+                },
+            };
+
+            let source = match mount {
+                Mount::Remap { source, .. } => source,
+                _ => path,
+            };
+
+            planned.push(PlannedMount {
+                source: path_cstring(source),
+                path: path_cstring(path),
+                target: path_cstring(&rooted(path)),
+                nodes: tree.nodes(path)?,
+                operation,
+            });
+        }
 
-    // Sort bind mounts by shortest length, to create parents before their children.
-    bind_mounts.sort_unstable_by(|(a_path, a_flags), (b_path, b_flags)| {
-        match a_path.components().count().cmp(&b_path.components().count()) {
-            Ordering::Equal => (a_path, a_flags).cmp(&(b_path, b_flags)),
-            ord => ord,
+        let mut symlinks = Vec::new();
+        for (symlink, target) in &mounts.symlinks {
+            let parent = match symlink.parent() {
+                Some(parent) => parent,
+                None => continue,
+            };
+
+            symlinks.push(PlannedSymlink {
+                link: path_cstring(&rooted(symlink)),
+                target: path_cstring(target),
+                parents: tree.nodes(parent)?,
+            });
         }
-    });
 
-    // Bind mount all allowed directories.
-    for (path, flags) in bind_mounts {
-        let src_c = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let dev = rooted(Path::new("/dev"));
+        let dev = PlannedDev {
+            devices: DEVICES
+                .iter()
+                .map(|device| {
+                    let device = Path::new(device);
+                    (path_cstring(device), path_cstring(&rooted(device)))
+                })
+                .collect(),
+            pts: path_cstring(&rooted(Path::new(DEVPTS))),
+            symlinks: DEV_SYMLINKS
+                .iter()
+                .map(|(name, target)| {
+                    (path_cstring(Path::new(target)), path_cstring(&dev.join(name)))
+                })
+                .collect(),
+            dir: path_cstring(&dev),
+        };
+
+        let ipc = IPC_FILESYSTEMS
+            .iter()
+            .map(|path| (path_cstring(&rooted(Path::new(path))), *path == "/dev/mqueue"))
+            .collect();
+
+        let proc = rooted(Path::new("/proc"));
+        let proc = PlannedProc {
+            masked: PROCFS_MASKED.iter().map(|entry| path_cstring(&proc.join(entry))).collect(),
+            read_only: PROCFS_READ_ONLY
+                .iter()
+                .map(|entry| path_cstring(&proc.join(entry)))
+                .collect(),
+            dir: path_cstring(&proc),
+        };
+
+        Ok(Self {
+            new_root: path_cstring(Path::new(NEW_ROOT)),
+            new_root_dirs: dir_chain(Path::new(NEW_ROOT)),
+            staging: path_cstring(staging),
+            staging_dirs: dir_chain(staging),
+            staging_file: path_cstring(&staging.join("file")),
+            dev_index: dev_index.unwrap_or(planned.len()),
+            mounts: planned,
+            dev,
+            ipc,
+            symlinks,
+            proc,
+        })
+    }
+}
+
+/// Mount prepared for creation under the new root.
+struct PlannedMount {
+    source: CString,
+    path: CString,
+    target: CString,
+    nodes: Vec<PlannedNode>,
+    operation: Operation,
+}
+
+/// Operation creating a [`PlannedMount`].
+enum Operation {
+    /// Bind mount the source with the specified flags.
+    Bind(MountAttrFlags),
+    /// Mask the target.
+    Deny,
+    /// Mount a tmpfs with the specified options.
+    Tmpfs(CString),
+    /// Mount an overlay with the specified options.
+    ///
+    /// The upper and work directories are on a discarded staging tmpfs if
+    /// `staged` is `true`.
+    Overlay { flags: MountAttrFlags, options: CString, upper: CString, work: CString, staged: bool },
+    /// Bind mount a device node.
+    Device,
+    /// Mount a file with the specified contents and permissions.
+    File { contents: Vec<u8>, mode: u32 },
+}
+
+/// Node of the new root's directory [`Tree`].
+struct PlannedNode {
+    path: CString,
+    directory: bool,
+    mode: u32,
+}
+
+/// Symlink recreated under the new root.
+struct PlannedSymlink {
+    link: CString,
+    target: CString,
+    parents: Vec<PlannedNode>,
+}
+
+/// Minimal `/dev` under the new root.
+struct PlannedDev {
+    dir: CString,
+    devices: Vec<(CString, CString)>,
+    pts: CString,
+    symlinks: Vec<(CString, CString)>,
+}
+
+/// Procfs under the new root.
+struct PlannedProc {
+    dir: CString,
+    masked: Vec<CString>,
+    read_only: Vec<CString>,
+}
+
+/// Create a mount namespace to isolate filesystem access.
+///
+/// This will deny access to any path which isn't part of the `plan`. Allowed
+/// paths are mounted according to their bind mount flags, tmpfs paths are
+/// replaced with an empty tmpfs and overlay paths are covered by a writable
+/// overlay, while denied paths are masked with an empty, read-only mount.
+///
+/// Devices can only be accessed through device mounts and the minimal `/dev`,
+/// all other mounts are created with `NODEV`.
+///
+/// If requested by the `config`, processes are isolated with a PID namespace,
+/// storing its [`Init`] in `init`. The PID namespace is skipped if `skippable`
+/// returns `true` for its error.
+///
+/// Unless host IPC is allowed by the `config`, an IPC namespace is created
+/// together with the mount namespace, which allows mounting its private
+/// `/dev/mqueue`.
+fn create_mount_namespace<'a>(
+    plan: &'a MountPlan,
+    config: &NamespaceConfig,
+    status: &mut Status<'a>,
+    skippable: impl Fn(&RawError<'a>) -> bool,
+    init: &mut Option<Init>,
+) -> RawResult<'a, ()> {
+    let isolate_ipc = !config.allow_host_ipc;
+
+    // Create mount namespace to allow creation of new mounts.
+    if isolate_ipc {
+        create_user_namespace(0, 0, Namespaces::MOUNT | Namespaces::IPC)?;
+        status.apply(Layer::IpcNamespace);
+    } else {
+        create_user_namespace(0, 0, Namespaces::MOUNT)?;
+    }
+
+    // Create PID namespace, which requires a fresh procfs mount.
+    if config.pid_namespace {
+        let result = Init::create().map(|pid_init| *init = Some(pid_init));
+        status.record(Layer::PidNamespace, result, skippable)?;
+    } else {
+        status.skip(Layer::PidNamespace, "PID namespaces require `Sandbox::spawn`");
+    }
 
-        // Get bind mount destination.
-        let unrooted_path = path.strip_prefix("/").unwrap();
-        let dst = new_root.join(unrooted_path);
-        let dst_c = CString::new(dst.as_os_str().as_bytes()).unwrap();
+    // Ensure new root is available as an empty directory.
+    create_dir_all(&plan.new_root_dirs)?;
+
+    // Create tmpfs mount for the new root, allowing pivot and ensuring directories
+    // aren't created outside the sandbox.
+    mount_tmpfs(&plan.new_root, MountFlags::NOSUID | MountFlags::NODEV, cstr(b"\0"))?;
+
+    // Create all allowed and denied mounts.
+    for (i, mount) in plan.mounts.iter().enumerate() {
+        // Replace `/dev` after mounting its parents, but before its children.
+        if i == plan.dev_index {
+            create_dev(&plan.dev)?;
+        }
 
         // Create mount target.
-        copy_tree(&path, &new_root)?;
+        create_nodes(&mount.nodes)?;
 
-        // Bind path with full permissions.
-        bind_mount(&src_c, &dst_c)?;
+        let (src, dst) = (mount.source.as_c_str(), mount.target.as_c_str());
+        match &mount.operation {
+            Operation::Bind(flags) => {
+                // Bind path with full permissions.
+                let tree = bind_mount_in_root(plan, src, &mount.path, dst)?;
 
-        // Remount to update permissions.
-        update_mount_flags(&dst_c, flags | MountAttrFlags::NOSUID)?;
+                // Remount to update permissions.
+                let flags = *flags | MountAttrFlags::NOSUID | MountAttrFlags::NODEV;
+                update_tree_flags(&tree, dst, flags, true)?;
+            },
+            Operation::Tmpfs(options) => {
+                // Replace the path with an empty, size-limited tmpfs.
+                let flags = MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC;
+                mount_tmpfs(dst, flags, options)?;
+            },
+            Operation::Overlay { flags, options, upper, work, staged } => {
+                // Redirect all writes to the overlay's upper directory.
+                mount_overlay(plan, dst, options, [upper, work], *staged)?;
+                let flags = *flags | MountAttrFlags::NOSUID | MountAttrFlags::NODEV;
+                update_mount_flags(dst, flags, false)?;
+            },
+            Operation::File { contents, mode } => {
+                // Mount a copy of the file, to discard all changes to it.
+                let tree = mount_file(plan, &mount.path, dst, contents, *mode)?;
+                let mut flags = MountAttrFlags::NOSUID | MountAttrFlags::NODEV;
+                if mode & 0o111 == 0 {
+                    flags |= MountAttrFlags::NOEXEC;
+                }
+                update_tree_flags(&tree, dst, flags, false)?;
+            },
+            Operation::Device => {
+                // Bind device without `NODEV`, to allow opening it.
+                bind_mount(src, dst)?;
+                let flags = MountAttrFlags::NOSUID | MountAttrFlags::NOEXEC;
+                update_mount_flags(dst, flags, false)?;
+            },
+            Operation::Deny => match sys::stat(dst) {
+                Ok(metadata) if sys::is_dir(&metadata) => {
+                    // Mask directories with an empty tmpfs.
+                    //
+                    // The tmpfs is only made read-only once all mounts are
+                    // created, to allow creating mount targets for nested
+                    // exceptions.
+                    mount_tmpfs(dst, MountFlags::empty(), cstr(b"\0"))?;
+                    sys::chmod(dst, metadata.st_mode).map_err(|error| node_error(dst, &error))?;
+                },
+                _ => {
+                    // Mask files with an inaccessible `/dev/null`.
+                    bind_mount(cstr(DEV_NULL), dst)?;
+                    update_mount_flags(dst, MountAttrFlags::MASK, true)?;
+                },
+            },
+        }
+    }
+
+    // Create `/dev` if there were no mounts beneath it.
+    if plan.dev_index == plan.mounts.len() {
+        create_dev(&plan.dev)?;
+    }
+
+    // Replace the host's shared memory and message queues with private ones.
+    if isolate_ipc {
+        mount_ipc_filesystems(&plan.ipc)?;
+    }
+
+    // Prevent writes to `/dev` and masked directories.
+    update_mount_flags(&plan.dev.dir, MountAttrFlags::MASK, false)?;
+    for mount in &plan.mounts {
+        let is_dir = sys::stat(&mount.target).map_or(false, |metadata| sys::is_dir(&metadata));
+        if matches!(mount.operation, Operation::Deny) && is_dir {
+            update_mount_flags(&mount.target, MountAttrFlags::MASK, false)?;
+        }
     }
 
     // Ensure original symlink paths are available.
-    create_symlinks(&new_root, symlinks)?;
+    create_symlinks(&plan.symlinks)?;
 
-    // Bind mount old procfs.
-    let old_proc_c = CString::new("/proc").unwrap();
-    let new_proc = new_root.join("proc");
-    let new_proc_c = CString::new(new_proc.as_os_str().as_bytes()).unwrap();
-    fs::create_dir_all(&new_proc)?;
-    bind_mount(&old_proc_c, &new_proc_c).unwrap();
+    // Mount procfs of the new PID namespace, or bind mount the old procfs.
+    create_procfs(&plan.proc, init.is_some(), config.full_procfs)?;
 
     // Pivot root to `new_root`, placing the old root at the same location.
-    pivot_root(&new_root_c, &new_root_c)?;
+    pivot_root(&plan.new_root, &plan.new_root)?;
 
     // Remove old root mounted at /, leaving only the new root at the same location.
-    let root_c = CString::new("/").unwrap();
-    umount(&root_c)?;
+    umount(cstr(b"/\0"))?;
 
     // Prevent child mount namespaces from accessing this namespace's mounts.
     deny_mount_propagation()?;
@@ -140,116 +795,492 @@ fn create_mount_namespace(bind_mounts: HashMap<PathBuf, MountAttrFlags>) -> Resu
     Ok(())
 }
 
+/// Replace `/dev` with a minimal tmpfs.
+///
+/// Only the default [`DEVICES`] are bind mounted from the host, together with
+/// a private devpts instance and the standard symlinks into procfs. The tmpfs
+/// itself is still writable, so it must be made read-only by the caller.
+fn create_dev(dev: &PlannedDev) -> RawResult<'_, ()> {
+    create_dir_all(slice::from_ref(&dev.dir))?;
+
+    let flags = MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC;
+    mount_tmpfs(&dev.dir, flags, cstr(b"mode=755\0"))?;
+
+    // Bind devices without `NODEV`, to allow opening them.
+    for (src, dst) in &dev.devices {
+        if sys::lstat(src).is_err() {
+            continue;
+        }
+
+        sys::create_file(dst).map_err(|error| node_error(dst, &error))?;
+        bind_mount(src, dst)?;
+        update_mount_flags(dst, MountAttrFlags::NOSUID | MountAttrFlags::NOEXEC, false)?;
+    }
+
+    // Mount a private devpts, hiding the host's terminals.
+    sys::mkdir(&dev.pts)
+        .map_err(|error| RawError::CreateDir { path: &dev.pts, errno: errno(&error) })?;
+    mount_devpts(&dev.pts)?;
+
+    for (target, link) in &dev.symlinks {
+        sys::symlink(target, link).map_err(|error| node_error(link, &error))?;
+    }
+
+    Ok(())
+}
+
+/// Check if a path is part of the sandbox's minimal `/dev`.
+pub fn is_default_device(path: &Path) -> bool {
+    path.starts_with(DEVPTS) || DEVICES.iter().any(|device| path == Path::new(device))
+}
+
+/// Mount a private `/dev/shm` and the IPC namespace's `/dev/mqueue`.
+///
+/// Filesystems are skipped if their directory cannot be created, because its
+/// parent is a read-only mount without the host's mount point.
+fn mount_ipc_filesystems(filesystems: &[(CString, bool)]) -> RawResult<'_, ()> {
+    for (dst, is_mqueue) in filesystems {
+        let is_dir = sys::stat(dst).map_or(false, |metadata| sys::is_dir(&metadata));
+        if !is_dir && create_dir_all(slice::from_ref(dst)).is_err() {
+            continue;
+        }
+
+        if *is_mqueue {
+            mount_mqueue(dst)?;
+        } else {
+            let flags = MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC;
+            mount_tmpfs(dst, flags, cstr(b"mode=1777\0"))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Create the procfs.
+///
+/// With a PID namespace, a private procfs is mounted which only exposes the
+/// processes inside the namespace, together with all other entries if `full`
+/// is `true`. Otherwise the host's procfs is bind mounted.
+///
+/// Failing to mount the private procfs is an error, since falling back to the
+/// host's procfs would expose processes outside of the PID namespace.
+///
+/// Sensitive entries are masked or made read-only in both cases.
+fn create_procfs(proc: &PlannedProc, pid_namespace: bool, full: bool) -> RawResult<'_, ()> {
+    create_dir_all(slice::from_ref(&proc.dir))?;
+
+    // Try to hide inaccessible processes and entries, falling back to all
+    // procfs entries on kernels without support for these options (Linux <5.8).
+    if pid_namespace {
+        let options = if full { FULL_PROCFS_OPTIONS } else { PROCFS_OPTIONS };
+        if mount_procfs(&proc.dir, cstr(options)).is_err() {
+            mount_procfs(&proc.dir, cstr(b"\0"))?;
+        }
+    } else {
+        bind_mount(cstr(b"/proc\0"), &proc.dir)?;
+        let flags = MountAttrFlags::NOSUID | MountAttrFlags::NODEV | MountAttrFlags::NOEXEC;
+        update_mount_flags(&proc.dir, flags, true)?;
+    }
+
+    // Mask entries exposing kernel state.
+    for path in &proc.masked {
+        if sys::stat(path).is_ok() {
+            bind_mount(cstr(DEV_NULL), path)?;
+            update_mount_flags(path, MountAttrFlags::MASK, true)?;
+        }
+    }
+
+    // Prevent modification of kernel settings.
+    for path in &proc.read_only {
+        if sys::stat(path).is_ok() {
+            bind_mount(path, path)?;
+            update_mount_flags(path, MountAttrFlags::MASK, true)?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Create missing symlinks.
 ///
 /// If the parent directory of a symlink is mapped, we do not need to map the
 /// symlink ourselves and it's not possible to mount on top of it anyway. So
 /// here we make sure that symlinks are created if no bind mount was created for
 /// their parent directory.
-fn create_symlinks(new_root: &Path, symlinks: Vec<(PathBuf, PathBuf)>) -> Result<()> {
-    for (symlink, target) in symlinks {
+fn create_symlinks(symlinks: &[PlannedSymlink]) -> RawResult<'_, ()> {
+    for symlink in symlinks {
         // Ignore symlinks if a parent bind mount exists.
-        let unrooted_path = symlink.strip_prefix("/").unwrap();
-        let dst = new_root.join(unrooted_path);
-        if dst.symlink_metadata().is_ok() {
+        if sys::lstat(&symlink.link).is_ok() {
             continue;
         }
 
         // Create all parent directories.
-        let parent = match symlink.parent() {
-            Some(parent) => parent,
-            None => continue,
-        };
-        copy_tree(parent, new_root)?;
+        create_nodes(&symlink.parents)?;
 
         // Create the symlink.
-        unixfs::symlink(target, dst)?;
+        sys::symlink(&symlink.target, &symlink.link)
+            .map_err(|error| node_error(&symlink.link, &error))?;
     }
 
     Ok(())
 }
 
-/// Replicate a directory tree under a different directory.
-///
-/// This will create all missing empty diretories and copy their permissions
-/// from the source tree.
+/// Replicate a part of the [`Tree`] under the new root.
 ///
-/// If `src` ends in a file, an empty file with matching permissions will be
-/// created.
-fn copy_tree(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
-    let mut dst = dst.as_ref().to_path_buf();
-    let mut src_sub = PathBuf::new();
-    let src = src.as_ref();
-
-    for component in src.components() {
-        // Append root only to source.
-        if component == Component::RootDir {
-            src_sub = src_sub.join(component);
-            continue;
-        }
-
-        src_sub = src_sub.join(component);
-        dst = dst.join(component);
-
+/// This will create all missing empty directories and files with permissions
+/// matching the original tree.
+fn create_nodes(nodes: &[PlannedNode]) -> RawResult<'_, ()> {
+    for node in nodes {
         // Skip nodes that already exist.
-        if dst.exists() {
+        if sys::stat(&node.path).is_ok() {
             continue;
         }
 
         // Create target file/directory.
-        let metadata = src_sub.metadata()?;
-        if metadata.is_dir() {
-            fs::create_dir(&dst)?;
+        let result = if node.directory {
+            sys::mkdir(&node.path)
         } else {
-            File::create(&dst)?;
-        }
+            sys::create_file(&node.path).map(drop)
+        };
 
         // Copy permissions.
-        let permissions = metadata.permissions();
-        fs::set_permissions(&dst, permissions)?;
+        result
+            .and_then(|_| sys::chmod(&node.path, node.mode))
+            .map_err(|error| node_error(&node.path, &error))?;
     }
 
     Ok(())
 }
 
-/// Mount a new tmpfs.
-fn mount_tmpfs(dst: &CStr) -> Result<()> {
-    let flags = MountFlags::empty();
-    let fstype = CString::new("tmpfs").unwrap();
+/// Create all missing `directories`, which must be ordered parents first.
+fn create_dir_all(directories: &[CString]) -> RawResult<'_, ()> {
+    for directory in directories {
+        match sys::mkdir(directory) {
+            Err(error) if error.raw_os_error() != Some(libc::EEXIST) => {
+                return Err(RawError::CreateDir { path: directory, errno: errno(&error) });
+            },
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Create the error for a node which could not be replicated at `path`.
+fn node_error<'a>(path: &'a CStr, error: &IoError) -> RawError<'a> {
+    RawError::CreateNode { path, errno: errno(error) }
+}
+
+/// Directory tree replicated under the new root.
+///
+/// This contains the metadata of all mount targets and symlink parents,
+/// including all of their parent directories, which is read ahead of time.
+struct Tree {
+    nodes: HashMap<PathBuf, TreeNode>,
+}
+
+impl Tree {
+    /// Read the metadata of all nodes required by the `mounts`.
+    ///
+    /// Remapped targets and files use the metadata of their mount, since they
+    /// might not exist on the host. Their missing parents are created as
+    /// directories, which is only possible if they are not beneath another
+    /// bind mount.
+    ///
+    /// Nodes beneath a remapped directory must already exist in its source,
+    /// since creating them would modify the source.
+    fn plan(mounts: &Mounts) -> Result<Self> {
+        let mut targets = Vec::new();
+        for (path, mount) in &mounts.mounts {
+            let node = match mount {
+                Mount::Remap { source, .. } => Some(TreeNode::new(&source.metadata()?)),
+                Mount::File { mode, .. } => Some(TreeNode { directory: false, mode: *mode }),
+                _ => None,
+            };
+            targets.push((path.as_path(), node));
+        }
+        let symlink_parents =
+            mounts.symlinks.iter().filter_map(|(symlink, _)| Some((symlink.parent()?, None)));
+
+        let mut nodes = HashMap::new();
+        let mut missing = Vec::new();
+        for (path, mut target) in targets.into_iter().chain(symlink_parents) {
+            let is_virtual = target.is_some();
+            for ancestor in path.ancestors() {
+                if ancestor.parent().is_none() || nodes.contains_key(ancestor) {
+                    continue;
+                }
+
+                let is_missing = is_virtual && !ancestor.exists();
+                if is_missing {
+                    missing.push(ancestor);
+                }
+
+                let node = match target.take().filter(|_| ancestor == path) {
+                    Some(node) => node,
+                    None if is_missing => TreeNode { directory: true, mode: 0o755 },
+                    None => TreeNode::new(&ancestor.metadata()?),
+                };
+                nodes.insert(ancestor.to_path_buf(), node);
+            }
+        }
+
+        // Creating missing nodes beneath a bind mount would modify the host.
+        for path in missing {
+            let is_bound = mounts.mounts.iter().any(|(mount_path, mount)| {
+                path != mount_path
+                    && path.starts_with(mount_path)
+                    && !matches!(mount, Mount::Deny | Mount::Tmpfs { .. })
+            });
+            if is_bound {
+                return Err(Error::InvalidPath(path.into()));
+            }
+        }
+
+        // Creating nodes beneath a remapped directory would modify its source.
+        for path in nodes.keys() {
+            let parent_mount = mounts
+                .mounts
+                .iter()
+                .filter(|(mount_path, _)| path != mount_path && path.starts_with(mount_path))
+                .max_by_key(|(mount_path, _)| mount_path.components().count());
+            if let Some((mount_path, Mount::Remap { source, .. })) = parent_mount {
+                let relative = path.strip_prefix(mount_path).unwrap();
+                if !source.join(relative).exists() {
+                    return Err(Error::InvalidPath(path.into()));
+                }
+            }
+        }
+
+        Ok(Self { nodes })
+    }
+
+    /// Get all nodes up to `path`, mapped to their location under the new
+    /// root.
+    fn nodes(&self, path: &Path) -> Result<Vec<PlannedNode>> {
+        let mut nodes = Vec::new();
+        for ancestor in path.ancestors().filter(|ancestor| ancestor.parent().is_some()) {
+            let node =
+                self.nodes.get(ancestor).ok_or_else(|| Error::InvalidPath(ancestor.into()))?;
+            nodes.push(PlannedNode {
+                path: path_cstring(&rooted(ancestor)),
+                directory: node.directory,
+                mode: node.mode,
+            });
+        }
+        nodes.reverse();
+
+        Ok(nodes)
+    }
+}
+
+/// File or directory in a [`Tree`].
+struct TreeNode {
+    directory: bool,
+    mode: u32,
+}
+
+impl TreeNode {
+    fn new(metadata: &Metadata) -> Self {
+        Self { directory: metadata.is_dir(), mode: metadata.permissions().mode() }
+    }
+}
+
+/// Mount a filesystem.
+fn mount(
+    src: &CStr,
+    dst: &CStr,
+    fstype: Option<&CStr>,
+    flags: MountFlags,
+    options: Option<&CStr>,
+) -> io::Result<()> {
     let res = unsafe {
-        libc::mount(ptr::null(), dst.as_ptr(), fstype.as_ptr(), flags.bits(), ptr::null())
+        libc::mount(
+            src.as_ptr(),
+            dst.as_ptr(),
+            fstype.map_or(ptr::null(), CStr::as_ptr),
+            flags.bits(),
+            options.map_or(ptr::null(), |options| options.as_ptr().cast()),
+        )
     };
 
     if res == 0 {
         Ok(())
     } else {
-        Err(IoError::last_os_error().into())
+        Err(IoError::last_os_error())
+    }
+}
+
+/// Mount a new tmpfs.
+///
+/// The `options` are passed to tmpfs as comma-separated mount data.
+fn mount_tmpfs<'a>(dst: &'a CStr, flags: MountFlags, options: &CStr) -> RawResult<'a, ()> {
+    let fstype = cstr(b"tmpfs\0");
+    mount(fstype, dst, Some(fstype), flags, Some(options)).map_err(|error| mount_error(dst, &error))
+}
+
+/// Mount an overlay at `dst`.
+///
+/// The upper and work directories are created if they are missing. If
+/// `staged` is `true`, they are placed on a tmpfs which is discarded with the
+/// mount namespace.
+fn mount_overlay<'a>(
+    plan: &'a MountPlan,
+    dst: &'a CStr,
+    options: &CStr,
+    directories: [&'a CString; 2],
+    staged: bool,
+) -> RawResult<'a, ()> {
+    // Stage a tmpfs for the changes, which is kept alive by the overlay.
+    if staged {
+        create_dir_all(&plan.staging_dirs)?;
+        mount_tmpfs(&plan.staging, MountFlags::empty(), cstr(b"\0"))?;
     }
+
+    for directory in directories {
+        create_dir_all(slice::from_ref(directory))?;
+    }
+
+    let fstype = cstr(b"overlay\0");
+    mount(fstype, dst, Some(fstype), MountFlags::NODEV, Some(options))
+        .map_err(|error| mount_error(dst, &error))?;
+
+    // Remove the staging mount, without affecting the overlay.
+    if staged {
+        umount(&plan.staging)?;
+    }
+
+    Ok(())
+}
+
+/// Mount a file with the specified `contents` and `mode` at `path`.
+///
+/// The file is written to a staging tmpfs, which is kept alive by the bind
+/// mount. Like [`bind_mount_in_root`], this returns the new mount.
+fn mount_file<'a>(
+    plan: &'a MountPlan,
+    path: &CStr,
+    dst: &'a CStr,
+    contents: &[u8],
+    mode: u32,
+) -> RawResult<'a, OwnedFd> {
+    create_dir_all(&plan.staging_dirs)?;
+    mount_tmpfs(&plan.staging, MountFlags::empty(), cstr(b"\0"))?;
+
+    sys::create_file(&plan.staging_file)
+        .and_then(|file| sys::write_all(&file, contents))
+        .and_then(|_| sys::chmod(&plan.staging_file, mode))
+        .map_err(|error| node_error(&plan.staging_file, &error))?;
+
+    let tree = bind_mount_in_root(plan, &plan.staging_file, path, dst)?;
+
+    // Remove the staging mount, without affecting the file.
+    umount(&plan.staging)?;
+
+    Ok(tree)
+}
+
+/// Mount a new procfs for the current PID namespace.
+fn mount_procfs<'a>(dst: &'a CStr, options: &CStr) -> RawResult<'a, ()> {
+    let flags = MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC;
+    let fstype = cstr(b"proc\0");
+    mount(fstype, dst, Some(fstype), flags, Some(options)).map_err(|error| mount_error(dst, &error))
+}
+
+/// Mount the POSIX message queue filesystem of the current IPC namespace.
+fn mount_mqueue(dst: &CStr) -> RawResult<'_, ()> {
+    let flags = MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC;
+    let fstype = cstr(b"mqueue\0");
+    mount(fstype, dst, Some(fstype), flags, None).map_err(|error| mount_error(dst, &error))
+}
+
+/// Mount a new devpts instance.
+fn mount_devpts(dst: &CStr) -> RawResult<'_, ()> {
+    let flags = MountFlags::NOSUID | MountFlags::NOEXEC;
+    let fstype = cstr(b"devpts\0");
+    mount(fstype, dst, Some(fstype), flags, Some(cstr(DEVPTS_OPTIONS)))
+        .map_err(|error| mount_error(dst, &error))
+}
+
+/// Create the error for a filesystem which could not be mounted at `dst`.
+fn mount_error<'a>(dst: &'a CStr, error: &IoError) -> RawError<'a> {
+    RawError::Mount { path: dst, errno: errno(error) }
 }
 
 /// Create a new bind mount.
-fn bind_mount(src: &CStr, dst: &CStr) -> Result<()> {
+fn bind_mount<'a>(src: &'a CStr, dst: &'a CStr) -> RawResult<'a, ()> {
     let flags = MountFlags::BIND | MountFlags::RECURSIVE;
-    let res =
-        unsafe { libc::mount(src.as_ptr(), dst.as_ptr(), ptr::null(), flags.bits(), ptr::null()) };
+    mount(src, dst, None, flags, None).map_err(|error| RawError::BindMount {
+        path: src,
+        target: dst,
+        errno: errno(&error),
+    })
+}
 
-    if res == 0 {
-        Ok(())
-    } else {
-        Err(IoError::last_os_error().into())
-    }
+/// Bind mount `src` at the sandbox `path`, without following symlinks out of
+/// the new root.
+///
+/// Symlinks leading to `path` are resolved relative to the new root instead of
+/// the host's root, and a symlink at `path` itself is covered by the mount
+/// rather than followed. The `dst` is only used for error reporting.
+///
+/// Returns the new mount, which allows updating its flags without resolving
+/// `path` again.
+fn bind_mount_in_root<'a>(
+    plan: &MountPlan,
+    src: &'a CStr,
+    path: &CStr,
+    dst: &'a CStr,
+) -> RawResult<'a, OwnedFd> {
+    let error =
+        |error: IoError| RawError::BindMount { path: src, target: dst, errno: errno(&error) };
+
+    let tree = sys::open_tree(src).map_err(error)?;
+    let root = sys::open(&plan.new_root, libc::O_PATH | libc::O_DIRECTORY, 0).map_err(error)?;
+    let target = sys::open_in_root(&root, path).map_err(error)?;
+    sys::move_mount(&tree, &target).map_err(error)?;
+
+    Ok(tree)
+}
+
+/// Remount an existing mount with a new set of mount flags.
+///
+/// If `recursive` is `true`, the flags are also applied to all submounts.
+fn update_mount_flags(mount: &CStr, flags: MountAttrFlags, recursive: bool) -> RawResult<'_, ()> {
+    mount_setattr(libc::AT_FDCWD, mount, 0, flags, recursive)
+        .map_err(|error| RawError::MountSetattr { path: mount, errno: errno(&error) })
+}
+
+/// Update the mount flags of a mount returned by [`bind_mount_in_root`].
+///
+/// The `dst` is only used for error reporting.
+fn update_tree_flags<'a>(
+    tree: &OwnedFd,
+    dst: &'a CStr,
+    flags: MountAttrFlags,
+    recursive: bool,
+) -> RawResult<'a, ()> {
+    mount_setattr(tree.as_raw_fd(), cstr(b"\0"), libc::AT_EMPTY_PATH, flags, recursive)
+        .map_err(|error| RawError::MountSetattr { path: dst, errno: errno(&error) })
 }
 
-/// Remount an existing bind mount with a new set of mount flags.
-fn update_mount_flags(mount: &CStr, flags: MountAttrFlags) -> Result<()> {
+/// Set the mount flags of the mount at `path` relative to `dirfd`.
+fn mount_setattr(
+    dirfd: c_int,
+    path: &CStr,
+    at_flags: c_int,
+    flags: MountAttrFlags,
+    recursive: bool,
+) -> io::Result<()> {
     let attrs = MountAttr { attr_set: flags.bits(), ..Default::default() };
+    let at_flags = if recursive { at_flags | libc::AT_RECURSIVE } else { at_flags };
 
     let res = unsafe {
         libc::syscall(
             libc::SYS_mount_setattr,
-            libc::AT_FDCWD,
-            mount.as_ptr(),
-            libc::AT_RECURSIVE,
+            dirfd,
+            path.as_ptr(),
+            at_flags,
             &attrs as *const _,
             mem::size_of::<MountAttr>(),
         )
@@ -258,59 +1289,69 @@ fn update_mount_flags(mount: &CStr, flags: MountAttrFlags) -> Result<()> {
     if res == 0 {
         Ok(())
     } else {
-        Err(IoError::last_os_error().into())
+        Err(IoError::last_os_error())
     }
 }
 
 /// Recursively update the root to deny mount propagation.
-fn deny_mount_propagation() -> Result<()> {
+fn deny_mount_propagation() -> RawResult<'static, ()> {
     let flags = MountFlags::PRIVATE | MountFlags::RECURSIVE;
-    let root = CString::new("/").unwrap();
-    let res =
-        unsafe { libc::mount(ptr::null(), root.as_ptr(), ptr::null(), flags.bits(), ptr::null()) };
+    let res = unsafe {
+        libc::mount(ptr::null(), cstr(b"/\0").as_ptr(), ptr::null(), flags.bits(), ptr::null())
+    };
 
     if res == 0 {
         Ok(())
     } else {
-        Err(IoError::last_os_error().into())
+        Err(RawError::last_os_error())
     }
 }
 
 /// Change root directory to `new_root` and mount the old root in `put_old`.
 ///
 /// The `put_old` directory must be at or undearneath `new_root`.
-fn pivot_root(new_root: &CStr, put_old: &CStr) -> Result<()> {
+fn pivot_root<'a>(new_root: &'a CStr, put_old: &CStr) -> RawResult<'a, ()> {
     // Get target working directory path.
-    let working_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+    let mut working_dir = [0; libc::PATH_MAX as usize];
+    let cwd = unsafe { libc::getcwd(working_dir.as_mut_ptr(), working_dir.len()) };
 
     let result =
         unsafe { libc::syscall(libc::SYS_pivot_root, new_root.as_ptr(), put_old.as_ptr()) };
 
     if result != 0 {
-        return Err(IoError::last_os_error().into());
+        let errno = errno(&IoError::last_os_error());
+        return Err(RawError::PivotRoot { path: new_root, errno });
     }
 
     // Attempt to recover working directory, or switch to root.
     //
     // Without this, the user's working directory would stay the same, giving him
     // full access to it even if it is not bound.
-    if env::set_current_dir(working_dir).is_err() {
-        env::set_current_dir("/")?;
+    if cwd.is_null() || unsafe { libc::chdir(working_dir.as_ptr()) } != 0 {
+        sys::chdir(cstr(b"/\0"))?;
     }
 
     Ok(())
 }
 
 /// Unmount a filesystem.
-fn umount(target: &CStr) -> Result<()> {
+fn umount(target: &CStr) -> RawResult<'_, ()> {
     let result = unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) };
 
     match result {
         0 => Ok(()),
-        _ => Err(IoError::last_os_error().into()),
+        _ => Err(RawError::Unmount { path: target, errno: errno(&IoError::last_os_error()) }),
     }
 }
 
+/// Get the user and group ID of the sandboxed processes.
+///
+/// The effective IDs outside of the namespaces are mapped to the same IDs
+/// inside of them.
+pub fn mapped_ids() -> (libc::uid_t, libc::gid_t) {
+    unsafe { (libc::geteuid(), libc::getegid()) }
+}
+
 /// Create a new user namespace.
 ///
 /// The parent and child UIDs and GIDs define the user and group mappings
@@ -319,37 +1360,74 @@ fn create_user_namespace(
     child_uid: u32,
     child_gid: u32,
     extra_namespaces: Namespaces,
-) -> Result<()> {
+) -> RawResult<'static, ()> {
     // Get current user's EUID and EGID.
     let parent_uid = unsafe { libc::geteuid() };
     let parent_gid = unsafe { libc::getegid() };
 
     // Create the namespace.
-    unshare(Namespaces::USER | extra_namespaces)?;
+    unshare(Namespaces::USER | extra_namespaces)
+        .map_err(|error| RawError::UserNamespace(errno(&error)))?;
 
     // Map the UID and GID.
-    let uid_map = format!("{child_uid} {parent_uid} 1\n");
-    let gid_map = format!("{child_gid} {parent_gid} 1\n");
-    fs::write("/proc/self/uid_map", uid_map.as_bytes())?;
-    fs::write("/proc/self/setgroups", b"deny")?;
-    fs::write("/proc/self/gid_map", gid_map.as_bytes())?;
+    let mut uid_map = FormatBuffer::<ID_MAP_SIZE>::new();
+    let mut gid_map = FormatBuffer::<ID_MAP_SIZE>::new();
+    let _ = writeln!(uid_map, "{child_uid} {parent_uid} 1");
+    let _ = writeln!(gid_map, "{child_gid} {parent_gid} 1");
+    write_id_map(cstr(UID_MAP), uid_map.as_bytes())?;
+    write_id_map(cstr(SETGROUPS), b"deny")?;
+    write_id_map(cstr(GID_MAP), gid_map.as_bytes())?;
 
     Ok(())
 }
 
+/// Write a user namespace's ID mapping file.
+fn write_id_map(path: &'static CStr, content: &[u8]) -> RawResult<'static, ()> {
+    sys::write_file(path, content).map_err(|error| RawError::IdMap { path, errno: errno(&error) })
+}
+
 /// Enter a namespace.
-fn unshare(namespaces: Namespaces) -> Result<()> {
+fn unshare(namespaces: Namespaces) -> io::Result<()> {
     let result = unsafe { libc::unshare(namespaces.bits()) };
     match result {
         0 => Ok(()),
-        _ => Err(IoError::last_os_error().into()),
+        _ => Err(IoError::last_os_error()),
     }
 }
 
+/// Get a path's location under the new root.
+fn rooted(path: &Path) -> PathBuf {
+    Path::new(NEW_ROOT).join(path.strip_prefix("/").unwrap())
+}
+
+/// Get all directories up to `path`, starting with the topmost one.
+fn dir_chain(path: &Path) -> Vec<CString> {
+    let mut chain: Vec<_> =
+        path.ancestors().filter(|ancestor| ancestor.parent().is_some()).map(path_cstring).collect();
+    chain.reverse();
+    chain
+}
+
+/// Convert a path to a C string.
+fn path_cstring(path: &Path) -> CString {
+    CString::new(path.as_os_str().as_bytes()).unwrap()
+}
+
+/// Convert a NUL-terminated string constant to a C string.
+fn cstr(bytes: &'static [u8]) -> &'static CStr {
+    CStr::from_bytes_with_nul(bytes).unwrap()
+}
+
 bitflags! {
     /// Mount syscall flags.
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-    pub struct MountFlags: libc::c_ulong {
+    pub struct MountFlags: c_ulong {
+        /// Ignore suid and sgid bits.
+        const NOSUID = libc::MS_NOSUID;
+        /// Disallow access to device special files.
+        const NODEV = libc::MS_NODEV;
+        /// Disallow program execution.
+        const NOEXEC = libc::MS_NOEXEC;
         /// Create a bind mount.
         const BIND = libc::MS_BIND;
         /// Used in conjuction with [`Self::BIND`] to create a recursive bind mount, and
@@ -402,13 +1480,16 @@ bitflags! {
         const IDMAP           = 0x00100000;
         /// Do not follow symlinks.
         const NOSYMFOLLOW     = 0x00200000;
+
+        /// Flags for mounts masking denied paths.
+        const MASK = Self::RDONLY.bits() | Self::NOSUID.bits() | Self::NODEV.bits() | Self::NOEXEC.bits();
     }
 }
 
 bitflags! {
     /// Unshare system call namespace flags.
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-    struct Namespaces: libc::c_int {
+    struct Namespaces: c_int {
         /// Unshare the file descriptor table, so that the calling process no longer
         /// shares its file descriptors with any other process.
         const FILES = libc::CLONE_FILES;
@@ -507,7 +1588,7 @@ fn absolute(path: &Path) -> io::Result<PathBuf> {
 }
 
 /// Normalize path components, stripping out `.` and `..`.
-fn normalize_path(path: &Path) -> PathBuf {
+pub fn normalize_path(path: &Path) -> PathBuf {
     let mut normalized = PathBuf::new();
 
     for component in path.components() {
@@ -0,0 +1,125 @@
+//! Network interface configuration using rtnetlink.
+
+use std::io::Error as IoError;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::{mem, ptr};
+
+use crate::error::{errno, RawError, RawResult};
+
+/// Name of the loopback interface.
+const LOOPBACK: &[u8] = b"lo\0";
+
+/// Netlink message requesting a change to a network interface.
+#[repr(C)]
+struct LinkRequest {
+    header: NetlinkHeader,
+    info: InterfaceInfo,
+}
+
+/// Netlink message header.
+#[repr(C)]
+#[derive(Default)]
+struct NetlinkHeader {
+    len: u32,
+    kind: u16,
+    flags: u16,
+    seq: u32,
+    pid: u32,
+}
+
+/// Link layer interface information.
+#[repr(C)]
+#[derive(Default)]
+struct InterfaceInfo {
+    family: u8,
+    _pad: u8,
+    kind: u16,
+    index: i32,
+    flags: u32,
+    change: u32,
+}
+
+/// Netlink error message.
+#[repr(C)]
+struct NetlinkError {
+    header: NetlinkHeader,
+    error: i32,
+}
+
+/// Bring up the loopback interface of the current network namespace.
+///
+/// This requires `CAP_NET_ADMIN` in the network namespace's user namespace.
+pub(crate) fn set_loopback_up() -> RawResult<'static, ()> {
+    let index = unsafe { libc::if_nametoindex(LOOPBACK.as_ptr().cast()) };
+    if index == 0 {
+        return Err(loopback_error());
+    }
+
+    let fd = unsafe {
+        libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE)
+    };
+    if fd < 0 {
+        return Err(loopback_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // Request the interface's UP flag to be set.
+    let request = LinkRequest {
+        header: NetlinkHeader {
+            len: mem::size_of::<LinkRequest>() as u32,
+            kind: libc::RTM_NEWLINK,
+            flags: (libc::NLM_F_REQUEST | libc::NLM_F_ACK) as u16,
+            seq: 1,
+            pid: 0,
+        },
+        info: InterfaceInfo {
+            family: libc::AF_UNSPEC as u8,
+            index: index as i32,
+            flags: libc::IFF_UP as u32,
+            change: libc::IFF_UP as u32,
+            ..Default::default()
+        },
+    };
+
+    let sent = unsafe {
+        libc::send(
+            socket.as_raw_fd(),
+            ptr::addr_of!(request).cast(),
+            mem::size_of::<LinkRequest>(),
+            0,
+        )
+    };
+    if sent < 0 {
+        return Err(loopback_error());
+    }
+
+    // Wait for the acknowledgement.
+    let mut response = mem::MaybeUninit::<NetlinkError>::zeroed();
+    let received = unsafe {
+        libc::recv(
+            socket.as_raw_fd(),
+            response.as_mut_ptr().cast(),
+            mem::size_of::<NetlinkError>(),
+            0,
+        )
+    };
+    if received < 0 {
+        return Err(loopback_error());
+    } else if (received as usize) < mem::size_of::<NetlinkError>() {
+        return Err(RawError::ActivationFailed("invalid netlink response"));
+    }
+
+    let response = unsafe { response.assume_init() };
+    if response.header.kind != libc::NLMSG_ERROR as u16 {
+        return Err(RawError::ActivationFailed("unexpected netlink response"));
+    } else if response.error != 0 {
+        return Err(RawError::Loopback(-response.error));
+    }
+
+    Ok(())
+}
+
+/// Create a loopback error from the last OS error.
+fn loopback_error() -> RawError<'static> {
+    RawError::Loopback(errno(&IoError::last_os_error()))
+}
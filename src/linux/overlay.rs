@@ -0,0 +1,285 @@
+//! Review of changes made through overlay exceptions.
+//!
+//! Writes to an [`Exception::Overlay`] path are stored in a separate
+//! directory. Once the sandbox exited, a [`ChangeSet`] lists these changes and
+//! applies them to the original path, or discards them:
+//!
+//! ```no_run
+//! use birdcage::overlay::ChangeSet;
+//!
+//! let changes = ChangeSet::new("/home/user/project", "/tmp/project-changes").unwrap();
+//! for change in changes.changes() {
+//!     println!("{:?}: {:?}", change.kind, change.path);
+//! }
+//! changes.commit().unwrap();
+//! ```
+//!
+//! [`Exception::Overlay`]: crate::Exception::Overlay
+
+use std::ffi::CString;
+use std::fs::{self, Metadata, Permissions};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{self as unixfs, FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+/// Directory storing the files changed through the overlay.
+pub(crate) const UPPER_DIR: &str = "upper";
+
+/// Directory used by the kernel to prepare changes.
+pub(crate) const WORK_DIR: &str = "work";
+
+/// Extended attribute marking directories hiding their original content.
+const OPAQUE_XATTR: &str = "user.overlay.opaque";
+
+/// Kind of change made to a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// The path did not exist before.
+    Added,
+    /// The path was replaced or its content was modified.
+    Modified,
+    /// The path was removed.
+    Deleted,
+}
+
+/// Change made to a path through an overlay.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Change {
+    /// Changed path, beneath the overlay's path.
+    pub path: PathBuf,
+    /// Kind of change.
+    pub kind: ChangeKind,
+}
+
+/// Changes made through an [`Exception::Overlay`].
+///
+/// [`Exception::Overlay`]: crate::Exception::Overlay
+#[derive(Debug)]
+pub struct ChangeSet {
+    lower: PathBuf,
+    changes_dir: PathBuf,
+    changes: Vec<Change>,
+}
+
+impl ChangeSet {
+    /// Collect all changes made to `path` through an overlay which stored its
+    /// changes in `changes_dir`.
+    ///
+    /// This must only be called after all sandboxed processes using the
+    /// overlay have exited.
+    pub fn new(path: impl AsRef<Path>, changes_dir: impl Into<PathBuf>) -> Result<Self> {
+        let lower = path.as_ref().canonicalize()?;
+        let changes_dir = changes_dir.into();
+
+        let mut changes = Vec::new();
+        let upper = changes_dir.join(UPPER_DIR);
+        if upper.exists() {
+            collect_changes(&upper, &lower, &mut changes)?;
+        }
+
+        Ok(Self { lower, changes_dir, changes })
+    }
+
+    /// Get all changed paths.
+    ///
+    /// Paths inside added or deleted directories are listed individually.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Apply all changes to the original path.
+    ///
+    /// The changes directory is cleared afterwards, so the same directory can
+    /// be used for the next sandbox.
+    pub fn commit(self) -> Result<()> {
+        let upper = self.changes_dir.join(UPPER_DIR);
+        if upper.exists() {
+            apply_changes(&upper, &self.lower)?;
+        }
+
+        self.discard()
+    }
+
+    /// Discard all changes, leaving the original path untouched.
+    pub fn discard(self) -> Result<()> {
+        for dir in [UPPER_DIR, WORK_DIR] {
+            let path = self.changes_dir.join(dir);
+            if path.exists() {
+                remove_all(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Recursively collect the changes in the `upper` directory of `lower`.
+fn collect_changes(upper: &Path, lower: &Path, changes: &mut Vec<Change>) -> Result<()> {
+    // Opaque directories hide all original entries which were not re-created.
+    if is_opaque(upper) {
+        for entry in fs::read_dir(lower)? {
+            let name = entry?.file_name();
+            if fs::symlink_metadata(upper.join(&name)).is_err() {
+                collect_tree(&lower.join(&name), ChangeKind::Deleted, changes)?;
+            }
+        }
+    }
+
+    for entry in fs::read_dir(upper)? {
+        let entry = entry?;
+        let upper_path = entry.path();
+        let lower_path = lower.join(entry.file_name());
+        let metadata = entry.metadata()?;
+        let lower_metadata = fs::symlink_metadata(&lower_path).ok();
+
+        match lower_metadata {
+            // Whiteouts mark removed files.
+            Some(_) if is_whiteout(&metadata) => {
+                collect_tree(&lower_path, ChangeKind::Deleted, changes)?;
+            },
+            None if is_whiteout(&metadata) => (),
+            // Directories present in both layers might contain changes.
+            Some(lower_metadata) if metadata.is_dir() && lower_metadata.is_dir() => {
+                collect_changes(&upper_path, &lower_path, changes)?;
+            },
+            Some(_) => {
+                changes.push(Change { path: lower_path.clone(), kind: ChangeKind::Modified });
+                if metadata.is_dir() {
+                    collect_added(&upper_path, &lower_path, changes)?;
+                }
+            },
+            None => {
+                changes.push(Change { path: lower_path.clone(), kind: ChangeKind::Added });
+                if metadata.is_dir() {
+                    collect_added(&upper_path, &lower_path, changes)?;
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Record all entries in the `upper` directory as added beneath `lower`.
+fn collect_added(upper: &Path, lower: &Path, changes: &mut Vec<Change>) -> Result<()> {
+    for entry in fs::read_dir(upper)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if is_whiteout(&metadata) {
+            continue;
+        }
+
+        let lower_path = lower.join(entry.file_name());
+        changes.push(Change { path: lower_path.clone(), kind: ChangeKind::Added });
+        if metadata.is_dir() {
+            collect_added(&entry.path(), &lower_path, changes)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Record a path and everything beneath it with the same kind of change.
+fn collect_tree(path: &Path, kind: ChangeKind, changes: &mut Vec<Change>) -> Result<()> {
+    changes.push(Change { path: path.to_path_buf(), kind });
+
+    if fs::symlink_metadata(path)?.is_dir() {
+        for entry in fs::read_dir(path)? {
+            collect_tree(&entry?.path(), kind, changes)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Recursively apply the changes in the `upper` directory to `lower`.
+fn apply_changes(upper: &Path, lower: &Path) -> Result<()> {
+    // Remove original entries hidden by opaque directories.
+    if is_opaque(upper) {
+        for entry in fs::read_dir(lower)? {
+            let name = entry?.file_name();
+            if fs::symlink_metadata(upper.join(&name)).is_err() {
+                remove_all(&lower.join(name))?;
+            }
+        }
+    }
+
+    for entry in fs::read_dir(upper)? {
+        let entry = entry?;
+        let upper_path = entry.path();
+        let lower_path = lower.join(entry.file_name());
+        let metadata = entry.metadata()?;
+        let lower_metadata = fs::symlink_metadata(&lower_path).ok();
+
+        // Remove entries which were deleted or replaced with a different type.
+        //
+        // Symlinks are always replaced, to avoid writing through them.
+        let replaced = lower_metadata.as_ref().map_or(false, |lower_metadata| {
+            is_whiteout(&metadata)
+                || lower_metadata.is_dir() != metadata.is_dir()
+                || lower_metadata.file_type().is_symlink()
+                || metadata.file_type().is_symlink()
+        });
+        if replaced {
+            remove_all(&lower_path)?;
+        }
+
+        if is_whiteout(&metadata) {
+            continue;
+        } else if metadata.is_dir() {
+            if !lower_path.exists() {
+                fs::create_dir(&lower_path)?;
+            }
+            apply_changes(&upper_path, &lower_path)?;
+            fs::set_permissions(&lower_path, metadata.permissions())?;
+        } else if metadata.file_type().is_symlink() {
+            unixfs::symlink(fs::read_link(&upper_path)?, &lower_path)?;
+        } else {
+            fs::copy(&upper_path, &lower_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove a path and everything beneath it.
+///
+/// Unlike [`fs::remove_dir_all`], this also removes directories without write
+/// permissions, which are created by the kernel in the work directory.
+fn remove_all(path: &Path) -> Result<()> {
+    if !fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_file(path)?;
+        return Ok(());
+    }
+
+    fs::set_permissions(path, Permissions::from_mode(0o700))?;
+    for entry in fs::read_dir(path)? {
+        remove_all(&entry?.path())?;
+    }
+    fs::remove_dir(path)?;
+
+    Ok(())
+}
+
+/// Check if an upper directory entry marks a removed file.
+fn is_whiteout(metadata: &Metadata) -> bool {
+    metadata.file_type().is_char_device() && metadata.rdev() == 0
+}
+
+/// Check if an upper directory hides the content of its lower directory.
+fn is_opaque(path: &Path) -> bool {
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let name = CString::new(OPAQUE_XATTR).unwrap();
+    let mut value = [0u8; 1];
+    let len = unsafe {
+        libc::lgetxattr(path.as_ptr(), name.as_ptr(), value.as_mut_ptr().cast(), value.len())
+    };
+    len == 1 && value[0] == b'y'
+}
+
+/// Check if a path can be passed to the kernel as overlay directory.
+pub(crate) fn is_valid_path(path: &Path) -> bool {
+    !path.as_os_str().as_bytes().iter().any(|byte| matches!(byte, b',' | b':' | b'\\'))
+}
@@ -0,0 +1,231 @@
+//! Kernel capability probing.
+
+use std::io::Error as IoError;
+use std::os::raw::{c_char, c_int};
+use std::{fs, mem};
+
+use bitflags::bitflags;
+
+use crate::linux::landlock;
+
+/// Operation for the `seccomp` syscall to check if an action is supported.
+const SECCOMP_GET_ACTION_AVAIL: u32 = 2;
+
+/// Seccomp return value for errors.
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+
+/// Seccomp return value for user notifications.
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+
+/// Attribute for `mount_setattr` to make a mount read-only.
+const MOUNT_ATTR_RDONLY: u64 = 0x1;
+
+/// Flag for `unshare` to create a time namespace.
+const CLONE_NEWTIME: c_int = 0x80;
+
+/// AppArmor sysctl restricting unprivileged user namespaces.
+const APPARMOR_USERNS_SYSCTL: &str = "/proc/sys/kernel/apparmor_restrict_unprivileged_userns";
+
+/// Isolation layers supported by the current host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Unprivileged user namespaces can be created.
+    pub user_namespace: bool,
+    /// Mount namespaces can be created inside a user namespace.
+    pub mount_namespace: bool,
+    /// Network namespaces can be created inside a user namespace.
+    pub network_namespace: bool,
+    /// PID namespaces can be created inside a user namespace.
+    pub pid_namespace: bool,
+    /// IPC namespaces can be created inside a user namespace.
+    pub ipc_namespace: bool,
+    /// UTS namespaces can be created inside a user namespace.
+    pub uts_namespace: bool,
+    /// Time namespaces can be created inside a user namespace (Linux 5.6+).
+    pub time_namespace: bool,
+    /// The `mount_setattr` syscall is available (Linux 5.12+).
+    pub mount_setattr: bool,
+    /// Supported Landlock ABI version.
+    pub landlock_abi: Option<u32>,
+    /// Seccomp mode of the current process.
+    pub seccomp_mode: SeccompMode,
+    /// Seccomp filters can be installed.
+    pub seccomp_filter: bool,
+    /// Seccomp user notifications are supported, which is required for
+    /// audit mode.
+    pub seccomp_user_notification: bool,
+    /// AppArmor restricts unprivileged user namespaces.
+    pub apparmor_userns_restricted: bool,
+}
+
+impl Capabilities {
+    /// Detect the capabilities of the current host.
+    ///
+    /// Namespace support is tested in a short-lived child process, so the
+    /// current process is not modified.
+    pub(crate) fn detect() -> Self {
+        let namespaces = probe_namespaces();
+        let seccomp_mode = SeccompMode::current();
+
+        Self {
+            user_namespace: namespaces.contains(ProbedNamespaces::USER),
+            mount_namespace: namespaces.contains(ProbedNamespaces::MOUNT),
+            network_namespace: namespaces.contains(ProbedNamespaces::NETWORK),
+            pid_namespace: namespaces.contains(ProbedNamespaces::PID),
+            ipc_namespace: namespaces.contains(ProbedNamespaces::IPC),
+            uts_namespace: namespaces.contains(ProbedNamespaces::UTS),
+            time_namespace: namespaces.contains(ProbedNamespaces::TIME),
+            mount_setattr: mount_setattr_available(),
+            landlock_abi: landlock::abi_version(),
+            seccomp_mode,
+            seccomp_filter: seccomp_mode != SeccompMode::Strict
+                && seccomp_action_available(SECCOMP_RET_ERRNO),
+            seccomp_user_notification: seccomp_action_available(SECCOMP_RET_USER_NOTIF),
+            apparmor_userns_restricted: fs::read_to_string(APPARMOR_USERNS_SYSCTL)
+                .map_or(false, |value| value.trim() == "1"),
+        }
+    }
+
+    /// Check if filesystem access can be isolated.
+    pub fn isolates_filesystem(&self) -> bool {
+        (self.mount_namespace && self.mount_setattr) || self.landlock_abi.is_some()
+    }
+
+    /// Check if network access can be isolated.
+    ///
+    /// Without network namespaces, Landlock can only isolate TCP traffic and
+    /// all other internet sockets are blocked by seccomp.
+    pub fn isolates_network(&self) -> bool {
+        self.network_namespace || self.landlock_abi.map_or(false, |abi| abi >= 4)
+    }
+}
+
+/// Seccomp mode of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeccompMode {
+    /// No seccomp restrictions are applied.
+    Disabled,
+    /// Strict mode, allowing only a minimal set of syscalls.
+    Strict,
+    /// One or more seccomp filters are applied.
+    Filter,
+    /// The kernel does not support seccomp.
+    Unsupported,
+}
+
+impl SeccompMode {
+    /// Get the seccomp mode of the current process.
+    fn current() -> Self {
+        match unsafe { libc::prctl(libc::PR_GET_SECCOMP) } {
+            0 => Self::Disabled,
+            1 => Self::Strict,
+            2 => Self::Filter,
+            _ => Self::Unsupported,
+        }
+    }
+}
+
+bitflags! {
+    /// Namespaces supported by the kernel.
+    ///
+    /// These are reported as the exit code of the probing child.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct ProbedNamespaces: u8 {
+        const USER    = 1 << 0;
+        const MOUNT   = 1 << 1;
+        const NETWORK = 1 << 2;
+        const PID     = 1 << 3;
+        const IPC     = 1 << 4;
+        const UTS     = 1 << 5;
+        const TIME    = 1 << 6;
+    }
+}
+
+/// Check which namespaces can be created by the current user.
+fn probe_namespaces() -> ProbedNamespaces {
+    let pid = unsafe { libc::fork() };
+
+    // Create namespaces in the child, only using async-signal-safe functions.
+    if pid == 0 {
+        let mut supported = ProbedNamespaces::empty();
+        if unsafe { libc::unshare(libc::CLONE_NEWUSER) } == 0 {
+            supported |= ProbedNamespaces::USER;
+
+            for (flag, namespace) in [
+                (libc::CLONE_NEWNS, ProbedNamespaces::MOUNT),
+                (libc::CLONE_NEWNET, ProbedNamespaces::NETWORK),
+                (libc::CLONE_NEWPID, ProbedNamespaces::PID),
+                (libc::CLONE_NEWIPC, ProbedNamespaces::IPC),
+                (libc::CLONE_NEWUTS, ProbedNamespaces::UTS),
+                (CLONE_NEWTIME, ProbedNamespaces::TIME),
+            ] {
+                if unsafe { libc::unshare(flag) } == 0 {
+                    supported |= namespace;
+                }
+            }
+        }
+        unsafe { libc::_exit(supported.bits() as c_int) };
+    } else if pid < 0 {
+        return ProbedNamespaces::empty();
+    }
+
+    let mut status = 0;
+    loop {
+        if unsafe { libc::waitpid(pid, &mut status, 0) } == pid {
+            break;
+        } else if IoError::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            return ProbedNamespaces::empty();
+        }
+    }
+
+    if libc::WIFEXITED(status) {
+        ProbedNamespaces::from_bits_truncate(libc::WEXITSTATUS(status) as u8)
+    } else {
+        ProbedNamespaces::empty()
+    }
+}
+
+/// Check if the `mount_setattr` syscall is available.
+fn mount_setattr_available() -> bool {
+    mount_setattr_errno() == Some(libc::EBADF)
+}
+
+/// Check if the kernel lacks the `mount_setattr` syscall.
+///
+/// Unlike [`mount_setattr_available`], this ignores missing privileges over
+/// the current mount namespace. It does not allocate, so it is safe to call
+/// in a forked child.
+pub(crate) fn mount_setattr_missing() -> bool {
+    mount_setattr_errno() == Some(libc::ENOSYS)
+}
+
+/// Get the error returned by an invalid `mount_setattr` call.
+fn mount_setattr_errno() -> Option<i32> {
+    // Make a read-only request for an invalid file descriptor, which fails
+    // with `EBADF` once the syscall has validated its arguments.
+    let attr: [u64; 4] = [MOUNT_ATTR_RDONLY, 0, 0, 0];
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            -1,
+            b"\0".as_ptr().cast::<c_char>(),
+            libc::AT_EMPTY_PATH,
+            attr.as_ptr(),
+            mem::size_of_val(&attr),
+        )
+    };
+
+    if result == -1 {
+        IoError::last_os_error().raw_os_error()
+    } else {
+        None
+    }
+}
+
+/// Check if a seccomp filter return action is supported.
+fn seccomp_action_available(action: u32) -> bool {
+    let result = unsafe {
+        libc::syscall(libc::SYS_seccomp, SECCOMP_GET_ACTION_AVAIL, 0, &action as *const u32)
+    };
+    result == 0
+}
@@ -0,0 +1,296 @@
+//! Network egress proxy.
+//!
+//! The proxy runs in the parent process and accepts HTTP CONNECT requests on a
+//! Unix socket, forwarding connections only to explicitly allowed
+//! destinations.
+
+use std::fs::{self, DirBuilder};
+use std::io::{self, Error as IoError, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{env, mem, process, thread};
+
+use crate::error::Result;
+
+/// Environment variable exposing the proxy socket to the sandboxee.
+pub const SOCKET_ENV: &str = "BIRDCAGE_PROXY_SOCKET";
+
+/// File name of the proxy's Unix socket.
+const SOCKET_NAME: &str = "proxy.sock";
+
+/// Maximum size of a CONNECT request's header.
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// Maximum number of connections handled concurrently.
+const MAX_CONNECTIONS: usize = 64;
+
+/// Interval for checking if the sandboxee exited, if it cannot be monitored
+/// with a pidfd.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Counter for unique proxy directory names.
+static PROXY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// HTTP CONNECT proxy for allowed network destinations.
+pub struct EgressProxy {
+    listener: UnixListener,
+    hosts: Arc<Vec<(String, u16)>>,
+    dir: PathBuf,
+}
+
+impl EgressProxy {
+    /// Create a proxy listening on a new Unix socket.
+    pub fn new(hosts: Vec<(String, u16)>) -> Result<Self> {
+        let dir = create_private_dir()?;
+        let listener = match UnixListener::bind(dir.join(SOCKET_NAME)) {
+            Ok(listener) => listener,
+            Err(err) => {
+                let _ = fs::remove_dir_all(&dir);
+                return Err(err.into());
+            },
+        };
+
+        Ok(Self { listener, hosts: Arc::new(hosts), dir })
+    }
+
+    /// Path of the proxy's Unix socket.
+    pub fn socket_path(&self) -> PathBuf {
+        self.dir.join(SOCKET_NAME)
+    }
+
+    /// Serve connections until the child process `pid` has exited.
+    ///
+    /// Without pidfd support, the process is periodically checked for its
+    /// exit instead.
+    ///
+    /// At most [`MAX_CONNECTIONS`] are handled at once, further connections
+    /// are rejected.
+    pub fn serve(self, pid: u32) {
+        let pidfd = pidfd_open(pid).ok();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        thread::spawn(move || {
+            loop {
+                // Wait for the process to exit or a new connection.
+                let pidfd = pidfd.as_ref().map(|pidfd| pidfd.as_raw_fd());
+                match wait_readable(self.listener.as_raw_fd(), pidfd, pid) {
+                    Ok(true) => (),
+                    Ok(false) | Err(_) => break,
+                }
+
+                let mut stream = match self.listener.accept() {
+                    Ok((stream, _)) => stream,
+                    Err(_) => continue,
+                };
+
+                // Reject connections exceeding the limit.
+                let guard = match ConnectionGuard::new(&connections) {
+                    Some(guard) => guard,
+                    None => {
+                        let _ = stream.write_all(b"HTTP/1.1 503 Service Unavailable\r\n\r\n");
+                        continue;
+                    },
+                };
+
+                let hosts = self.hosts.clone();
+                thread::spawn(move || {
+                    let _guard = guard;
+                    handle_connection(stream, &hosts)
+                });
+            }
+        });
+    }
+}
+
+impl Drop for EgressProxy {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Slot of a connection counting towards [`MAX_CONNECTIONS`].
+struct ConnectionGuard {
+    connections: Arc<AtomicUsize>,
+}
+
+impl ConnectionGuard {
+    /// Reserve a connection slot, if the limit was not reached yet.
+    fn new(connections: &Arc<AtomicUsize>) -> Option<Self> {
+        connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < MAX_CONNECTIONS).then_some(count + 1)
+            })
+            .ok()?;
+
+        Some(Self { connections: connections.clone() })
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Handle a single CONNECT request.
+fn handle_connection(mut client: UnixStream, hosts: &[(String, u16)]) -> io::Result<()> {
+    // Read the request header.
+    let mut buffer = Vec::new();
+    let header_len = loop {
+        let mut chunk = [0; 1024];
+        let read = client.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        if let Some(index) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break index + 4;
+        } else if buffer.len() > MAX_HEADER_SIZE {
+            return client.write_all(b"HTTP/1.1 431 Request Header Fields Too Large\r\n\r\n");
+        }
+    };
+
+    let (host, port) = match parse_connect(&buffer[..header_len]) {
+        Some(destination) => destination,
+        None => return client.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n"),
+    };
+
+    // Reject destinations which were not explicitly allowed.
+    let allowed = hosts.iter().any(|(allowed_host, allowed_port)| {
+        allowed_host.eq_ignore_ascii_case(&host) && *allowed_port == port
+    });
+    if !allowed {
+        return client.write_all(b"HTTP/1.1 403 Forbidden\r\n\r\n");
+    }
+
+    let mut upstream = match TcpStream::connect((host.as_str(), port)) {
+        Ok(upstream) => upstream,
+        Err(_) => return client.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n"),
+    };
+    client.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")?;
+
+    // Forward data sent along with the header.
+    upstream.write_all(&buffer[header_len..])?;
+
+    // Relay data in both directions.
+    let mut client_read = client.try_clone()?;
+    let mut upstream_write = upstream.try_clone()?;
+    let upload = thread::spawn(move || {
+        let _ = io::copy(&mut client_read, &mut upstream_write);
+        let _ = upstream_write.shutdown(Shutdown::Write);
+    });
+    let _ = io::copy(&mut upstream, &mut client);
+    let _ = client.shutdown(Shutdown::Write);
+    let _ = upload.join();
+
+    Ok(())
+}
+
+/// Parse the destination of an HTTP CONNECT request header.
+fn parse_connect(header: &[u8]) -> Option<(String, u16)> {
+    let header = std::str::from_utf8(header).ok()?;
+    let request_line = header.lines().next()?;
+
+    let mut parts = request_line.split(' ');
+    let method = parts.next()?;
+    let authority = parts.next()?;
+    let version = parts.next()?;
+    if method != "CONNECT" || !version.starts_with("HTTP/1.") {
+        return None;
+    }
+
+    let (host, port) = authority.rsplit_once(':')?;
+    let host = host.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(host);
+    let port = port.parse().ok()?;
+
+    Some((host.into(), port))
+}
+
+/// Create a new directory which is only accessible by the current user.
+fn create_private_dir() -> Result<PathBuf> {
+    let temp_dir = env::temp_dir();
+
+    loop {
+        let count = PROXY_COUNT.fetch_add(1, Ordering::Relaxed);
+        let dir = temp_dir.join(format!("birdcage-proxy-{}-{count}", process::id()));
+
+        match DirBuilder::new().mode(0o700).create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// Get a file descriptor referring to a process.
+fn pidfd_open(pid: u32) -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+
+    if fd < 0 {
+        Err(IoError::last_os_error())
+    } else {
+        Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+    }
+}
+
+/// Wait for a new connection on `listener`, or the exit of the process `pid`.
+///
+/// The process is monitored through its `pidfd`, or polled if there is none.
+///
+/// Returns `true` if the listener has a new connection.
+fn wait_readable(listener: i32, pidfd: Option<i32>, pid: u32) -> io::Result<bool> {
+    let mut fds = [libc::pollfd { fd: listener, events: libc::POLLIN, revents: 0 }, libc::pollfd {
+        fd: pidfd.unwrap_or(-1),
+        events: libc::POLLIN,
+        revents: 0,
+    }];
+    let timeout = match pidfd {
+        Some(_) => -1,
+        None => EXIT_POLL_INTERVAL.as_millis() as i32,
+    };
+
+    loop {
+        let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, timeout) };
+        if result > 0 {
+            return Ok(fds[1].revents == 0 && fds[0].revents != 0);
+        } else if result == 0 {
+            if has_exited(pid)? {
+                return Ok(false);
+            }
+            continue;
+        }
+
+        let error = IoError::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Check if the child process `pid` has exited.
+///
+/// The process is not reaped, so it can still be waited for by its owner.
+fn has_exited(pid: u32) -> io::Result<bool> {
+    let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+    let result = unsafe {
+        libc::waitid(libc::P_PID, pid, &mut info, libc::WEXITED | libc::WNOHANG | libc::WNOWAIT)
+    };
+
+    if result == 0 {
+        Ok(unsafe { info.si_pid() } != 0)
+    } else {
+        let error = IoError::last_os_error();
+        match error.raw_os_error() {
+            // The process was already reaped.
+            Some(libc::ECHILD) => Ok(true),
+            _ => Err(error),
+        }
+    }
+}
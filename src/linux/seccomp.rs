@@ -1,13 +1,17 @@
 //! Seccomp system call filtering.
 
 use std::collections::BTreeMap;
+use std::io::Error as IoError;
+use std::os::raw::c_long;
+use std::os::unix::io::{FromRawFd, OwnedFd};
+use std::result::Result as StdResult;
 
 use seccompiler::{
-    BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
-    SeccompRule, TargetArch,
+    BpfProgram, Error as SeccompError, SeccompAction, SeccompCmpArgLen, SeccompCmpOp,
+    SeccompCondition, SeccompFilter, SeccompRule, TargetArch,
 };
 
-use crate::Result;
+use crate::error::{errno, RawError, RawResult, Result};
 
 #[cfg(target_arch = "x86_64")]
 const ARCH: TargetArch = TargetArch::x86_64;
@@ -28,65 +32,332 @@ const ARCH: TargetArch = TargetArch::aarch64;
 ///  - CLONE_NEWNET    = 0x40000000
 const CLONE_NAMESPACE_FILTER: u32 = 0b01111110000000100000000000000000;
 
+/// Marker for syscalls which should be reported to the audit supervisor.
+///
+/// Seccompiler does not support user notifications, so `SECCOMP_RET_TRACE`
+/// with this marker is replaced by `SECCOMP_RET_USER_NOTIF` after compilation.
+const AUDIT_MARKER: u32 = 0xb1d;
+
+/// Seccomp return value suspending the syscall until the supervisor responds.
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+
+/// Seccomp return value for tracer notification.
+const SECCOMP_RET_TRACE: u32 = 0x7ff0_0000;
+
+/// Operation for the `seccomp` syscall to install a new filter.
+const SECCOMP_SET_MODE_FILTER: u32 = 1;
+
+/// Flag for the `seccomp` syscall to return a user notification listener.
+const SECCOMP_FILTER_FLAG_NEW_LISTENER: u64 = 1 << 3;
+
+/// BPF return instruction with a constant value.
+const BPF_RET_K: u16 = 0x06;
+
+/// Bitmask for the socket type, without the `SOCK_NONBLOCK` and
+/// `SOCK_CLOEXEC` flags.
+const SOCK_TYPE_MASK: u64 = 0xf;
+
+/// Terminal `ioctl` requests denied inside the sandbox.
+///
+/// These allow injecting input into a terminal shared with processes outside
+/// of the sandbox, like the one accessible through `/dev/tty`.
+///
+/// Denied requests:
+///  - TIOCSTI   = 0x5412
+///  - TIOCLINUX = 0x541c
+const DENIED_IOCTLS: &[u64] = &[0x5412, 0x541c];
+
 /// Seccomp system call filter.
 ///
 /// This filter is aimed at restricting system calls which shouldn't be
 /// executable by an untrusted client.
-#[derive(Default)]
-pub struct SyscallFilter;
+pub struct SyscallFilter {
+    whitelist: BpfProgram,
+    clone3: BpfProgram,
+    ioctls: BpfProgram,
+    sockets: BpfProgram,
+}
 
 impl SyscallFilter {
-    /// Apply the seccomp filter.
-    pub fn apply() -> Result<()> {
-        let mut rules = BTreeMap::new();
+    /// Compile the seccomp filter.
+    ///
+    /// This does not apply any restrictions, allowing the filter to be built
+    /// before the sandbox is activated.
+    pub fn new() -> Result<Self> {
+        let rules = SYSCALL_WHITELIST.iter().map(|syscall| (*syscall, Vec::new())).collect();
+        let whitelist = compile_whitelist(rules, SeccompAction::Errno(libc::EACCES as u32))?;
 
-        // Add exceptions for allowed syscalls.
-        for syscall in SYSCALL_WHITELIST {
-            rules.insert(*syscall, Vec::new());
-        }
+        Ok(Self {
+            whitelist,
+            clone3: compile_clone3()?,
+            ioctls: compile_ioctls()?,
+            sockets: compile_sockets()?,
+        })
+    }
 
-        // Add exception for the `clone` syscall.
-        let allow_clone = SeccompCondition::new(
-            0,
-            SeccompCmpArgLen::Qword,
-            SeccompCmpOp::MaskedEq(CLONE_NAMESPACE_FILTER as u64),
+    /// Compile a seccomp filter reporting syscalls instead of denying them.
+    ///
+    /// All syscalls which are not part of the whitelist, together with the
+    /// whitelisted [`AUDITED_SYSCALLS`] and executable memory mappings, are
+    /// suspended until a supervisor has inspected them.
+    pub fn audit() -> Result<Self> {
+        let mut rules: BTreeMap<_, _> = SYSCALL_WHITELIST
+            .iter()
+            .filter(|syscall| !AUDITED_SYSCALLS.contains(syscall))
+            .map(|syscall| (*syscall, Vec::new()))
+            .collect();
+
+        // Add exception for non-executable memory mappings.
+        let allow_mmap = SeccompCondition::new(
+            2,
+            SeccompCmpArgLen::Dword,
+            SeccompCmpOp::MaskedEq(libc::PROT_EXEC as u64),
             0,
         )?;
-        let clone_rule = SeccompRule::new(vec![allow_clone])?;
-        rules.insert(libc::SYS_clone, vec![clone_rule]);
-
-        // Apply seccomp filter.
-        let filter = SeccompFilter::new(
-            rules,
-            // Action performed if no rule matches.
-            SeccompAction::Errno(libc::EACCES as u32),
-            // Action performed if any rule matches.
-            SeccompAction::Allow,
-            ARCH,
-        )?;
-        let program: BpfProgram = filter.try_into()?;
-        seccompiler::apply_filter(&program)?;
-
-        // Change `clone3` syscall error to "not implemented", to force `clone` usage.
-        let mut rules = BTreeMap::new();
-        rules.insert(libc::SYS_clone3, Vec::new());
-        let filter = SeccompFilter::new(
-            rules,
-            // Action performed if no rule matches.
-            SeccompAction::Allow,
-            // Action performed if any rule matches.
-            SeccompAction::Errno(libc::ENOSYS as u32),
-            ARCH,
-        )?;
-        let program: BpfProgram = filter.try_into()?;
-        seccompiler::apply_filter(&program)?;
+        rules.insert(libc::SYS_mmap, vec![SeccompRule::new(vec![allow_mmap])?]);
+
+        let mut whitelist = compile_whitelist(rules, SeccompAction::Trace(AUDIT_MARKER))?;
+
+        // Replace the marker with user notifications.
+        for instruction in &mut whitelist {
+            if instruction.code == BPF_RET_K && instruction.k == SECCOMP_RET_TRACE | AUDIT_MARKER {
+                instruction.k = SECCOMP_RET_USER_NOTIF;
+            }
+        }
+
+        Ok(Self {
+            whitelist,
+            clone3: compile_clone3()?,
+            ioctls: compile_ioctls()?,
+            sockets: compile_sockets()?,
+        })
+    }
+
+    /// Apply the seccomp filter.
+    ///
+    /// If `restrict_sockets` is `true`, all internet sockets except for TCP are
+    /// blocked, since Landlock cannot restrict them without a network
+    /// namespace.
+    pub(crate) fn apply(&self, restrict_sockets: bool) -> RawResult<'static, ()> {
+        load_filter(&self.whitelist)?;
+        load_filter(&self.clone3)?;
+        load_filter(&self.ioctls)?;
+        if restrict_sockets {
+            load_filter(&self.sockets)?;
+        }
 
         Ok(())
     }
+
+    /// Apply the audit seccomp filter.
+    ///
+    /// Returns the listener for the filter's user notifications. Until the
+    /// listener is handled by a supervisor, all audited syscalls will block.
+    pub(crate) fn apply_audit(&self) -> RawResult<'static, OwnedFd> {
+        // Force `clone` usage, since `clone3` flags cannot be audited.
+        load_filter(&self.clone3)?;
+
+        let program = libc::sock_fprog {
+            len: self.whitelist.len() as u16,
+            filter: self.whitelist.as_ptr() as _,
+        };
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                SECCOMP_SET_MODE_FILTER,
+                SECCOMP_FILTER_FLAG_NEW_LISTENER,
+                &program as *const libc::sock_fprog,
+            )
+        };
+
+        if fd < 0 {
+            Err(RawError::SeccompLoad(errno(&IoError::last_os_error())))
+        } else {
+            Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+        }
+    }
+}
+
+/// Load a compiled seccomp filter into the kernel.
+fn load_filter(filter: &BpfProgram) -> RawResult<'static, ()> {
+    seccompiler::apply_filter(filter).map_err(|error| match error {
+        SeccompError::Prctl(error) => RawError::SeccompLoad(errno(&error)),
+        _ => RawError::ActivationFailed("invalid seccomp filter"),
+    })
+}
+
+/// Compile a filter allowing syscalls matching `rules`.
+///
+/// The `clone` syscall is allowed without flags for creating new namespaces.
+fn compile_whitelist(
+    mut rules: BTreeMap<c_long, Vec<SeccompRule>>,
+    default_action: SeccompAction,
+) -> Result<BpfProgram> {
+    // Add exception for the `clone` syscall.
+    let allow_clone = SeccompCondition::new(
+        0,
+        SeccompCmpArgLen::Qword,
+        SeccompCmpOp::MaskedEq(CLONE_NAMESPACE_FILTER as u64),
+        0,
+    )?;
+    let clone_rule = SeccompRule::new(vec![allow_clone])?;
+    rules.insert(libc::SYS_clone, vec![clone_rule]);
+
+    let filter = SeccompFilter::new(
+        rules,
+        // Action performed if no rule matches.
+        default_action,
+        // Action performed if any rule matches.
+        SeccompAction::Allow,
+        ARCH,
+    )?;
+
+    Ok(filter.try_into()?)
+}
+
+/// Compile a filter reporting `clone3` as unimplemented, to force `clone`
+/// usage.
+fn compile_clone3() -> Result<BpfProgram> {
+    let mut rules = BTreeMap::new();
+    rules.insert(libc::SYS_clone3, Vec::new());
+    let filter = SeccompFilter::new(
+        rules,
+        // Action performed if no rule matches.
+        SeccompAction::Allow,
+        // Action performed if any rule matches.
+        SeccompAction::Errno(libc::ENOSYS as u32),
+        ARCH,
+    )?;
+
+    Ok(filter.try_into()?)
+}
+
+/// Compile a filter denying the [`DENIED_IOCTLS`].
+fn compile_ioctls() -> Result<BpfProgram> {
+    // Requests are 32-bit, so the upper half of the argument is ignored.
+    let rules = DENIED_IOCTLS
+        .iter()
+        .map(|request| {
+            let condition =
+                SeccompCondition::new(1, SeccompCmpArgLen::Dword, SeccompCmpOp::Eq, *request)?;
+            SeccompRule::new(vec![condition])
+        })
+        .collect::<StdResult<_, _>>()?;
+
+    let mut syscalls = BTreeMap::new();
+    syscalls.insert(libc::SYS_ioctl, rules);
+    let filter = SeccompFilter::new(
+        syscalls,
+        // Action performed if no rule matches.
+        SeccompAction::Allow,
+        // Action performed if any rule matches.
+        SeccompAction::Errno(libc::EACCES as u32),
+        ARCH,
+    )?;
+
+    Ok(filter.try_into()?)
+}
+
+/// Compile a filter denying all sockets not covered by Landlock's TCP
+/// restrictions.
+///
+/// Only Unix domain, netlink and TCP sockets can be created.
+fn compile_sockets() -> Result<BpfProgram> {
+    let arg = |index, op, value| SeccompCondition::new(index, SeccompCmpArgLen::Dword, op, value);
+
+    // Deny all address families except Unix domain, netlink and internet sockets.
+    let mut rules = vec![SeccompRule::new(vec![
+        arg(0, SeccompCmpOp::Ne, libc::AF_UNIX as u64)?,
+        arg(0, SeccompCmpOp::Ne, libc::AF_NETLINK as u64)?,
+        arg(0, SeccompCmpOp::Ne, libc::AF_INET as u64)?,
+        arg(0, SeccompCmpOp::Ne, libc::AF_INET6 as u64)?,
+    ])?];
+
+    for family in [libc::AF_INET, libc::AF_INET6] {
+        // Deny all internet socket types except for streams.
+        for socket_type in (0..=SOCK_TYPE_MASK).filter(|ty| *ty != libc::SOCK_STREAM as u64) {
+            rules.push(SeccompRule::new(vec![
+                arg(0, SeccompCmpOp::Eq, family as u64)?,
+                arg(1, SeccompCmpOp::MaskedEq(SOCK_TYPE_MASK), socket_type)?,
+            ])?);
+        }
+
+        // Deny stream protocols other than TCP, like SCTP or MPTCP.
+        rules.push(SeccompRule::new(vec![
+            arg(0, SeccompCmpOp::Eq, family as u64)?,
+            arg(2, SeccompCmpOp::Ne, 0)?,
+            arg(2, SeccompCmpOp::Ne, libc::IPPROTO_TCP as u64)?,
+        ])?);
+    }
+
+    let mut syscalls = BTreeMap::new();
+    syscalls.insert(libc::SYS_socket, rules);
+    let filter = SeccompFilter::new(
+        syscalls,
+        // Action performed if no rule matches.
+        SeccompAction::Allow,
+        // Action performed if any rule matches.
+        SeccompAction::Errno(libc::EACCES as u32),
+        ARCH,
+    )?;
+
+    Ok(filter.try_into()?)
 }
 
+/// Whitelisted syscalls which are inspected in audit mode.
+///
+/// These syscalls access filesystem paths or network addresses, which are
+/// checked against the sandbox exceptions by the audit supervisor.
+pub const AUDITED_SYSCALLS: &[c_long] = &[
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_open,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_creat,
+    libc::SYS_openat,
+    libc::SYS_openat2,
+    libc::SYS_execve,
+    libc::SYS_execveat,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_mkdir,
+    libc::SYS_mkdirat,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_unlink,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_rmdir,
+    libc::SYS_unlinkat,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_rename,
+    libc::SYS_renameat,
+    libc::SYS_renameat2,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_link,
+    libc::SYS_linkat,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_symlink,
+    libc::SYS_symlinkat,
+    libc::SYS_truncate,
+    libc::SYS_ftruncate,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_chmod,
+    libc::SYS_fchmodat,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_chown,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_lchown,
+    libc::SYS_fchownat,
+    libc::SYS_utimensat,
+    libc::SYS_setxattr,
+    libc::SYS_lsetxattr,
+    #[cfg(target_arch = "x86_64")]
+    libc::SYS_mknod,
+    libc::SYS_mknodat,
+    libc::SYS_connect,
+    libc::SYS_bind,
+    libc::SYS_sendto,
+];
+
 /// Unconditionally allowed syscalls for networking.
-const SYSCALL_WHITELIST: &[libc::c_long] = &[
+const SYSCALL_WHITELIST: &[c_long] = &[
     libc::SYS_read,
     libc::SYS_write,
     #[cfg(target_arch = "x86_64")]
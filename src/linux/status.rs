@@ -0,0 +1,362 @@
+//! Activation status of sandboxed processes.
+//!
+//! Sandboxes applied by [`crate::Sandbox::spawn`] are activated in a forked
+//! child, which must not allocate memory. The applied layers are recorded
+//! without allocating and sent to the parent through a pipe, right before the
+//! sandboxee is executed.
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{Error as IoError, Read};
+use std::os::unix::io::{FromRawFd, OwnedFd};
+use std::str;
+
+use crate::error::{Error, RawError, RawResult, Result};
+use crate::linux::sys;
+use crate::report::{DroppedException, Layer, Report};
+
+/// Maximum number of layers recorded during activation.
+const MAX_RECORDS: usize = 16;
+
+/// All layers, indexed by their identifier in the status pipe.
+const LAYERS: &[Layer] = &[
+    Layer::MountNamespace,
+    Layer::NetworkNamespace,
+    Layer::PidNamespace,
+    Layer::IpcNamespace,
+    Layer::UtsNamespace,
+    Layer::TimeNamespace,
+    Layer::Landlock,
+    Layer::LandlockNetwork,
+    Layer::NoNewPrivileges,
+    Layer::Seccomp,
+];
+
+/// Message tag for an applied layer.
+const TAG_APPLIED: u8 = 0;
+/// Message tag for a layer skipped with a static reason.
+const TAG_SKIPPED: u8 = 1;
+/// Message tag for a layer skipped because of an error.
+const TAG_SKIPPED_ERROR: u8 = 2;
+/// Message tag for a successful activation.
+const TAG_DONE: u8 = 3;
+/// Message tag for a failed activation.
+const TAG_FAILED: u8 = 4;
+
+/// Reason why a layer was skipped.
+#[derive(Clone, Copy)]
+enum SkipReason<'a> {
+    Message(&'a str),
+    Error(RawError<'a>),
+}
+
+/// Layer applied or skipped during activation.
+#[derive(Clone, Copy)]
+struct Record<'a> {
+    layer: Layer,
+    skipped: Option<SkipReason<'a>>,
+}
+
+/// Sandboxing layers recorded without allocating.
+///
+/// This mirrors the [`Report`] it is converted to once the sandbox is active.
+pub struct Status<'a> {
+    records: [Option<Record<'a>>; MAX_RECORDS],
+    len: usize,
+}
+
+impl<'a> Status<'a> {
+    pub fn new() -> Self {
+        Self { records: [None; MAX_RECORDS], len: 0 }
+    }
+
+    /// Record a successfully applied layer.
+    pub fn apply(&mut self, layer: Layer) {
+        self.push(Record { layer, skipped: None });
+    }
+
+    /// Record a layer which could not be applied.
+    pub fn skip(&mut self, layer: Layer, reason: &'a str) {
+        self.push(Record { layer, skipped: Some(SkipReason::Message(reason)) });
+    }
+
+    /// Record the result of applying a layer.
+    ///
+    /// Errors are recorded as skipped layer if `skippable` returns `true` for
+    /// them, otherwise they are returned.
+    ///
+    /// Returns `true` if the layer was applied.
+    pub(crate) fn record(
+        &mut self,
+        layer: Layer,
+        result: RawResult<'a, ()>,
+        skippable: impl Fn(&RawError<'a>) -> bool,
+    ) -> RawResult<'a, bool> {
+        match result {
+            Ok(()) => {
+                self.apply(layer);
+                Ok(true)
+            },
+            Err(error) if skippable(&error) => {
+                self.push(Record { layer, skipped: Some(SkipReason::Error(error)) });
+                Ok(false)
+            },
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Check if a layer was applied.
+    pub fn is_applied(&self, layer: Layer) -> bool {
+        self.records().any(|record| record.layer == layer && record.skipped.is_none())
+    }
+
+    /// Check if a layer was skipped.
+    pub fn is_skipped(&self, layer: Layer) -> bool {
+        self.records().any(|record| record.layer == layer && record.skipped.is_some())
+    }
+
+    /// Convert the recorded layers to a report.
+    pub fn into_report(self, dropped: Vec<DroppedException>) -> Report {
+        let mut report = Report::with_dropped(dropped);
+        for record in self.records() {
+            match record.skipped {
+                None => report.apply(record.layer),
+                Some(SkipReason::Message(reason)) => report.skip(record.layer, reason),
+                Some(SkipReason::Error(error)) => {
+                    report.skip(record.layer, Error::from(error).to_string())
+                },
+            }
+        }
+        report
+    }
+
+    /// Send the recorded layers and the activation `result` to the parent.
+    ///
+    /// Errors are ignored, the parent treats an incomplete status as failed
+    /// activation.
+    pub(crate) fn send(&self, pipe: &OwnedFd, result: RawResult<'_, ()>) {
+        let mut writer = Writer { pipe };
+
+        for record in self.records() {
+            let layer = LAYERS.iter().position(|layer| *layer == record.layer).unwrap_or(0);
+            match record.skipped {
+                None => writer.write(&[TAG_APPLIED, layer as u8]),
+                Some(SkipReason::Message(reason)) => {
+                    writer.write(&[TAG_SKIPPED, layer as u8]);
+                    writer.write_bytes(reason.as_bytes());
+                },
+                Some(SkipReason::Error(error)) => {
+                    writer.write(&[TAG_SKIPPED_ERROR, layer as u8]);
+                    writer.write_error(&error);
+                },
+            }
+        }
+
+        match result {
+            Ok(()) => writer.write(&[TAG_DONE]),
+            Err(error) => {
+                writer.write(&[TAG_FAILED]);
+                writer.write_error(&error);
+            },
+        }
+    }
+
+    /// Iterate over all recorded layers.
+    fn records(&self) -> impl Iterator<Item = &Record<'a>> {
+        self.records[..self.len].iter().flatten()
+    }
+
+    /// Add a new record, ignoring records exceeding the capacity.
+    fn push(&mut self, record: Record<'a>) {
+        if let Some(slot) = self.records.get_mut(self.len) {
+            *slot = Some(record);
+            self.len += 1;
+        }
+    }
+}
+
+/// Create a pipe for reporting the activation status to the parent.
+///
+/// Both ends are closed on `exec`, so the reader reaches EOF once the
+/// sandboxee was executed or the activating process exited.
+pub fn pipe() -> Result<(File, OwnedFd)> {
+    let mut fds = [0; 2];
+    let result = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+    if result != 0 {
+        return Err(IoError::last_os_error().into());
+    }
+
+    Ok(unsafe { (File::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// Receive the activation status sent by [`Status::send`].
+///
+/// All write ends of the pipe must be closed in the parent, otherwise this
+/// will block forever.
+pub fn receive(mut pipe: File, dropped: Vec<DroppedException>) -> Result<Report> {
+    let mut buffer = Vec::new();
+    pipe.read_to_end(&mut buffer)?;
+
+    let mut reader = Reader { buffer: &buffer };
+    let mut report = Report::with_dropped(dropped);
+    loop {
+        let layer = match reader.read_u8() {
+            Some(TAG_DONE) => return Ok(report),
+            Some(TAG_FAILED) => {
+                return Err(reader.read_error().map_or_else(incomplete, Error::from))
+            },
+            Some(TAG_APPLIED) => reader.read_layer().map(|layer| (layer, None)),
+            Some(TAG_SKIPPED) => reader.read_layer().and_then(|layer| {
+                let reason = str::from_utf8(reader.read_bytes()?).ok()?;
+                Some((layer, Some(reason.to_owned())))
+            }),
+            Some(TAG_SKIPPED_ERROR) => reader.read_layer().and_then(|layer| {
+                let error = Error::from(reader.read_error()?);
+                Some((layer, Some(error.to_string())))
+            }),
+            _ => None,
+        };
+
+        match layer {
+            Some((layer, None)) => report.apply(layer),
+            Some((layer, Some(reason))) => report.skip(layer, reason),
+            None => return Err(incomplete()),
+        }
+    }
+}
+
+/// Error for processes which exited before completing activation.
+fn incomplete() -> Error {
+    Error::ActivationFailed("sandboxed process exited during activation".into())
+}
+
+/// Allocation-free writer for the status pipe.
+struct Writer<'a> {
+    pipe: &'a OwnedFd,
+}
+
+impl Writer<'_> {
+    /// Write an error with its OS error code and paths.
+    fn write_error(&mut self, error: &RawError<'_>) {
+        let (tag, errno, strings) = encode_error(error);
+        self.write(&[tag]);
+        self.write(&errno.to_le_bytes());
+        for string in strings {
+            self.write_bytes(string);
+        }
+    }
+
+    /// Write a length-prefixed byte string.
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u32).to_le_bytes());
+        self.write(bytes);
+    }
+
+    /// Write all bytes.
+    fn write(&mut self, bytes: &[u8]) {
+        let _ = sys::write_all(self.pipe, bytes);
+    }
+}
+
+/// Reader for the status pipe's contents.
+struct Reader<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Read an error written by [`Writer::write_error`].
+    fn read_error(&mut self) -> Option<RawError<'a>> {
+        let tag = self.read_u8()?;
+        let errno = i32::from_le_bytes(self.read_array()?);
+        let strings = [self.read_bytes()?, self.read_bytes()?];
+        decode_error(tag, errno, strings)
+    }
+
+    /// Read a layer identifier.
+    fn read_layer(&mut self) -> Option<Layer> {
+        LAYERS.get(self.read_u8()? as usize).copied()
+    }
+
+    /// Read a length-prefixed byte string.
+    fn read_bytes(&mut self) -> Option<&'a [u8]> {
+        let len = u32::from_le_bytes(self.read_array()?) as usize;
+        self.take(len)
+    }
+
+    fn read_u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.buffer.len() {
+            return None;
+        }
+
+        let (bytes, rest) = self.buffer.split_at(len);
+        self.buffer = rest;
+        Some(bytes)
+    }
+}
+
+/// Split an error into its variant, OS error code and strings.
+///
+/// Paths are encoded with their trailing NUL byte.
+fn encode_error<'a>(error: &RawError<'a>) -> (u8, i32, [&'a [u8]; 2]) {
+    let errno = error.errno().unwrap_or(0);
+    let none: &[u8] = &[];
+    let (tag, string) = match *error {
+        RawError::Io(_) => (0, none),
+        RawError::ActivationFailed(message) => (1, message.as_bytes()),
+        RawError::InvalidPath(path) => (2, path.to_bytes_with_nul()),
+        RawError::UserNamespace(_) => (3, none),
+        RawError::IdMap { path, .. } => (4, path.to_bytes_with_nul()),
+        RawError::BindMount { path, target, .. } => {
+            return (5, errno, [path.to_bytes_with_nul(), target.to_bytes_with_nul()]);
+        },
+        RawError::MountSetattr { path, .. } => (6, path.to_bytes_with_nul()),
+        RawError::PivotRoot { path, .. } => (7, path.to_bytes_with_nul()),
+        RawError::SeccompLoad(_) => (8, none),
+        RawError::NoNewPrivs(_) => (9, none),
+        RawError::Mount { path, .. } => (10, path.to_bytes_with_nul()),
+        RawError::Unmount { path, .. } => (11, path.to_bytes_with_nul()),
+        RawError::CreateDir { path, .. } => (12, path.to_bytes_with_nul()),
+        RawError::CreateNode { path, .. } => (13, path.to_bytes_with_nul()),
+        RawError::Hostname(_) => (14, none),
+        RawError::Domainname(_) => (15, none),
+        RawError::TimeNamespace { path, .. } => (16, path.to_bytes_with_nul()),
+        RawError::Loopback(_) => (17, none),
+    };
+    (tag, errno, [string, none])
+}
+
+/// Restore an error split by [`encode_error`].
+fn decode_error(tag: u8, errno: i32, strings: [&[u8]; 2]) -> Option<RawError<'_>> {
+    let path = || CStr::from_bytes_with_nul(strings[0]).ok();
+    let target = || CStr::from_bytes_with_nul(strings[1]).ok();
+    let error = match tag {
+        0 => RawError::Io(errno),
+        1 => RawError::ActivationFailed(str::from_utf8(strings[0]).ok()?),
+        2 => RawError::InvalidPath(path()?),
+        3 => RawError::UserNamespace(errno),
+        4 => RawError::IdMap { path: path()?, errno },
+        5 => RawError::BindMount { path: path()?, target: target()?, errno },
+        6 => RawError::MountSetattr { path: path()?, errno },
+        7 => RawError::PivotRoot { path: path()?, errno },
+        8 => RawError::SeccompLoad(errno),
+        9 => RawError::NoNewPrivs(errno),
+        10 => RawError::Mount { path: path()?, errno },
+        11 => RawError::Unmount { path: path()?, errno },
+        12 => RawError::CreateDir { path: path()?, errno },
+        13 => RawError::CreateNode { path: path()?, errno },
+        14 => RawError::Hostname(errno),
+        15 => RawError::Domainname(errno),
+        16 => RawError::TimeNamespace { path: path()?, errno },
+        17 => RawError::Loopback(errno),
+        _ => return None,
+    };
+    Some(error)
+}
@@ -0,0 +1,226 @@
+//! Allocation-free system call wrappers.
+//!
+//! Sandboxes are activated in forked children of processes which might have
+//! multiple threads, so everything used during activation must work without
+//! allocating memory.
+
+use std::ffi::CStr;
+use std::fmt::{self, Write};
+use std::io::{self, Error as IoError};
+use std::mem;
+use std::os::raw::c_int;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+
+/// Open a file.
+pub fn open(path: &CStr, flags: c_int, mode: libc::mode_t) -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::open(path.as_ptr(), flags | libc::O_CLOEXEC, mode) };
+    if fd < 0 {
+        return Err(IoError::last_os_error());
+    }
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Open a path relative to `root`, as if `root` was the root directory.
+///
+/// Symlinks are resolved without leaving `root`, while a symlink at `path`
+/// itself is opened instead of its target.
+pub fn open_in_root(root: &OwnedFd, path: &CStr) -> io::Result<OwnedFd> {
+    let how = OpenHow {
+        flags: (libc::O_PATH | libc::O_NOFOLLOW | libc::O_CLOEXEC) as u64,
+        mode: 0,
+        resolve: RESOLVE_NO_MAGICLINKS | RESOLVE_IN_ROOT,
+    };
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_openat2,
+            root.as_raw_fd(),
+            path.as_ptr(),
+            &how as *const _,
+            mem::size_of::<OpenHow>(),
+        )
+    };
+    if fd < 0 {
+        return Err(IoError::last_os_error());
+    }
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as c_int) })
+}
+
+/// Create a detached, recursive copy of the mount tree at `path`.
+pub fn open_tree(path: &CStr) -> io::Result<OwnedFd> {
+    let flags = OPEN_TREE_CLONE | libc::O_CLOEXEC as u32 | libc::AT_RECURSIVE as u32;
+    let fd = unsafe { libc::syscall(libc::SYS_open_tree, libc::AT_FDCWD, path.as_ptr(), flags) };
+    if fd < 0 {
+        return Err(IoError::last_os_error());
+    }
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as c_int) })
+}
+
+/// Attach the mount tree `tree` on top of the file referenced by `target`.
+pub fn move_mount(tree: &OwnedFd, target: &OwnedFd) -> io::Result<()> {
+    let empty = b"\0".as_ptr();
+    let flags = MOVE_MOUNT_F_EMPTY_PATH | MOVE_MOUNT_T_EMPTY_PATH;
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_move_mount,
+            tree.as_raw_fd(),
+            empty,
+            target.as_raw_fd(),
+            empty,
+            flags,
+        )
+    };
+    match res {
+        0 => Ok(()),
+        _ => Err(IoError::last_os_error()),
+    }
+}
+
+/// Write all `bytes` to a file descriptor.
+pub fn write_all(fd: &OwnedFd, mut bytes: &[u8]) -> io::Result<()> {
+    while !bytes.is_empty() {
+        let written = unsafe { libc::write(fd.as_raw_fd(), bytes.as_ptr().cast(), bytes.len()) };
+        if written > 0 {
+            bytes = &bytes[written as usize..];
+        } else if written == 0 {
+            return Err(IoError::from_raw_os_error(libc::EIO));
+        } else {
+            let error = IoError::last_os_error();
+            if error.raw_os_error() != Some(libc::EINTR) {
+                return Err(error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write `contents` to an existing file.
+pub fn write_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+    let file = open(path, libc::O_WRONLY, 0)?;
+    write_all(&file, contents)
+}
+
+/// Get a file's metadata, following symlinks.
+pub fn stat(path: &CStr) -> io::Result<libc::stat> {
+    let mut stat = unsafe { mem::zeroed() };
+    match unsafe { libc::stat(path.as_ptr(), &mut stat) } {
+        0 => Ok(stat),
+        _ => Err(IoError::last_os_error()),
+    }
+}
+
+/// Get a file's metadata, without following symlinks.
+pub fn lstat(path: &CStr) -> io::Result<libc::stat> {
+    let mut stat = unsafe { mem::zeroed() };
+    match unsafe { libc::lstat(path.as_ptr(), &mut stat) } {
+        0 => Ok(stat),
+        _ => Err(IoError::last_os_error()),
+    }
+}
+
+/// Get the metadata of an open file.
+pub fn fstat(fd: &OwnedFd) -> io::Result<libc::stat> {
+    let mut stat = unsafe { mem::zeroed() };
+    match unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } {
+        0 => Ok(stat),
+        _ => Err(IoError::last_os_error()),
+    }
+}
+
+/// Check if the metadata belongs to a directory.
+pub fn is_dir(stat: &libc::stat) -> bool {
+    stat.st_mode & libc::S_IFMT == libc::S_IFDIR
+}
+
+/// Create a directory.
+pub fn mkdir(path: &CStr) -> io::Result<()> {
+    match unsafe { libc::mkdir(path.as_ptr(), 0o777) } {
+        0 => Ok(()),
+        _ => Err(IoError::last_os_error()),
+    }
+}
+
+/// Create an empty file, truncating existing files.
+pub fn create_file(path: &CStr) -> io::Result<OwnedFd> {
+    open(path, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC, 0o666)
+}
+
+/// Change the permissions of a file.
+pub fn chmod(path: &CStr, mode: u32) -> io::Result<()> {
+    match unsafe { libc::chmod(path.as_ptr(), mode & 0o7777) } {
+        0 => Ok(()),
+        _ => Err(IoError::last_os_error()),
+    }
+}
+
+/// Create a symlink at `link` pointing to `target`.
+pub fn symlink(target: &CStr, link: &CStr) -> io::Result<()> {
+    match unsafe { libc::symlink(target.as_ptr(), link.as_ptr()) } {
+        0 => Ok(()),
+        _ => Err(IoError::last_os_error()),
+    }
+}
+
+/// Change the working directory.
+pub fn chdir(path: &CStr) -> io::Result<()> {
+    match unsafe { libc::chdir(path.as_ptr()) } {
+        0 => Ok(()),
+        _ => Err(IoError::last_os_error()),
+    }
+}
+
+/// Fixed-size buffer for formatting short strings.
+pub struct FormatBuffer<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> FormatBuffer<N> {
+    pub fn new() -> Self {
+        Self { bytes: [0; N], len: 0 }
+    }
+
+    /// Get the formatted bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl<const N: usize> Write for FormatBuffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > N {
+            return Err(fmt::Error);
+        }
+
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Parameter for the `openat2` syscall.
+#[repr(C)]
+struct OpenHow {
+    flags: u64,
+    mode: u64,
+    resolve: u64,
+}
+
+/// Do not resolve magic links like `/proc/self/fd/*`.
+const RESOLVE_NO_MAGICLINKS: u64 = 0x02;
+
+/// Treat the directory file descriptor as the root directory.
+const RESOLVE_IN_ROOT: u64 = 0x10;
+
+/// Clone the mount tree instead of opening it.
+const OPEN_TREE_CLONE: u32 = 1;
+
+/// Use the source file descriptor as the mount to be moved.
+const MOVE_MOUNT_F_EMPTY_PATH: u32 = 0x04;
+
+/// Use the target file descriptor as the mount point.
+const MOVE_MOUNT_T_EMPTY_PATH: u32 = 0x40;
@@ -3,13 +3,19 @@
 //! This module implements sandboxing on macOS using `sandbox_init`.
 
 use std::ffi::{CStr, CString};
-use std::io::Write;
+use std::io::{Error as IoError, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
 use std::path::PathBuf;
+use std::process::{Child, Command};
 use std::result::Result as StdResult;
 use std::{fs, ptr};
 
+#[cfg(feature = "serde")]
+use crate::config::SandboxConfig;
 use crate::error::{Error, Result};
-use crate::{Exception, Sandbox};
+use crate::report::{Layer, Report};
+use crate::{Exception, Sandbox, Strictness};
 
 /// Deny-all fallback rule.
 static DEFAULT_RULE: &[u8] = b"\
@@ -27,90 +33,305 @@ static DEFAULT_RULE: &[u8] = b"\
 (system-network)
 ";
 
+/// Error code returned by spawned children if the profile could not be applied.
+const EPERM: i32 = 1;
+
 /// macOS sandboxing based on Seatbelt.
 pub struct MacSandbox {
     env_exceptions: Vec<String>,
     profile: Vec<u8>,
+    path_rules: Vec<PathRule>,
     full_env: bool,
+    strictness: Strictness,
+    #[cfg(feature = "serde")]
+    exceptions: Vec<Exception>,
 }
 
-impl Sandbox for MacSandbox {
-    fn new() -> Self {
-        Self { profile: DEFAULT_RULE.to_vec(), env_exceptions: Vec::new(), full_env: false }
+impl MacSandbox {
+    /// Get the configuration of this sandbox.
+    #[cfg(feature = "serde")]
+    pub fn config(&self) -> SandboxConfig {
+        let mut config = SandboxConfig::new(self.exceptions.clone());
+        config.strictness = self.strictness;
+        config
     }
 
-    fn add_exception(&mut self, exception: Exception) -> Result<&mut Self> {
+    /// Add the Seatbelt rules for an exception.
+    fn add_rule(&mut self, exception: Exception) -> Result<()> {
         // Temporary buffer to hold intermediate writes.
         // Prevents errors from breaking the whole sandbox profile.
         let mut buffer = Vec::new();
 
         match exception {
             Exception::Read(path) => {
-                buffer.write_all(b"(allow file-read* (subpath ")?;
-                let escaped_path = escape_path(path)?;
-                buffer.write_all(escaped_path.as_bytes())?;
-                buffer.write_all(b"))\n")?;
+                self.add_path_rule(path, b"(allow file-read* (subpath ", false)?;
             },
             Exception::WriteAndRead(path) => {
-                self.add_exception(Exception::Read(path.clone()))?;
-
-                buffer.write_all(b"(allow file-write* (subpath ")?;
-                let escaped_path = escape_path(path)?;
-                buffer.write_all(escaped_path.as_bytes())?;
-                buffer.write_all(b"))\n")?;
+                self.add_rule(Exception::Read(path.clone()))?;
+                self.add_path_rule(path, b"(allow file-write* (subpath ", false)?;
             },
             Exception::ExecuteAndRead(path) => {
-                self.add_exception(Exception::Read(path.clone()))?;
-
-                buffer.write_all(b"(allow process-exec (subpath ")?;
-                let escaped_path = escape_path(path)?;
-                buffer.write_all(escaped_path.as_bytes())?;
-                buffer.write_all(b"))\n")?;
+                self.add_rule(Exception::Read(path.clone()))?;
+                self.add_path_rule(path, b"(allow process-exec (subpath ", false)?;
+            },
+            Exception::Device(path) => {
+                let prefix = b"(allow file-read* file-write* file-ioctl (literal ";
+                self.add_path_rule(path, prefix, false)?;
+            },
+            Exception::ReadAll => {
+                let rule =
+                    b"(allow file-read* (require-all (subpath \"/\") (require-not (subpath \"/dev\"))))\n";
+                self.path_rules.push(PathRule { depth: 1, deny: false, rule: rule.to_vec() });
+            },
+            Exception::Deny(path) => {
+                let prefix = b"(deny file-read* file-write* process-exec (subpath ";
+                self.add_path_rule(path, prefix, true)?;
             },
             Exception::Networking => {
                 buffer.write_all(b"(allow network*)\n")?;
             },
+            Exception::LoopbackNetworking => {
+                buffer.write_all(b"(allow network* (local ip \"localhost:*\"))\n")?;
+                buffer.write_all(b"(allow network* (remote ip \"localhost:*\"))\n")?;
+            },
+            Exception::Tmpfs { .. } => {
+                return Err(Error::InvalidException("tmpfs exceptions are not supported on macOS"));
+            },
+            Exception::Overlay { .. } => {
+                return Err(Error::InvalidException(
+                    "overlay exceptions are not supported on macOS",
+                ));
+            },
+            Exception::Bind { .. } => {
+                return Err(Error::InvalidException("bind exceptions are not supported on macOS"));
+            },
+            Exception::File { .. } => {
+                return Err(Error::InvalidException("file exceptions are not supported on macOS"));
+            },
+            Exception::VirtualHome { .. } => {
+                return Err(Error::InvalidException(
+                    "virtual home directories are not supported on macOS",
+                ));
+            },
+            Exception::NetworkHost { .. } => {
+                return Err(Error::InvalidException(
+                    "network host exceptions are not supported on macOS",
+                ));
+            },
             Exception::Environment(key) => {
                 self.env_exceptions.push(key);
-                return Ok(self);
+                return Ok(());
             },
             Exception::FullEnvironment => {
                 self.full_env = true;
-                return Ok(self);
+                return Ok(());
             },
+            // IPC is always allowed by the default rule.
+            Exception::HostIpc | Exception::FullProcfs => return Ok(()),
         }
         self.profile.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Add a Seatbelt rule for a path, starting with `prefix`.
+    fn add_path_rule(&mut self, path: PathBuf, prefix: &[u8], deny: bool) -> Result<()> {
+        let depth = fs::canonicalize(&path)
+            .map_err(|_| Error::InvalidPath(path.clone()))?
+            .components()
+            .count();
+
+        let mut rule = prefix.to_vec();
+        rule.extend_from_slice(escape_path(path)?.as_bytes());
+        rule.extend_from_slice(b"))\n");
+        self.path_rules.push(PathRule { depth, deny, rule });
+
+        Ok(())
+    }
+
+    /// Build the complete Seatbelt profile.
+    ///
+    /// Since the last matching rule wins, path rules are ordered by their
+    /// depth. This way denied paths override their ancestors, while
+    /// exceptions beneath a denied path override the denial, independent of
+    /// the order they were added in.
+    fn profile(&self) -> Result<CString> {
+        let mut path_rules: Vec<_> = self.path_rules.iter().collect();
+        path_rules.sort_by_key(|rule| (rule.depth, rule.deny));
+
+        let mut profile = self.profile.clone();
+        for path_rule in path_rules {
+            profile.extend_from_slice(&path_rule.rule);
+        }
+
+        CString::new(profile).map_err(|_| Error::ActivationFailed("invalid profile".into()))
+    }
+}
+
+/// Seatbelt rule for a single path.
+struct PathRule {
+    depth: usize,
+    deny: bool,
+    rule: Vec<u8>,
+}
+
+impl Sandbox for MacSandbox {
+    fn new() -> Self {
+        Self {
+            profile: DEFAULT_RULE.to_vec(),
+            path_rules: Vec::new(),
+            env_exceptions: Vec::new(),
+            full_env: false,
+            strictness: Strictness::default(),
+            #[cfg(feature = "serde")]
+            exceptions: Vec::new(),
+        }
+    }
+
+    fn add_exception(&mut self, exception: Exception) -> Result<&mut Self> {
+        #[cfg(feature = "serde")]
+        let config_exception = exception.clone();
+
+        self.add_rule(exception)?;
+
+        #[cfg(feature = "serde")]
+        self.exceptions.push(config_exception);
+
         Ok(self)
     }
 
-    fn lock(self) -> Result<()> {
+    fn strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
+    fn prepare(self) -> Result<PreparedSandbox> {
+        let profile = self.profile()?;
+
+        Ok(PreparedSandbox {
+            profile,
+            env_exceptions: self.env_exceptions,
+            full_env: self.full_env,
+            strictness: self.strictness,
+        })
+    }
+
+    fn spawn(self, mut sandboxee: Command) -> Result<(Child, Report)> {
         // Remove environment variables.
         if !self.full_env {
-            crate::restrict_env_variables(&self.env_exceptions);
+            crate::restrict_command_env(&mut sandboxee, &self.env_exceptions);
         }
 
-        let profile = CString::new(self.profile)
-            .map_err(|_| Error::ActivationFailed("invalid profile".into()))?;
+        let profile = self.profile()?;
+
+        // Apply the sandbox profile in the child before executing the sandboxee.
+        //
+        // Errors are reported through a socket which is closed on `exec`, so the
+        // parent reads until the sandboxee was executed. The child of a
+        // multithreaded parent must not allocate, so the error message is
+        // written without any formatting.
+        let (mut status_reader, status_writer) = UnixStream::pair()?;
+        let best_effort = self.strictness == Strictness::BestEffort;
+        unsafe {
+            sandboxee.pre_exec(move || {
+                let report_error = |error: &[u8]| {
+                    let _ = (&status_writer).write_all(error);
+                };
+                if apply_profile(&profile, report_error) || best_effort {
+                    Ok(())
+                } else {
+                    Err(IoError::from_raw_os_error(EPERM))
+                }
+            });
+        }
 
-        let mut error = ptr::null_mut();
-        let result = unsafe { sandbox_init(profile.as_ptr(), 0, &mut error) };
+        let result = sandboxee.spawn();
 
-        if result == 0 {
-            Ok(())
+        // Close the writer held by the `pre_exec` closure, to receive the status.
+        drop(sandboxee);
+        let mut error = Vec::new();
+        status_reader.read_to_end(&mut error)?;
+        let error = String::from_utf8_lossy(&error);
+
+        let child = match result {
+            Err(_) if !best_effort && !error.is_empty() => {
+                return Err(Error::ActivationFailed(error.into()));
+            },
+            result => result?,
+        };
+
+        let mut report = Report::default();
+        if error.is_empty() {
+            report.apply(Layer::Seatbelt);
         } else {
-            unsafe {
-                let error_text = CStr::from_ptr(error)
-                    .to_str()
-                    .map_err(|_| Error::ActivationFailed("sandbox_init failed".into()))?
-                    .to_owned();
-                sandbox_free_error(error);
-
-                Err(Error::ActivationFailed(error_text))
-            }
+            report.skip(Layer::Seatbelt, error);
         }
+
+        Ok((child, report))
     }
 }
 
+/// macOS sandbox prepared for activation.
+///
+/// See [`Sandbox::prepare`].
+pub struct PreparedSandbox {
+    profile: CString,
+    env_exceptions: Vec<String>,
+    full_env: bool,
+    strictness: Strictness,
+}
+
+impl PreparedSandbox {
+    /// Apply the sandbox restrictions to the current process.
+    ///
+    /// This has the same requirements as [`Sandbox::lock`].
+    pub fn commit(self) -> Result<Report> {
+        // Remove environment variables.
+        if !self.full_env {
+            crate::restrict_env_variables(&self.env_exceptions);
+        }
+
+        let mut report = Report::default();
+        let best_effort = self.strictness == Strictness::BestEffort;
+        report.record(Layer::Seatbelt, activate(&self.profile), |_| best_effort)?;
+
+        Ok(report)
+    }
+}
+
+/// Apply the sandbox profile to the current process.
+fn activate(profile: &CStr) -> Result<()> {
+    let mut message = String::new();
+    if apply_profile(profile, |error| message = String::from_utf8_lossy(error).into_owned()) {
+        Ok(())
+    } else {
+        Err(Error::ActivationFailed(message))
+    }
+}
+
+/// Apply the sandbox profile to the current process.
+///
+/// Returns `true` if the profile was applied, otherwise Seatbelt's error
+/// message is passed to `report_error`. This does not allocate, so it is safe
+/// to call in a forked child.
+fn apply_profile(profile: &CStr, report_error: impl FnOnce(&[u8])) -> bool {
+    let mut error = ptr::null_mut();
+    let result = unsafe { sandbox_init(profile.as_ptr(), 0, &mut error) };
+
+    if result == 0 {
+        return true;
+    }
+
+    if error.is_null() {
+        report_error(b"sandbox_init failed");
+    } else {
+        let message = unsafe { CStr::from_ptr(error) }.to_bytes();
+        report_error(if message.is_empty() { b"sandbox_init failed" } else { message });
+        unsafe { sandbox_free_error(error) };
+    }
+
+    false
+}
+
 /// Escape a path: /tt/in\a"x -> "/tt/in\\a\"x"
 fn escape_path(path: PathBuf) -> StdResult<String, Error> {
     // Canonicalize the incoming path to support relative paths.
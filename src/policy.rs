@@ -0,0 +1,442 @@
+//! Declarative sandbox policy files.
+//!
+//! Policies are written in TOML and describe all exceptions of a sandbox:
+//!
+//! ```toml
+//! version = 1
+//!
+//! # Inherit exceptions from other policies.
+//! include = ["base.toml"]
+//!
+//! [filesystem]
+//! read = ["/usr/share", "~/.config/tool"]
+//! write = ["$XDG_CACHE_HOME/tool"]
+//! execute = ["/usr/bin", "/usr/lib"]
+//! deny = ["~/.config/tool/secrets"]
+//! devices = ["/dev/dri/renderD128"]
+//! read_all = false
+//! full_procfs = false
+//! tmpfs = [{ path = "/tmp", size = 67108864, mode = 0o1777 }]
+//! overlay = [{ path = "~/project", changes = "~/.cache/tool/changes" }]
+//! bind = [{ source = "~/jobs/42", target = "/work", access = "write_and_read" }]
+//! files = [{ path = "/etc/hosts", contents = "127.0.0.1 localhost\n", mode = 0o644 }]
+//!
+//! [environment]
+//! variables = ["PATH", "HOME"]
+//! all = false
+//!
+//! [network]
+//! enabled = false
+//! loopback = true
+//! hosts = ["example.com:443"]
+//!
+//! [ipc]
+//! host = false
+//!
+//! [home]
+//! virtual = true
+//! persistent = "~/.local/share/tool/home"
+//! ```
+//!
+//! A leading `~` in paths and includes is replaced by the `HOME` directory,
+//! while `$VAR` and `${VAR}` are replaced by the value of the environment
+//! variable `VAR`. Relative paths are resolved from the policy file's
+//! directory, except for bind targets and file paths, which refer to the
+//! sandbox and must be absolute.
+
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::config::SandboxConfig;
+use crate::error::{Error, Result};
+use crate::{BindAccess, Exception};
+
+/// Latest supported policy file version.
+pub const VERSION: u32 = 1;
+
+/// Sandbox policy loaded from a file.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    exceptions: Vec<Exception>,
+}
+
+impl Policy {
+    /// Load a policy file, including all policies it inherits from.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut includes = path.canonicalize().into_iter().collect();
+
+        let mut policy = Self::default();
+        policy.load_file(path, &mut includes)?;
+        Ok(policy)
+    }
+
+    /// Get all exceptions of the policy.
+    ///
+    /// Exceptions of included policies come before the exceptions of the
+    /// policy including them.
+    pub fn exceptions(&self) -> &[Exception] {
+        &self.exceptions
+    }
+
+    /// Get the sandbox configuration described by the policy.
+    ///
+    /// Use [`SandboxConfig::sandbox`] to create a sandbox from it.
+    pub fn config(&self) -> SandboxConfig {
+        SandboxConfig::new(self.exceptions.clone())
+    }
+
+    /// Load the exceptions of a single policy file.
+    ///
+    /// The `includes` are the files currently being loaded, to detect cycles.
+    fn load_file(&mut self, path: &Path, includes: &mut Vec<PathBuf>) -> Result<()> {
+        let source = fs::read_to_string(path)
+            .map_err(|err| PolicyError::new(path, None, format!("could not read file: {err}")))?;
+        let file = PolicyFile { path, source: &source };
+
+        let policy: RawPolicy = toml::from_str(&source)
+            .map_err(|err| file.error(err.span(), err.message().trim_end()))?;
+
+        if *policy.version.get_ref() != VERSION {
+            let message = format!("unsupported version, expected {VERSION}");
+            return Err(file.error(Some(policy.version.span()), message));
+        }
+
+        for include in &policy.include {
+            let include_path = file.resolve(include)?;
+            let include_path = include_path.canonicalize().map_err(|err| {
+                file.error(Some(include.span()), format!("could not read include: {err}"))
+            })?;
+
+            if includes.contains(&include_path) {
+                return Err(file.error(Some(include.span()), "include cycle detected"));
+            }
+
+            includes.push(include_path.clone());
+            self.load_file(&include_path, includes)?;
+            includes.pop();
+        }
+
+        let filesystem = &policy.filesystem;
+        let paths = [
+            (&filesystem.read, Exception::Read as fn(_) -> _),
+            (&filesystem.write, Exception::WriteAndRead),
+            (&filesystem.execute, Exception::ExecuteAndRead),
+            (&filesystem.deny, Exception::Deny),
+            (&filesystem.devices, Exception::Device),
+        ];
+        for (entries, exception) in paths {
+            for entry in entries {
+                let path = file.resolve(entry)?;
+                if !path.exists() {
+                    let message = format!("invalid path: {path:?}");
+                    return Err(file.error(Some(entry.span()), message));
+                }
+                self.exceptions.push(exception(path));
+            }
+        }
+        for tmpfs in &filesystem.tmpfs {
+            let path = file.resolve(&tmpfs.path)?;
+            if !path.is_dir() {
+                let message = format!("invalid tmpfs directory: {path:?}");
+                return Err(file.error(Some(tmpfs.path.span()), message));
+            } else if *tmpfs.size.get_ref() == 0 {
+                return Err(file.error(Some(tmpfs.size.span()), "tmpfs size must not be zero"));
+            }
+            let size = *tmpfs.size.get_ref();
+            self.exceptions.push(Exception::Tmpfs { path, size, mode: tmpfs.mode });
+        }
+        for overlay in &filesystem.overlay {
+            let path = file.resolve(&overlay.path)?;
+            if !path.is_dir() {
+                let message = format!("invalid overlay directory: {path:?}");
+                return Err(file.error(Some(overlay.path.span()), message));
+            }
+            let changes =
+                overlay.changes.as_ref().map(|changes| file.resolve(changes)).transpose()?;
+            self.exceptions.push(Exception::Overlay { path, changes });
+        }
+        for bind in &filesystem.bind {
+            let source = file.resolve(&bind.source)?;
+            if !source.exists() {
+                let message = format!("invalid path: {source:?}");
+                return Err(file.error(Some(bind.source.span()), message));
+            }
+            let target = file.sandbox_path(&bind.target)?;
+            self.exceptions.push(Exception::Bind { source, target, access: bind.access });
+        }
+        for file_entry in &filesystem.files {
+            let path = file.sandbox_path(&file_entry.path)?;
+            let contents = file_entry.contents.clone().into_bytes();
+            self.exceptions.push(Exception::File { path, contents, mode: file_entry.mode });
+        }
+        if filesystem.read_all {
+            self.exceptions.push(Exception::ReadAll);
+        }
+        if filesystem.full_procfs {
+            self.exceptions.push(Exception::FullProcfs);
+        }
+
+        let environment = policy.environment;
+        for variable in environment.variables {
+            self.exceptions.push(Exception::Environment(variable));
+        }
+        if environment.all {
+            self.exceptions.push(Exception::FullEnvironment);
+        }
+
+        let network = policy.network;
+        if network.enabled {
+            self.exceptions.push(Exception::Networking);
+        }
+        if network.loopback {
+            self.exceptions.push(Exception::LoopbackNetworking);
+        }
+        for host in &network.hosts {
+            let (host, port) = parse_host(host.get_ref()).ok_or_else(|| {
+                file.error(Some(host.span()), "invalid host, expected `host:port`")
+            })?;
+            self.exceptions.push(Exception::NetworkHost { host, port });
+        }
+
+        if policy.ipc.host {
+            self.exceptions.push(Exception::HostIpc);
+        }
+
+        let home = policy.home;
+        if home.is_virtual || home.persistent.is_some() {
+            let persistent = home.persistent.as_ref().map(|path| file.resolve(path)).transpose()?;
+            self.exceptions.push(Exception::VirtualHome { persistent });
+        }
+
+        Ok(())
+    }
+}
+
+/// Error in a policy file.
+#[derive(Debug)]
+pub struct PolicyError {
+    /// Path of the policy file containing the error.
+    pub path: PathBuf,
+    /// Line of the error, starting at 1.
+    pub line: Option<usize>,
+    /// Description of the error.
+    pub message: String,
+}
+
+impl PolicyError {
+    fn new(path: &Path, line: Option<usize>, message: impl Into<String>) -> Self {
+        Self { path: path.into(), line, message: message.into() }
+    }
+}
+
+impl StdError for PolicyError {}
+
+impl Display for PolicyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}: {}", self.path.display(), self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Policy file currently being loaded.
+struct PolicyFile<'a> {
+    path: &'a Path,
+    source: &'a str,
+}
+
+impl PolicyFile<'_> {
+    /// Create an error at a location of the file.
+    fn error(&self, span: Option<Range<usize>>, message: impl Into<String>) -> Error {
+        let line = span.map(|span| self.source[..span.start].matches('\n').count() + 1);
+        PolicyError::new(self.path, line, message).into()
+    }
+
+    /// Expand and resolve a path relative to this file.
+    fn resolve(&self, path: &Spanned<String>) -> Result<PathBuf> {
+        let expanded = expand(path.get_ref()).map_err(|err| self.error(Some(path.span()), err))?;
+
+        let directory = self.path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(directory.join(expanded))
+    }
+
+    /// Expand an absolute path inside the sandbox.
+    ///
+    /// Unlike [`Self::resolve`], relative paths are rejected instead of being
+    /// resolved relative to this file, since they do not refer to the host.
+    fn sandbox_path(&self, path: &Spanned<String>) -> Result<PathBuf> {
+        let expanded = expand(path.get_ref()).map_err(|err| self.error(Some(path.span()), err))?;
+
+        let expanded = PathBuf::from(expanded);
+        if !expanded.is_absolute() {
+            let message = format!("sandbox path must be absolute: {expanded:?}");
+            return Err(self.error(Some(path.span()), message));
+        }
+
+        Ok(expanded)
+    }
+}
+
+/// Expand `~` and environment variables in a path.
+fn expand(path: &str) -> std::result::Result<String, String> {
+    let mut expanded = String::new();
+
+    // Replace `~` with the home directory.
+    let mut rest = path;
+    if path == "~" || path.starts_with("~/") {
+        expanded.push_str(&env_var("HOME")?);
+        rest = &path[1..];
+    }
+
+    while let Some(index) = rest.find('$') {
+        expanded.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+
+        // Parse both `$VAR` and `${VAR}`.
+        let name = if let Some(braced) = rest.strip_prefix('{') {
+            let end = braced.find('}').ok_or("unterminated variable, expected `}`")?;
+            rest = &braced[end + 1..];
+            &braced[..end]
+        } else {
+            let end =
+                rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            let name = &rest[..end];
+            rest = &rest[end..];
+            name
+        };
+
+        if name.is_empty() {
+            return Err("missing variable name after `$`".into());
+        }
+        expanded.push_str(&env_var(name)?);
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// Get the value of an environment variable used in a policy.
+fn env_var(name: &str) -> std::result::Result<String, String> {
+    env::var(name).map_err(|_| format!("undefined environment variable `{name}`"))
+}
+
+/// Parse a `host:port` network destination.
+fn parse_host(host: &str) -> Option<(String, u16)> {
+    let (host, port) = host.rsplit_once(':')?;
+    let host = host.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(host);
+    let port = port.parse().ok()?;
+
+    (!host.is_empty()).then(|| (host.into(), port))
+}
+
+/// Policy file format.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPolicy {
+    version: Spanned<u32>,
+    #[serde(default)]
+    include: Vec<Spanned<String>>,
+    #[serde(default)]
+    filesystem: RawFilesystem,
+    #[serde(default)]
+    environment: RawEnvironment,
+    #[serde(default)]
+    network: RawNetwork,
+    #[serde(default)]
+    ipc: RawIpc,
+    #[serde(default)]
+    home: RawHome,
+}
+
+/// Filesystem section of a policy file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+struct RawFilesystem {
+    read: Vec<Spanned<String>>,
+    write: Vec<Spanned<String>>,
+    execute: Vec<Spanned<String>>,
+    deny: Vec<Spanned<String>>,
+    devices: Vec<Spanned<String>>,
+    tmpfs: Vec<RawTmpfs>,
+    overlay: Vec<RawOverlay>,
+    bind: Vec<RawBind>,
+    files: Vec<RawFile>,
+    read_all: bool,
+    full_procfs: bool,
+}
+
+/// Tmpfs mount in the filesystem section of a policy file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTmpfs {
+    path: Spanned<String>,
+    size: Spanned<u64>,
+    mode: u32,
+}
+
+/// Overlay mount in the filesystem section of a policy file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOverlay {
+    path: Spanned<String>,
+    changes: Option<Spanned<String>>,
+}
+
+/// Bind mount in the filesystem section of a policy file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBind {
+    source: Spanned<String>,
+    target: Spanned<String>,
+    access: BindAccess,
+}
+
+/// File in the filesystem section of a policy file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFile {
+    path: Spanned<String>,
+    contents: String,
+    mode: u32,
+}
+
+/// Environment section of a policy file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+struct RawEnvironment {
+    variables: Vec<String>,
+    all: bool,
+}
+
+/// Network section of a policy file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+struct RawNetwork {
+    enabled: bool,
+    loopback: bool,
+    hosts: Vec<Spanned<String>>,
+}
+
+/// IPC section of a policy file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+struct RawIpc {
+    host: bool,
+}
+
+/// Home section of a policy file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+struct RawHome {
+    #[serde(rename = "virtual")]
+    is_virtual: bool,
+    persistent: Option<Spanned<String>>,
+}
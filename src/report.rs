@@ -0,0 +1,192 @@
+//! Sandbox activation reports.
+
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+#[cfg(target_os = "macos")]
+use crate::error::{Error, Result};
+
+/// Sandboxing layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    /// Mount namespace isolating filesystem access.
+    #[cfg(target_os = "linux")]
+    MountNamespace,
+
+    /// Network namespace isolating network access.
+    #[cfg(target_os = "linux")]
+    NetworkNamespace,
+
+    /// PID namespace isolating processes.
+    #[cfg(target_os = "linux")]
+    PidNamespace,
+
+    /// IPC namespace isolating System V IPC and POSIX message queues.
+    #[cfg(target_os = "linux")]
+    IpcNamespace,
+
+    /// UTS namespace with a custom hostname.
+    #[cfg(target_os = "linux")]
+    UtsNamespace,
+
+    /// Time namespace with offset clocks.
+    #[cfg(target_os = "linux")]
+    TimeNamespace,
+
+    /// Landlock filesystem restrictions.
+    #[cfg(target_os = "linux")]
+    Landlock,
+
+    /// Landlock TCP restrictions, used when no network namespace is available.
+    ///
+    /// Landlock cannot restrict other protocols, so all other internet sockets
+    /// are blocked by the [`Layer::Seccomp`] filter instead.
+    #[cfg(target_os = "linux")]
+    LandlockNetwork,
+
+    /// `PR_SET_NO_NEW_PRIVS` flag preventing privilege escalation.
+    #[cfg(target_os = "linux")]
+    NoNewPrivileges,
+
+    /// Seccomp system call filter.
+    #[cfg(target_os = "linux")]
+    Seccomp,
+
+    /// Seatbelt sandbox profile.
+    #[cfg(target_os = "macos")]
+    Seatbelt,
+}
+
+impl Display for Layer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(target_os = "linux")]
+            Self::MountNamespace => write!(f, "mount namespace"),
+            #[cfg(target_os = "linux")]
+            Self::NetworkNamespace => write!(f, "network namespace"),
+            #[cfg(target_os = "linux")]
+            Self::PidNamespace => write!(f, "PID namespace"),
+            #[cfg(target_os = "linux")]
+            Self::IpcNamespace => write!(f, "IPC namespace"),
+            #[cfg(target_os = "linux")]
+            Self::UtsNamespace => write!(f, "UTS namespace"),
+            #[cfg(target_os = "linux")]
+            Self::TimeNamespace => write!(f, "time namespace"),
+            #[cfg(target_os = "linux")]
+            Self::Landlock => write!(f, "landlock"),
+            #[cfg(target_os = "linux")]
+            Self::LandlockNetwork => write!(f, "landlock network restrictions"),
+            #[cfg(target_os = "linux")]
+            Self::NoNewPrivileges => write!(f, "no_new_privs"),
+            #[cfg(target_os = "linux")]
+            Self::Seccomp => write!(f, "seccomp"),
+            #[cfg(target_os = "macos")]
+            Self::Seatbelt => write!(f, "seatbelt"),
+        }
+    }
+}
+
+/// Sandboxing layer which could not be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLayer {
+    /// The skipped layer.
+    pub layer: Layer,
+    /// Reason why the layer could not be applied.
+    pub reason: String,
+}
+
+impl Display for SkippedLayer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.layer, self.reason)
+    }
+}
+
+/// Filesystem exception which could not be applied.
+///
+/// Paths are resolved when the sandbox is activated, so exceptions are dropped
+/// if their path was removed after the exception was added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DroppedException {
+    /// Path of the exception.
+    pub path: PathBuf,
+    /// Reason why the path could not be resolved.
+    pub reason: String,
+}
+
+impl Display for DroppedException {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.path, self.reason)
+    }
+}
+
+/// Sandboxing layers applied by [`crate::Sandbox::lock`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+    applied: Vec<Layer>,
+    skipped: Vec<SkippedLayer>,
+    dropped: Vec<DroppedException>,
+}
+
+impl Report {
+    /// Get all layers which were applied, in the order of activation.
+    pub fn applied(&self) -> &[Layer] {
+        &self.applied
+    }
+
+    /// Get all layers which could not be applied.
+    pub fn skipped(&self) -> &[SkippedLayer] {
+        &self.skipped
+    }
+
+    /// Get all exceptions which could not be applied.
+    pub fn dropped(&self) -> &[DroppedException] {
+        &self.dropped
+    }
+
+    /// Check if all layers and exceptions were applied.
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty() && self.dropped.is_empty()
+    }
+
+    /// Create a report for a sandbox which had to drop some exceptions.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn with_dropped(dropped: Vec<DroppedException>) -> Self {
+        Self { dropped, ..Self::default() }
+    }
+
+    /// Record a successfully applied layer.
+    pub(crate) fn apply(&mut self, layer: Layer) {
+        self.applied.push(layer);
+    }
+
+    /// Record a layer which could not be applied.
+    pub(crate) fn skip(&mut self, layer: Layer, reason: impl Into<String>) {
+        self.skipped.push(SkippedLayer { layer, reason: reason.into() });
+    }
+
+    /// Record the result of applying a layer.
+    ///
+    /// Errors are recorded as skipped layer if `skippable` returns `true` for
+    /// them, otherwise they are returned.
+    ///
+    /// Returns `true` if the layer was applied.
+    #[cfg(target_os = "macos")]
+    pub(crate) fn record(
+        &mut self,
+        layer: Layer,
+        result: Result<()>,
+        skippable: impl Fn(&Error) -> bool,
+    ) -> Result<bool> {
+        match result {
+            Ok(()) => {
+                self.apply(layer);
+                Ok(true)
+            },
+            Err(error) if skippable(&error) => {
+                self.skip(layer, error.to_string());
+                Ok(false)
+            },
+            Err(error) => Err(error),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+#[cfg(target_os = "linux")]
+use std::net::{TcpListener, TcpStream};
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{env, fs};
+
+#[cfg(target_os = "linux")]
+use birdcage::audit::{Access, Violation};
+#[cfg(target_os = "linux")]
+use birdcage::{Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::{NamedTempFile, TempDir};
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Run the sandboxed part of the test.
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("sandboxee") {
+        // Violations are not blocked.
+        let private_path = args.next().unwrap();
+        let content = fs::read_to_string(&private_path).unwrap();
+        assert_eq!(content, FILE_CONTENT);
+        fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600)).unwrap();
+        fs::write(args.next().unwrap(), FILE_CONTENT).unwrap();
+        TcpStream::connect(args.next().unwrap()).unwrap();
+        let result = unsafe { libc::syscall(libc::SYS_unshare, 0) };
+        assert_eq!(result, 0);
+
+        // Allowed access is not reported.
+        fs::read_to_string(args.next().unwrap()).unwrap();
+        TcpStream::connect(args.next().unwrap()).unwrap();
+        return;
+    }
+
+    // Setup our test files.
+    let private_file = NamedTempFile::new().unwrap();
+    fs::write(&private_file, FILE_CONTENT.as_bytes()).unwrap();
+    let public_file = NamedTempFile::new().unwrap();
+    let tempdir = TempDir::new().unwrap();
+    let new_file = tempdir.path().join("new");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let allowed_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let allowed_address = allowed_listener.local_addr().unwrap();
+
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::ExecuteAndRead("/etc".into())).unwrap();
+    birdcage.add_exception(Exception::Read(public_file.path().into())).unwrap();
+    birdcage.add_exception(Exception::Read(tempdir.path().into())).unwrap();
+    let host = allowed_address.ip().to_string();
+    let port = allowed_address.port();
+    birdcage.add_exception(Exception::NetworkHost { host, port }).unwrap();
+    let events = birdcage.audit();
+
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg("sandboxee").arg(private_file.path()).arg(&new_file);
+    command.arg(address.to_string()).arg(public_file.path());
+    command.arg(allowed_address.to_string());
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // All violations were reported.
+    let violations: Vec<_> = events.iter().collect();
+    let private_path = private_file.path().canonicalize().unwrap();
+    let public_path = public_file.path().canonicalize().unwrap();
+    let new_path = tempdir.path().canonicalize().unwrap().join("new");
+    assert!(violations.iter().any(|violation| matches!(
+        violation,
+        Violation::Path { path, access: Access::Read, .. } if path == &private_path
+    )));
+    assert!(violations.iter().any(|violation| matches!(
+        violation,
+        Violation::Path { path, access: Access::Write, .. } if path == &new_path
+    )));
+    assert!(violations.iter().any(|violation| matches!(
+        violation,
+        Violation::Path { path, access: Access::Write, .. } if path == &private_path
+    )));
+    assert!(violations.iter().any(|violation| matches!(
+        violation,
+        Violation::Network { address: violation_address, .. } if violation_address == &address
+    )));
+    assert!(violations.iter().any(|violation| matches!(
+        violation,
+        Violation::Syscall { syscall, .. } if *syscall == libc::SYS_unshare
+    )));
+
+    // Allowed access was not reported.
+    assert!(!violations.iter().any(|violation| matches!(
+        violation,
+        Violation::Path { path, .. } if path == &public_path || path.starts_with("/usr/lib")
+    )));
+    assert!(!violations.iter().any(|violation| matches!(
+        violation,
+        Violation::Network { address, .. } if address == &allowed_address
+    )));
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
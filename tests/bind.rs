@@ -0,0 +1,101 @@
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "linux")]
+use std::process::{self, Command};
+#[cfg(target_os = "linux")]
+use std::{env, fs};
+
+#[cfg(target_os = "linux")]
+use birdcage::{BindAccess, Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+const FILE_CONTENT: &str = "expected content";
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Check the bind mounts from inside the sandbox.
+    let mut args = env::args().skip(1);
+    if let Some(root) = args.next() {
+        let target = PathBuf::from(args.next().unwrap());
+        sandboxee(Path::new(&root), &target);
+        return;
+    }
+
+    // Setup our test files.
+    let tempdir = TempDir::new().unwrap();
+    let root = tempdir.path();
+    fs::create_dir(root.join("scratch")).unwrap();
+    fs::write(root.join("scratch/file"), FILE_CONTENT).unwrap();
+    fs::write(root.join("config"), FILE_CONTENT).unwrap();
+    let target = PathBuf::from(format!("/birdcage-bind-{}", process::id()));
+
+    // Sources must exist and targets must be absolute.
+    let access = BindAccess::Read;
+    let missing = Exception::Bind { source: root.join("missing"), target: target.clone(), access };
+    assert!(Birdcage::new().add_exception(missing).is_err());
+    let relative = Exception::Bind { source: root.into(), target: "work".into(), access };
+    assert!(Birdcage::new().add_exception(relative).is_err());
+
+    // Missing targets cannot be created beneath other exceptions.
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::Read(root.into())).unwrap();
+    let nested = Exception::Bind { source: root.join("config"), target: root.join("new"), access };
+    birdcage.add_exception(nested).unwrap();
+    assert!(birdcage.spawn(sandboxee_command(root, &target)).is_err());
+    assert!(!root.join("new").exists());
+
+    let mut birdcage = common::sandbox();
+    birdcage
+        .add_exception(Exception::Bind {
+            source: root.join("scratch"),
+            target: target.join("work"),
+            access: BindAccess::WriteAndRead,
+        })
+        .unwrap();
+    birdcage
+        .add_exception(Exception::Bind {
+            source: root.join("config"),
+            target: target.join("etc/config"),
+            access: BindAccess::Read,
+        })
+        .unwrap();
+    let status = birdcage.spawn(sandboxee_command(root, &target)).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // Writes are applied to the source, without creating the target on the host.
+    assert_eq!(fs::read_to_string(root.join("scratch/new")).unwrap(), FILE_CONTENT);
+    assert!(!target.exists());
+}
+
+/// Create the command running this test inside the sandbox.
+#[cfg(target_os = "linux")]
+fn sandboxee_command(root: &Path, target: &Path) -> Command {
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.args([root, target]);
+    command
+}
+
+/// Assertions run inside the sandbox.
+#[cfg(target_os = "linux")]
+fn sandboxee(root: &Path, target: &Path) {
+    // Sources are only accessible at their target.
+    assert!(!root.exists());
+
+    // Directories are writable with write access.
+    let work = target.join("work");
+    assert_eq!(fs::read_to_string(work.join("file")).unwrap(), FILE_CONTENT);
+    fs::write(work.join("new"), FILE_CONTENT).unwrap();
+
+    // Files are bound with missing parents created.
+    let config = target.join("etc/config");
+    assert_eq!(fs::read_to_string(&config).unwrap(), FILE_CONTENT);
+    assert!(fs::write(&config, FILE_CONTENT).is_err());
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
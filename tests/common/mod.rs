@@ -0,0 +1,23 @@
+//! Helpers shared by the integration tests.
+
+use std::env;
+use std::path::Path;
+
+use birdcage::{Birdcage, Exception, Sandbox};
+
+/// Library directories required for executing the test binary.
+const LIBRARIES: &[&str] = &["/usr/lib", "/lib64", "/lib"];
+
+/// Create a sandbox which allows executing the current test binary.
+///
+/// This is used to run the sandboxed part of a test in a spawned child.
+pub fn sandbox() -> Birdcage {
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::ExecuteAndRead(env::current_exe().unwrap())).unwrap();
+    for path in LIBRARIES {
+        if Path::new(path).exists() {
+            birdcage.add_exception(Exception::ExecuteAndRead(path.into())).unwrap();
+        }
+    }
+    birdcage
+}
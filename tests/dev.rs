@@ -0,0 +1,111 @@
+#[cfg(target_os = "linux")]
+use std::ffi::CString;
+#[cfg(target_os = "linux")]
+use std::fs::{self, File, OpenOptions};
+#[cfg(target_os = "linux")]
+use std::io::Read;
+#[cfg(target_os = "linux")]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{env, str};
+
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+/// Entries expected in the sandbox's `/dev`.
+#[cfg(target_os = "linux")]
+const DEV_ENTRIES: &[&str] = &[
+    "null", "zero", "full", "random", "urandom", "tty", "pts", "ptmx", "fd", "stdin", "stdout",
+    "stderr", "shm", "mqueue",
+];
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Check the devices from inside the sandbox.
+    let mut args = env::args().skip(1);
+    if let Some(root) = args.next() {
+        let has_nodes = args.next().unwrap() == "true";
+        sandboxee(Path::new(&root), has_nodes);
+        return;
+    }
+
+    // Setup our test files, creating device nodes if we are privileged.
+    let tempdir = TempDir::new().unwrap();
+    let root = tempdir.path();
+    fs::write(root.join("file"), "").unwrap();
+    let has_nodes = mknod(&root.join("null"), 1, 3) && mknod(&root.join("zero"), 1, 5);
+
+    // Device exceptions require a device node.
+    assert!(Birdcage::new().add_exception(Exception::Device(root.join("file"))).is_err());
+
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::WriteAndRead(root.into())).unwrap();
+    if has_nodes {
+        birdcage.add_exception(Exception::Device(root.join("zero"))).unwrap();
+    }
+
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg(root).arg(has_nodes.to_string());
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+}
+
+/// Assertions run inside the sandbox.
+#[cfg(target_os = "linux")]
+fn sandboxee(root: &Path, has_nodes: bool) {
+    // Only the minimal set of devices is available.
+    for entry in fs::read_dir("/dev").unwrap() {
+        let name = entry.unwrap().file_name();
+        assert!(DEV_ENTRIES.iter().any(|entry| name == *entry), "unexpected device {name:?}");
+    }
+
+    // Default devices are usable.
+    fs::write("/dev/null", "blub").unwrap();
+    assert_eq!(read_bytes("/dev/zero"), [0; 16]);
+    read_bytes("/dev/urandom");
+    assert!(fs::write("/dev/full", "blub").is_err());
+
+    // Standard streams are linked to procfs.
+    assert_eq!(fs::read_link("/dev/fd").unwrap(), Path::new("/proc/self/fd"));
+    assert!(Path::new("/dev/stdout").exists());
+
+    // Pseudo-terminals are allocated in a private devpts.
+    let ptmx = OpenOptions::new().read(true).write(true).open("/dev/ptmx").unwrap();
+    drop(ptmx);
+    let pts: Vec<_> = fs::read_dir("/dev/pts").unwrap().map(|entry| entry.unwrap()).collect();
+    assert!(pts.iter().all(|entry| entry.file_name() == "ptmx" || entry.file_name() == "0"));
+
+    // Devices can only be opened with a device exception.
+    if has_nodes {
+        assert!(File::open(root.join("null")).is_err());
+        assert_eq!(read_bytes(root.join("zero")), [0; 16]);
+    }
+}
+
+/// Read 16 bytes from a device.
+#[cfg(target_os = "linux")]
+fn read_bytes(path: impl AsRef<Path>) -> [u8; 16] {
+    let mut buffer = [1; 16];
+    File::open(path).unwrap().read_exact(&mut buffer).unwrap();
+    buffer
+}
+
+/// Create a character device node.
+#[cfg(target_os = "linux")]
+fn mknod(path: &Path, major: u32, minor: u32) -> bool {
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let dev = libc::makedev(major, minor);
+    unsafe { libc::mknod(path.as_ptr(), libc::S_IFCHR | 0o666, dev) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
@@ -6,8 +6,8 @@ use birdcage::{Birdcage, Exception, Sandbox};
 
 fn main() {
     // Create symlinked executable dir.
-    let tempdir = tempfile::tempdir().unwrap().into_path();
-    let symlink_dir = tempdir.join("bin");
+    let tempdir = tempfile::tempdir().unwrap();
+    let symlink_dir = tempdir.path().join("bin");
     let symlink_dir_exec = symlink_dir.join("true");
     unixfs::symlink("/usr/bin", &symlink_dir).unwrap();
 
@@ -7,8 +7,8 @@ use birdcage::{Birdcage, Exception, Sandbox};
 
 fn main() {
     // Create symlinked executable.
-    let tempdir = tempfile::tempdir().unwrap().into_path();
-    let exec_dir = tempdir.join("bin");
+    let tempdir = tempfile::tempdir().unwrap();
+    let exec_dir = tempdir.path().join("bin");
     fs::create_dir(&exec_dir).unwrap();
     let symlink_exec = exec_dir.join("true");
     unixfs::symlink("/usr/bin/true", &symlink_exec).unwrap();
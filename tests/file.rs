@@ -0,0 +1,127 @@
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::{self as unix_fs, PermissionsExt};
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "linux")]
+use std::process::{self, Command};
+#[cfg(target_os = "linux")]
+use std::{env, fs};
+
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+const HOSTS: &str = "127.0.0.1 localhost\n";
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Check the files from inside the sandbox.
+    let mut args = env::args().skip(1);
+    if let Some(nested) = args.next() {
+        if nested == "symlink" {
+            let link = args.next().unwrap();
+            assert_eq!(fs::read_to_string(link).unwrap(), HOSTS);
+            return;
+        }
+
+        sandboxee(Path::new(&nested));
+        return;
+    }
+
+    let host_passwd = fs::read("/etc/passwd").unwrap();
+    let nested = PathBuf::from(format!("/birdcage-file-{}/nested", process::id()));
+
+    // File paths must be absolute.
+    let relative = Exception::File { path: "hosts".into(), contents: Vec::new(), mode: 0o644 };
+    assert!(Birdcage::new().add_exception(relative).is_err());
+
+    // Files are created without access to the host's `/etc`.
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::passwd()).unwrap();
+    birdcage.add_exception(Exception::group()).unwrap();
+    let hosts = Exception::File { path: "/etc/hosts".into(), contents: HOSTS.into(), mode: 0o600 };
+    birdcage.add_exception(hosts).unwrap();
+    let nested_file = Exception::File { path: nested.clone(), contents: Vec::new(), mode: 0o644 };
+    birdcage.add_exception(nested_file).unwrap();
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg(&nested);
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // Files are mounted on top of existing host files.
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::ReadAll).unwrap();
+    birdcage.add_exception(Exception::passwd()).unwrap();
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg("/");
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // Symlinks are covered by the file, instead of being followed.
+    let tempdir = TempDir::new().unwrap();
+    let outside = TempDir::new().unwrap();
+    let outside_file = outside.path().join("file");
+    fs::write(&outside_file, "outside").unwrap();
+    let link = tempdir.path().join("link");
+    unix_fs::symlink(&outside_file, &link).unwrap();
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::Read(tempdir.path().into())).unwrap();
+    let file = Exception::File { path: link.clone(), contents: HOSTS.into(), mode: 0o644 };
+    birdcage.add_exception(file).unwrap();
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg("symlink").arg(&link);
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // Host files are not modified.
+    assert_eq!(fs::read("/etc/passwd").unwrap(), host_passwd);
+    assert_eq!(fs::read_to_string(&outside_file).unwrap(), "outside");
+    assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    assert!(!nested.exists());
+}
+
+/// Assertions run inside the sandbox.
+#[cfg(target_os = "linux")]
+fn sandboxee(nested: &Path) {
+    // The sandboxed user is the only user.
+    let uid = unsafe { libc::geteuid() };
+    let passwd = fs::read_to_string("/etc/passwd").unwrap();
+    let users: Vec<_> = passwd.lines().map(|line| line.split(':').nth(2).unwrap()).collect();
+    assert_eq!(users[0], uid.to_string());
+    assert!(users[1..].iter().all(|user| *user == "65534"));
+
+    // Changes are discarded.
+    assert!(fs::write("/etc/passwd", "").is_ok());
+
+    // Only the read-all sandbox is checked for host files.
+    if nested == Path::new("/") {
+        assert!(Path::new("/etc/os-release").exists() || Path::new("/etc/hostname").exists());
+        return;
+    }
+
+    // The sandboxed group is the only group.
+    let gid = unsafe { libc::getegid() };
+    let group = fs::read_to_string("/etc/group").unwrap();
+    assert!(group.starts_with(&format!("{}:x:{gid}:", group.split(':').next().unwrap())));
+
+    // Files have the requested content and mode.
+    assert_eq!(fs::read_to_string("/etc/hosts").unwrap(), HOSTS);
+    let mode = fs::metadata("/etc/hosts").unwrap().permissions().mode();
+    assert_eq!(mode & 0o7777, 0o600);
+
+    // Missing parents are created.
+    assert!(nested.is_file());
+
+    // Host files are not accessible.
+    for file in ["os-release", "hostname", "shadow", "fstab"] {
+        assert!(!Path::new("/etc").join(file).exists());
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
@@ -0,0 +1,51 @@
+use std::fs;
+
+use birdcage::{Birdcage, Exception, Sandbox};
+
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Setup our test tree.
+    let tempdir = tempfile::tempdir().unwrap();
+    let root = tempdir.path();
+    let public_file = root.join("public");
+    let denied_file = root.join("denied");
+    let denied_dir = root.join("private");
+    let private_file = denied_dir.join("secret");
+    let allowed_dir = denied_dir.join("shared");
+    let allowed_file = allowed_dir.join("public");
+    fs::create_dir_all(&allowed_dir).unwrap();
+    for path in [&public_file, &denied_file, &private_file, &allowed_file] {
+        fs::write(path, FILE_CONTENT.as_bytes()).unwrap();
+    }
+
+    // Activate our sandbox.
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::WriteAndRead(root.into())).unwrap();
+    birdcage.add_exception(Exception::Deny(denied_file.clone())).unwrap();
+    birdcage.add_exception(Exception::Deny(denied_dir.clone())).unwrap();
+    birdcage.add_exception(Exception::Read(allowed_dir.clone())).unwrap();
+    birdcage.lock().unwrap();
+
+    // Access to the allowed parent is not affected.
+    let content = fs::read_to_string(&public_file).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+
+    // Access to the denied file is prohibited.
+    let result = fs::read_to_string(&denied_file);
+    assert!(result.is_err());
+    let result = fs::write(&denied_file, b"x");
+    assert!(result.is_err());
+
+    // Access to the denied directory is prohibited.
+    let result = fs::read_to_string(&private_file);
+    assert!(result.is_err());
+    let result = fs::write(denied_dir.join("new"), b"x");
+    assert!(result.is_err());
+
+    // Access to the allowed directory inside the denied one is allowed.
+    let content = fs::read_to_string(&allowed_file).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+    let result = fs::write(&allowed_file, b"x");
+    assert!(result.is_err());
+}
@@ -0,0 +1,58 @@
+#[cfg(target_os = "linux")]
+use std::fs;
+
+#[cfg(target_os = "linux")]
+use birdcage::error::Error;
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Setup our test files.
+    let tempdir = TempDir::new().unwrap();
+    let public_path = tempdir.path().join("public");
+    fs::write(&public_path, FILE_CONTENT).unwrap();
+    let removed_path = tempdir.path().join("removed");
+    fs::write(&removed_path, FILE_CONTENT).unwrap();
+
+    let sandbox = || {
+        let mut birdcage = Birdcage::new();
+        birdcage.add_exception(Exception::Read(public_path.clone())).unwrap();
+        birdcage.add_exception(Exception::WriteAndRead(removed_path.clone())).unwrap();
+        birdcage
+    };
+    let mut required = sandbox();
+    required.require_exceptions();
+    let birdcage = sandbox();
+
+    // Remove the path after its exception was added.
+    fs::remove_file(&removed_path).unwrap();
+
+    // Required exceptions fail before activating the sandbox.
+    match required.lock() {
+        Err(Error::DroppedExceptions(dropped)) => {
+            assert_eq!(dropped.len(), 1);
+            assert_eq!(dropped[0].path, removed_path);
+        },
+        result => panic!("unexpected result: {result:?}"),
+    }
+
+    // Dropped exceptions are reported.
+    let report = birdcage.lock().unwrap();
+    assert!(!report.is_complete());
+    assert_eq!(report.dropped().len(), 1);
+    assert_eq!(report.dropped()[0].path, removed_path);
+
+    // Remaining exceptions are still applied.
+    let content = fs::read_to_string(&public_path).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+    let result = fs::write(&removed_path, FILE_CONTENT);
+    assert!(result.is_err());
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
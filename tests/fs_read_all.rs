@@ -0,0 +1,44 @@
+use std::fs;
+use std::process::Command;
+
+use birdcage::{Birdcage, Exception, Sandbox};
+use tempfile::NamedTempFile;
+
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Setup our test files.
+    let private_path = NamedTempFile::new().unwrap();
+    fs::write(&private_path, FILE_CONTENT.as_bytes()).unwrap();
+    let tempdir = tempfile::tempdir().unwrap();
+    let public_path = tempdir.path().join("public");
+
+    // Activate our sandbox.
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::ReadAll).unwrap();
+    birdcage.add_exception(Exception::WriteAndRead(tempdir.path().into())).unwrap();
+    birdcage.lock().unwrap();
+
+    // Reading arbitrary files is allowed.
+    let content = fs::read_to_string(&private_path).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+
+    // Writing is only allowed for explicit exceptions.
+    let result = fs::write(&private_path, b"x");
+    assert!(result.is_err());
+    fs::write(&public_path, FILE_CONTENT.as_bytes()).unwrap();
+
+    // Execution is prohibited.
+    let cmd = Command::new("/usr/bin/true").status();
+    assert!(cmd.is_err());
+
+    // Pseudo-filesystems are hidden, except for the minimal `/dev`.
+    #[cfg(target_os = "linux")]
+    {
+        assert!(fs::read_dir("/sys").map_or(true, |mut entries| entries.next().is_none()));
+        assert!(fs::read_dir("/dev/pts")
+            .unwrap()
+            .all(|entry| entry.unwrap().file_name() == "ptmx"));
+        assert!(fs::File::open("/dev/null").is_ok());
+    }
+}
@@ -0,0 +1,44 @@
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs as unixfs;
+
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Setup our test files, with a symlink for each of them.
+    let tempdir = TempDir::new().unwrap();
+    let writable = tempdir.path().join("writable");
+    let writable_link = tempdir.path().join("writable_link");
+    let denied = tempdir.path().join("denied");
+    let denied_link = tempdir.path().join("denied_link");
+    for (path, link) in [(&writable, &writable_link), (&denied, &denied_link)] {
+        fs::write(path, FILE_CONTENT.as_bytes()).unwrap();
+        unixfs::symlink(path, link).unwrap();
+    }
+
+    // Activate our sandbox.
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::Read(writable.clone())).unwrap();
+    birdcage.add_exception(Exception::WriteAndRead(writable_link.clone())).unwrap();
+    birdcage.add_exception(Exception::WriteAndRead(denied.clone())).unwrap();
+    birdcage.add_exception(Exception::Deny(denied_link.clone())).unwrap();
+    birdcage.lock().unwrap();
+
+    // Permissions of a symlink and its target are combined.
+    fs::write(&writable, b"x").unwrap();
+    assert_eq!(fs::read_to_string(&writable_link).unwrap(), "x");
+
+    // Denying either the symlink or its target denies both.
+    assert!(fs::read_to_string(&denied).is_err());
+    assert!(fs::read_to_string(&denied_link).is_err());
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
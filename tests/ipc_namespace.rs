@@ -0,0 +1,92 @@
+#[cfg(target_os = "linux")]
+use std::ffi::CString;
+#[cfg(target_os = "linux")]
+use std::os::raw::c_int;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{env, ptr};
+
+#[cfg(target_os = "linux")]
+use birdcage::report::Layer;
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+const SHM_KEY: libc::key_t = 0xb1dc;
+#[cfg(target_os = "linux")]
+const HOST_QUEUE: &str = "/birdcage-host";
+#[cfg(target_os = "linux")]
+const SANDBOX_QUEUE: &str = "/birdcage-sandbox";
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Check host IPC access from inside the sandbox.
+    match env::args().nth(1).as_deref() {
+        Some("host") => {
+            assert!(unsafe { libc::shmget(SHM_KEY, 0, 0) } >= 0);
+            if Path::new("/dev/mqueue").is_dir() {
+                assert!(open_queue(HOST_QUEUE, libc::O_RDONLY) >= 0);
+            }
+            return;
+        },
+        Some("isolated") => {
+            assert!(unsafe { libc::shmget(SHM_KEY, 0, 0) } < 0);
+            assert!(open_queue(HOST_QUEUE, libc::O_RDONLY) < 0);
+            return;
+        },
+        _ => (),
+    }
+
+    // Setup host IPC objects.
+    let shm = unsafe { libc::shmget(SHM_KEY, 4096, libc::IPC_CREAT | 0o600) };
+    assert!(shm >= 0);
+    assert!(open_queue(HOST_QUEUE, libc::O_CREAT | libc::O_RDONLY) >= 0);
+
+    // Host IPC objects are only accessible with an exception.
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::HostIpc).unwrap();
+    if Path::new("/dev/mqueue").is_dir() {
+        birdcage.add_exception(Exception::WriteAndRead("/dev/mqueue".into())).unwrap();
+    }
+    let status = birdcage.spawn(sandboxee("host")).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    let status = common::sandbox().spawn(sandboxee("isolated")).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // Cleanup host IPC objects.
+    unsafe { libc::shmctl(shm, libc::IPC_RMID, ptr::null_mut()) };
+    let host_queue = CString::new(HOST_QUEUE).unwrap();
+    unsafe { libc::mq_unlink(host_queue.as_ptr()) };
+
+    // Message queues are listed in a private `/dev/mqueue`.
+    let report = Birdcage::new().lock().unwrap();
+    assert!(report.applied().contains(&Layer::IpcNamespace));
+    assert!(open_queue(SANDBOX_QUEUE, libc::O_CREAT | libc::O_RDONLY) >= 0);
+    assert!(Path::new("/dev/mqueue").join(&SANDBOX_QUEUE[1..]).exists());
+    assert!(!Path::new("/dev/mqueue").join(&HOST_QUEUE[1..]).exists());
+}
+
+/// Open a POSIX message queue.
+#[cfg(target_os = "linux")]
+fn open_queue(name: &str, flags: c_int) -> libc::mqd_t {
+    let name = CString::new(name).unwrap();
+    unsafe { libc::mq_open(name.as_ptr(), flags, 0o600, ptr::null::<libc::mq_attr>()) }
+}
+
+/// Create a command running this test in the specified mode.
+#[cfg(target_os = "linux")]
+fn sandboxee(mode: &str) -> Command {
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg(mode);
+    command
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
@@ -0,0 +1,95 @@
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::net::{TcpListener, TcpStream, UdpSocket};
+
+#[cfg(target_os = "linux")]
+use birdcage::error::Error;
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::NamedTempFile;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Setup our test files.
+    let private_path = NamedTempFile::new().unwrap();
+    fs::write(&private_path, FILE_CONTENT.as_bytes()).unwrap();
+    let public_path = NamedTempFile::new().unwrap();
+    fs::write(&public_path, FILE_CONTENT.as_bytes()).unwrap();
+
+    // Setup a local TCP server.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Force the sandbox to fall back to Landlock.
+    disable_user_namespaces();
+
+    // Loopback networking cannot be isolated by Landlock.
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::LoopbackNetworking).unwrap();
+    assert!(birdcage.lock().is_err());
+
+    // IPC cannot be isolated by Landlock.
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::Read(public_path.path().into())).unwrap();
+    let result = birdcage.lock();
+    assert!(matches!(&result, Err(Error::ActivationFailed(error)) if error.contains("IPC")));
+
+    // Activate our sandbox.
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::Read(public_path.path().into())).unwrap();
+    birdcage.add_exception(Exception::HostIpc).unwrap();
+    birdcage.lock().unwrap();
+
+    // Access to the public file is allowed.
+    let content = fs::read_to_string(&public_path).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+
+    // Writing to the public file is prohibited.
+    let result = fs::write(&public_path, b"x");
+    assert!(result.is_err());
+
+    // Access to the private file is prohibited.
+    let result = fs::read_to_string(&private_path);
+    assert!(result.is_err());
+
+    // TCP connections are prohibited.
+    let result = TcpStream::connect(addr);
+    assert!(result.is_err());
+
+    // UDP sockets cannot be restricted by Landlock, so they are prohibited.
+    let result = UdpSocket::bind("127.0.0.1:0");
+    assert!(result.is_err());
+
+    // Other internet socket types are prohibited too.
+    let fd = unsafe { libc::socket(libc::AF_INET6, libc::SOCK_RAW, libc::IPPROTO_ICMPV6) };
+    assert_eq!(fd, -1);
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, libc::IPPROTO_SCTP) };
+    assert_eq!(fd, -1);
+
+    // Unix domain sockets are still allowed.
+    let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    assert!(fd >= 0);
+}
+
+/// Prevent creation of new user namespaces.
+#[cfg(target_os = "linux")]
+fn disable_user_namespaces() {
+    let uid = unsafe { libc::geteuid() };
+    let gid = unsafe { libc::getegid() };
+
+    let result = unsafe { libc::unshare(libc::CLONE_NEWUSER) };
+    assert_eq!(result, 0);
+
+    fs::write("/proc/self/uid_map", format!("0 {uid} 1\n")).unwrap();
+    fs::write("/proc/self/setgroups", b"deny").unwrap();
+    fs::write("/proc/self/gid_map", format!("0 {gid} 1\n")).unwrap();
+
+    fs::write("/proc/sys/user/max_user_namespaces", b"0").unwrap();
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
@@ -0,0 +1,100 @@
+#[cfg(target_os = "linux")]
+use std::net::{TcpListener, TcpStream};
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{env, fs};
+
+#[cfg(target_os = "linux")]
+use birdcage::{learn, Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::{NamedTempFile, TempDir};
+
+#[cfg(target_os = "linux")]
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Run the sandboxed part of the test.
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("sandboxee") {
+        let content = fs::read_to_string(args.next().unwrap()).unwrap();
+        assert_eq!(content, FILE_CONTENT);
+        fs::write(args.next().unwrap(), FILE_CONTENT).unwrap();
+
+        // Temporary files are only written to a directory which was accessed.
+        let scratch = args.next().unwrap();
+        fs::read_dir(&scratch).unwrap();
+        let scratch_file = Path::new(&scratch).join("file");
+        fs::write(&scratch_file, FILE_CONTENT).unwrap();
+        fs::remove_file(scratch_file).unwrap();
+
+        // Writes beneath missing directories fail.
+        assert!(fs::write(args.next().unwrap(), FILE_CONTENT).is_err());
+
+        // The listener is only reachable without network isolation.
+        let _ = TcpStream::connect(args.next().unwrap());
+        return;
+    }
+
+    // Setup our test files.
+    let input_file = NamedTempFile::new().unwrap();
+    fs::write(&input_file, FILE_CONTENT.as_bytes()).unwrap();
+    let tempdir = TempDir::new().unwrap();
+    let output_file = tempdir.path().join("output");
+    let scratch = tempdir.path().join("scratch");
+    fs::create_dir(&scratch).unwrap();
+    let missing_file = tempdir.path().join("missing/file");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let command = || {
+        let mut command = Command::new(env::current_exe().unwrap());
+        command.arg("sandboxee").arg(input_file.path()).arg(&output_file);
+        command.arg(&scratch).arg(&missing_file);
+        command.arg(listener.local_addr().unwrap().to_string());
+        command
+    };
+
+    // Learn the required exceptions.
+    let (status, profile) = learn::learn(command()).unwrap();
+    assert!(status.success());
+    let exceptions = profile.exceptions();
+
+    assert!(exceptions.iter().any(|exception| matches!(
+        exception,
+        Exception::Read(path) if path == input_file.path()
+    )));
+    assert!(exceptions.iter().any(|exception| matches!(
+        exception,
+        Exception::WriteAndRead(path) if path == &output_file
+    )));
+    assert!(exceptions.iter().any(|exception| matches!(
+        exception,
+        Exception::WriteAndRead(path) if path == &scratch
+    )));
+    assert!(exceptions.iter().any(|exception| matches!(
+        exception,
+        Exception::ExecuteAndRead(path) if path == &env::current_exe().unwrap()
+    )));
+    assert!(exceptions.iter().any(|exception| matches!(exception, Exception::LoopbackNetworking)));
+    assert!(!exceptions.iter().any(|exception| matches!(exception, Exception::Networking)));
+
+    // Writes beneath missing directories are not widened to their ancestors.
+    assert!(!exceptions.iter().any(|exception| matches!(
+        exception,
+        Exception::WriteAndRead(path) if missing_file.starts_with(path)
+    )));
+    assert_eq!(profile.unresolved().collect::<Vec<_>>(), [missing_file.as_path()]);
+
+    // Learned exceptions are sufficient to run the command in the sandbox.
+    let mut birdcage = Birdcage::new();
+    for exception in exceptions {
+        birdcage.add_exception(exception).unwrap();
+    }
+    let status = birdcage.spawn(command()).unwrap().0.wait().unwrap();
+    assert!(status.success());
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
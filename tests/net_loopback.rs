@@ -0,0 +1,30 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+
+use birdcage::{Birdcage, Exception, Sandbox};
+
+fn main() {
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::LoopbackNetworking).unwrap();
+    birdcage.lock().unwrap();
+
+    // TCP over the loopback interface is allowed.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (mut server, _) = listener.accept().unwrap();
+    client.write_all(b"ping").unwrap();
+    let mut buffer = [0; 4];
+    server.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"ping");
+
+    // UDP over the loopback interface is allowed.
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.send_to(b"pong", receiver.local_addr().unwrap()).unwrap();
+    let (len, _) = receiver.recv_from(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"pong");
+
+    // Remote networking is prohibited.
+    let result = TcpStream::connect("8.8.8.8:443");
+    assert!(result.is_err());
+}
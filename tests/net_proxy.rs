@@ -0,0 +1,108 @@
+#[cfg(target_os = "linux")]
+use std::io::{Read, Write};
+#[cfg(target_os = "linux")]
+use std::net::{TcpListener, TcpStream};
+#[cfg(target_os = "linux")]
+use std::os::unix::net::UnixStream;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{env, thread};
+
+#[cfg(target_os = "linux")]
+use birdcage::{Exception, Sandbox};
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Run the sandboxed part of the test.
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("sandboxee") {
+        let allowed: u16 = args.next().unwrap().parse().unwrap();
+        let denied: u16 = args.next().unwrap().parse().unwrap();
+        sandboxee(allowed, denied);
+        return;
+    }
+
+    // Setup local TCP stand-ins for remote servers.
+    let allowed = echo_server();
+    let denied = echo_server();
+
+    let mut birdcage = common::sandbox();
+    let host = String::from("127.0.0.1");
+    birdcage.add_exception(Exception::NetworkHost { host, port: allowed }).unwrap();
+
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.args(["sandboxee", &allowed.to_string(), &denied.to_string()]);
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+}
+
+#[cfg(target_os = "linux")]
+fn sandboxee(allowed: u16, denied: u16) {
+    // Direct connections are prohibited.
+    let result = TcpStream::connect(("127.0.0.1", allowed));
+    assert!(result.is_err());
+
+    // Connections to the allowed destination are forwarded.
+    let mut stream = connect(allowed);
+    assert_eq!(read_response(&mut stream), "HTTP/1.1 200 Connection Established");
+    stream.write_all(b"ping").unwrap();
+    let mut buffer = [0; 4];
+    stream.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"ping");
+
+    // Connections to other destinations are rejected.
+    let mut stream = connect(denied);
+    assert_eq!(read_response(&mut stream), "HTTP/1.1 403 Forbidden");
+
+    // Connections exceeding the proxy's limit are rejected.
+    let socket_path = env::var_os("BIRDCAGE_PROXY_SOCKET").unwrap();
+    let _idle: Vec<_> = (0..64).map(|_| UnixStream::connect(&socket_path).unwrap()).collect();
+    let mut stream = connect(allowed);
+    assert_eq!(read_response(&mut stream), "HTTP/1.1 503 Service Unavailable");
+}
+
+/// Send a CONNECT request for a local port to the egress proxy.
+#[cfg(target_os = "linux")]
+fn connect(port: u16) -> UnixStream {
+    let socket_path = env::var_os("BIRDCAGE_PROXY_SOCKET").unwrap();
+    let mut stream = UnixStream::connect(socket_path).unwrap();
+    let request = format!("CONNECT 127.0.0.1:{port} HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\n\r\n");
+    stream.write_all(request.as_bytes()).unwrap();
+    stream
+}
+
+/// Read the status line of a proxy response.
+#[cfg(target_os = "linux")]
+fn read_response(stream: &mut UnixStream) -> String {
+    let mut response = Vec::new();
+    let mut byte = [0];
+    while !response.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).unwrap();
+        response.push(byte[0]);
+    }
+    String::from_utf8(response).unwrap().lines().next().unwrap().into()
+}
+
+/// Start a TCP server echoing all received data.
+#[cfg(target_os = "linux")]
+fn echo_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = stream.try_clone().unwrap();
+            thread::spawn(move || std::io::copy(&mut reader, &mut stream));
+        }
+    });
+
+    port
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
@@ -0,0 +1,164 @@
+#[cfg(target_os = "linux")]
+use std::os::unix::fs as unixfs;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{env, fs};
+
+#[cfg(target_os = "linux")]
+use birdcage::overlay::{ChangeKind, ChangeSet};
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+const FILE_CONTENT: &str = "expected content";
+
+#[cfg(target_os = "linux")]
+const SANDBOX_CONTENT: &str = "sandbox content";
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Modify the overlays from inside the sandbox.
+    if let Some(root) = env::args().nth(1) {
+        sandboxee(Path::new(&root));
+        return;
+    }
+
+    // Setup our test files.
+    let tempdir = TempDir::new().unwrap();
+    let root = tempdir.path().canonicalize().unwrap();
+    let project = root.join("project");
+    fs::create_dir_all(project.join("removed_dir")).unwrap();
+    fs::write(project.join("modified"), FILE_CONTENT).unwrap();
+    fs::write(project.join("deleted"), FILE_CONTENT).unwrap();
+    fs::write(project.join("removed_dir/file"), FILE_CONTENT).unwrap();
+    fs::create_dir(project.join("opaque_dir")).unwrap();
+    fs::write(project.join("opaque_dir/old"), FILE_CONTENT).unwrap();
+    fs::write(root.join("outside"), FILE_CONTENT).unwrap();
+    unixfs::symlink(root.join("outside"), project.join("link")).unwrap();
+    fs::create_dir(root.join("scratch")).unwrap();
+    fs::write(root.join("scratch/file"), FILE_CONTENT).unwrap();
+    fs::create_dir(root.join("changes")).unwrap();
+
+    // Overlay paths must be valid directories.
+    let file_overlay = Exception::Overlay { path: project.join("modified"), changes: None };
+    assert!(Birdcage::new().add_exception(file_overlay).is_err());
+    fs::create_dir(root.join("invalid,dir")).unwrap();
+    let invalid_overlay = Exception::Overlay { path: root.join("invalid,dir"), changes: None };
+    assert!(Birdcage::new().add_exception(invalid_overlay).is_err());
+
+    // Changes are kept outside the overlay, without modifying the host.
+    run_sandbox(&root);
+    assert_unmodified(&project);
+    assert_eq!(fs::read_to_string(root.join("scratch/file")).unwrap(), FILE_CONTENT);
+    assert!(!root.join("scratch/added").exists());
+
+    // Changes can be reviewed.
+    let change_set = ChangeSet::new(&project, root.join("changes")).unwrap();
+    let mut changes: Vec<_> =
+        change_set.changes().iter().map(|change| (change.path.clone(), change.kind)).collect();
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(changes, [
+        (project.join("added"), ChangeKind::Added),
+        (project.join("deleted"), ChangeKind::Deleted),
+        (project.join("link"), ChangeKind::Modified),
+        (project.join("modified"), ChangeKind::Modified),
+        (project.join("opaque_dir/new"), ChangeKind::Added),
+        (project.join("opaque_dir/old"), ChangeKind::Deleted),
+        (project.join("removed_dir"), ChangeKind::Deleted),
+        (project.join("removed_dir/file"), ChangeKind::Deleted),
+    ]);
+
+    // Discarded changes are removed.
+    change_set.discard().unwrap();
+    assert_unmodified(&project);
+    assert!(ChangeSet::new(&project, root.join("changes")).unwrap().changes().is_empty());
+
+    // Committed changes are applied to the host.
+    run_sandbox(&root);
+    ChangeSet::new(&project, root.join("changes")).unwrap().commit().unwrap();
+    assert_eq!(fs::read_to_string(project.join("added")).unwrap(), SANDBOX_CONTENT);
+    assert_eq!(fs::read_to_string(project.join("modified")).unwrap(), SANDBOX_CONTENT);
+    assert!(!project.join("deleted").exists());
+    assert!(!project.join("removed_dir").exists());
+
+    // Replaced symlinks are removed instead of writing to their target.
+    assert!(!project.join("link").symlink_metadata().unwrap().file_type().is_symlink());
+    assert_eq!(fs::read_to_string(project.join("link")).unwrap(), SANDBOX_CONTENT);
+    assert_eq!(fs::read_to_string(root.join("outside")).unwrap(), FILE_CONTENT);
+
+    // Opaque directories replace the original directory's content.
+    assert!(!project.join("opaque_dir/old").exists());
+    assert_eq!(fs::read_to_string(project.join("opaque_dir/new")).unwrap(), SANDBOX_CONTENT);
+    assert!(ChangeSet::new(&project, root.join("changes")).unwrap().changes().is_empty());
+}
+
+/// Run the sandboxee with overlays for the project and scratch directories.
+#[cfg(target_os = "linux")]
+fn run_sandbox(root: &Path) {
+    let mut birdcage = common::sandbox();
+    let project =
+        Exception::Overlay { path: root.join("project"), changes: Some(root.join("changes")) };
+    birdcage.add_exception(project).unwrap();
+    birdcage
+        .add_exception(Exception::Overlay { path: root.join("scratch"), changes: None })
+        .unwrap();
+
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg(root);
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+}
+
+/// Assert that the project directory has its original content.
+#[cfg(target_os = "linux")]
+fn assert_unmodified(project: &Path) {
+    assert!(!project.join("added").exists());
+    assert_eq!(fs::read_to_string(project.join("modified")).unwrap(), FILE_CONTENT);
+    assert_eq!(fs::read_to_string(project.join("deleted")).unwrap(), FILE_CONTENT);
+    assert_eq!(fs::read_to_string(project.join("removed_dir/file")).unwrap(), FILE_CONTENT);
+    assert_eq!(fs::read_to_string(project.join("opaque_dir/old")).unwrap(), FILE_CONTENT);
+    assert!(!project.join("opaque_dir/new").exists());
+    assert!(project.join("link").symlink_metadata().unwrap().file_type().is_symlink());
+}
+
+/// Modifications run inside the sandbox.
+#[cfg(target_os = "linux")]
+fn sandboxee(root: &Path) {
+    let project = root.join("project");
+
+    // Original content is visible.
+    assert_eq!(fs::read_to_string(project.join("modified")).unwrap(), FILE_CONTENT);
+
+    // Overlay is writable.
+    fs::write(project.join("added"), SANDBOX_CONTENT).unwrap();
+    fs::write(project.join("modified"), SANDBOX_CONTENT).unwrap();
+    fs::remove_file(project.join("deleted")).unwrap();
+    fs::remove_dir_all(project.join("removed_dir")).unwrap();
+
+    // Symlinks can be replaced with files.
+    fs::remove_file(project.join("link")).unwrap();
+    fs::write(project.join("link"), SANDBOX_CONTENT).unwrap();
+
+    // Recreated directories hide their original content.
+    fs::remove_dir_all(project.join("opaque_dir")).unwrap();
+    fs::create_dir(project.join("opaque_dir")).unwrap();
+    fs::write(project.join("opaque_dir/new"), SANDBOX_CONTENT).unwrap();
+    assert!(!project.join("opaque_dir/old").exists());
+    assert_eq!(fs::read_to_string(project.join("modified")).unwrap(), SANDBOX_CONTENT);
+    assert!(!project.join("deleted").exists());
+
+    // Overlays without a changes directory are writable too.
+    fs::write(root.join("scratch/added"), SANDBOX_CONTENT).unwrap();
+    fs::write(root.join("scratch/file"), SANDBOX_CONTENT).unwrap();
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
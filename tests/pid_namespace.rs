@@ -0,0 +1,170 @@
+#[cfg(target_os = "linux")]
+use std::io::{BufRead, BufReader};
+#[cfg(target_os = "linux")]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::process::{self, Command, Stdio};
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
+#[cfg(target_os = "linux")]
+use std::{env, fs, thread};
+
+#[cfg(target_os = "linux")]
+use birdcage::report::Layer;
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+/// Exit code of the sandboxee after receiving `SIGTERM`.
+#[cfg(target_os = "linux")]
+const TERMINATED_CODE: i32 = 42;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Run the sandboxed parts of the test.
+    let mut args = env::args().skip(1);
+    match args.next().as_deref() {
+        Some("sandboxee") => {
+            // Sandboxee is the only process besides init.
+            assert_eq!(process::id(), 2);
+            assert_eq!(process_ids(), [1, 2]);
+
+            // Start a process which outlives the sandboxee.
+            let marker = args.next().unwrap();
+            Command::new(env::current_exe().unwrap()).args(["orphan", &marker]).spawn().unwrap();
+            process::exit(3);
+        },
+        Some("orphan") => {
+            thread::sleep(Duration::from_secs(1));
+            fs::write(args.next().unwrap(), b"orphan").unwrap();
+            return;
+        },
+        Some("killed") => {
+            unsafe { libc::kill(process::id() as i32, libc::SIGKILL) };
+            unreachable!();
+        },
+        Some("terminated") => {
+            let handler = exit_terminated as extern "C" fn(libc::c_int);
+            unsafe { libc::signal(libc::SIGTERM, handler as libc::sighandler_t) };
+            println!("ready");
+            loop {
+                thread::sleep(Duration::from_secs(1));
+            }
+        },
+        Some("double-fork") => {
+            // Orphan a grandchild, which has to be reaped by init.
+            let status = Command::new(env::current_exe().unwrap()).arg("fork").status().unwrap();
+            assert!(status.success());
+
+            let start = Instant::now();
+            while process_ids() != [1, 2] {
+                assert!(start.elapsed() < Duration::from_secs(5), "{:?}", process_ids());
+                thread::sleep(Duration::from_millis(50));
+            }
+            return;
+        },
+        Some("fork") => {
+            // Exit without waiting for the child.
+            #[allow(clippy::zombie_processes)]
+            Command::new(env::current_exe().unwrap()).arg("exit").spawn().unwrap();
+            return;
+        },
+        Some("exit") => return,
+        _ => (),
+    }
+
+    // Setup our test files.
+    let tempdir = TempDir::new().unwrap();
+    let marker = tempdir.path().join("marker");
+
+    // Ensure the orphan can write its marker.
+    let status = sandbox(tempdir.path())
+        .spawn(sandboxee(["orphan", marker.to_str().unwrap()]))
+        .unwrap()
+        .0
+        .wait()
+        .unwrap();
+    assert!(status.success());
+    assert!(marker.exists());
+    fs::remove_file(&marker).unwrap();
+
+    // Exit code is forwarded and descendants are killed with the sandboxee.
+    let command = sandboxee(["sandboxee", marker.to_str().unwrap()]);
+    let status = sandbox(tempdir.path()).spawn(command).unwrap().0.wait().unwrap();
+    assert_eq!(status.code(), Some(3));
+    thread::sleep(Duration::from_secs(2));
+    assert!(!marker.exists());
+
+    // Termination signals are forwarded.
+    let status = sandbox(tempdir.path()).spawn(sandboxee(["killed"])).unwrap().0.wait().unwrap();
+    assert_eq!(status.signal(), Some(libc::SIGKILL));
+
+    // Signals sent to the spawned child reach the sandboxee.
+    let mut command = sandboxee(["terminated"]);
+    command.stdout(Stdio::piped());
+    let mut child = sandbox(tempdir.path()).spawn(command).unwrap().0;
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    assert_eq!(line, "ready\n");
+    unsafe { libc::kill(child.id() as i32, libc::SIGTERM) };
+    assert_eq!(child.wait().unwrap().code(), Some(TERMINATED_CODE));
+
+    // Orphaned processes are reaped by init.
+    let status =
+        sandbox(tempdir.path()).spawn(sandboxee(["double-fork"])).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // Spawned sandboxes report the PID namespace.
+    let (mut child, report) = sandbox(tempdir.path()).spawn(sandboxee(["exit"])).unwrap();
+    assert!(child.wait().unwrap().success());
+    assert!(report.applied().contains(&Layer::PidNamespace));
+
+    // Locking the current process does not fork it.
+    let (pid, parent) = (process::id(), unsafe { libc::getppid() });
+    let report = Birdcage::new().lock().unwrap();
+    assert!(report.skipped().iter().any(|skipped| skipped.layer == Layer::PidNamespace));
+    assert_eq!(process::id(), pid);
+    assert_eq!(unsafe { libc::getppid() }, parent);
+}
+
+/// Signal handler exiting with [`TERMINATED_CODE`].
+#[cfg(target_os = "linux")]
+extern "C" fn exit_terminated(_signal: libc::c_int) {
+    unsafe { libc::_exit(TERMINATED_CODE) };
+}
+
+/// Get all process IDs in procfs.
+#[cfg(target_os = "linux")]
+fn process_ids() -> Vec<u32> {
+    let mut pids: Vec<u32> = fs::read_dir("/proc")
+        .unwrap()
+        .filter_map(|entry| entry.unwrap().file_name().to_str()?.parse().ok())
+        .collect();
+    pids.sort_unstable();
+    pids
+}
+
+/// Create a sandbox which allows executing this test.
+#[cfg(target_os = "linux")]
+fn sandbox(tempdir: &Path) -> Birdcage {
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::WriteAndRead(tempdir.into())).unwrap();
+    birdcage
+}
+
+/// Create a command running this test with the specified arguments.
+#[cfg(target_os = "linux")]
+fn sandboxee<const N: usize>(args: [&str; N]) -> Command {
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.args(args);
+    command
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
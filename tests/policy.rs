@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::Path;
+
+use birdcage::error::Error;
+use birdcage::policy::Policy;
+use birdcage::{Exception, Sandbox};
+use tempfile::TempDir;
+
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Setup our test files.
+    let tempdir = TempDir::new().unwrap();
+    let root = tempdir.path();
+    fs::create_dir(root.join("public")).unwrap();
+    fs::write(root.join("public/file"), FILE_CONTENT).unwrap();
+    fs::write(root.join("private"), FILE_CONTENT).unwrap();
+    std::env::set_var("BIRDCAGE_POLICY_TEST", root);
+
+    fs::write(
+        root.join("base.toml"),
+        r#"
+        version = 1
+
+        [filesystem]
+        full_procfs = true
+
+        [environment]
+        variables = ["PATH"]
+
+        [ipc]
+        host = true
+        "#,
+    )
+    .unwrap();
+    fs::write(
+        root.join("policy.toml"),
+        r#"
+        version = 1
+        include = ["base.toml"]
+
+        [filesystem]
+        read = ["${BIRDCAGE_POLICY_TEST}/public"]
+        write = ["public/file"]
+
+        [network]
+        hosts = ["example.com:443"]
+        "#,
+    )
+    .unwrap();
+
+    // Exceptions are loaded from includes first.
+    let policy = Policy::load(root.join("policy.toml")).unwrap();
+    let exceptions = policy.exceptions();
+    assert_eq!(exceptions.len(), 6);
+    assert_eq!(exceptions[0], Exception::FullProcfs);
+    assert!(matches!(&exceptions[1], Exception::Environment(var) if var == "PATH"));
+    assert_eq!(exceptions[2], Exception::HostIpc);
+    assert!(matches!(&exceptions[3], Exception::Read(path) if path == &root.join("public")));
+    assert!(
+        matches!(&exceptions[4], Exception::WriteAndRead(path) if path == &root.join("public/file"))
+    );
+    assert!(matches!(
+        &exceptions[5],
+        Exception::NetworkHost { host, port: 443 } if host == "example.com"
+    ));
+
+    // Errors report the line of unknown keys.
+    assert_error(root, "version = 1\n\n[filesystem]\nreed = []\n", 4);
+
+    // Errors report the line of invalid paths.
+    assert_error(root, "version = 1\n\n[filesystem]\nread = [\n  \"missing\",\n]\n", 5);
+
+    // Errors report the line of tmpfs mounts without size limit.
+    let tmpfs =
+        "version = 1\n[filesystem]\ntmpfs = [\n  { path = \".\", size = 0, mode = 0o700 },\n]\n";
+    assert_error(root, tmpfs, 4);
+
+    // Errors report the line of relative paths inside the sandbox.
+    let bind = "version = 1\n[filesystem]\nbind = [\n  { source = \".\", target = \"work\", \
+                access = \"read\" },\n]\n";
+    assert_error(root, bind, 4);
+    let files = "version = 1\n[filesystem]\nfiles = [\n  { path = \"hosts\", contents = \"\", \
+                 mode = 0o644 },\n]\n";
+    assert_error(root, files, 4);
+
+    // Errors report the line of undefined variables.
+    assert_error(root, "version = 1\n[filesystem]\nread = [\"$BIRDCAGE_MISSING\"]\n", 3);
+
+    // Errors report unsupported versions.
+    assert_error(root, "\nversion = 2\n", 2);
+
+    // Errors report include cycles.
+    assert_error(root, "version = 1\ninclude = [\"invalid.toml\"]\n", 2);
+
+    // Apply the policy.
+    let mut birdcage = Policy::load(root.join("policy.toml")).unwrap().config().sandbox().unwrap();
+    birdcage.add_exception(Exception::Networking).unwrap();
+    birdcage.lock().unwrap();
+
+    let content = fs::read_to_string(root.join("public/file")).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+    let result = fs::read_to_string(root.join("private"));
+    assert!(result.is_err());
+}
+
+/// Ensure loading a policy fails at the expected line.
+fn assert_error(root: &Path, policy: &str, line: usize) {
+    let path = root.join("invalid.toml");
+    fs::write(&path, policy).unwrap();
+
+    match Policy::load(&path) {
+        Err(Error::Policy(error)) => {
+            assert_eq!(error.path, path);
+            assert_eq!(error.line, Some(line), "{error}");
+        },
+        result => panic!("unexpected result: {result:?}"),
+    }
+}
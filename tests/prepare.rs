@@ -0,0 +1,48 @@
+use std::{env, fs};
+
+use birdcage::{Birdcage, Exception, Sandbox};
+use tempfile::NamedTempFile;
+
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Setup our test files.
+    let public_file = NamedTempFile::new().unwrap();
+    fs::write(&public_file, FILE_CONTENT.as_bytes()).unwrap();
+    let private_file = NamedTempFile::new().unwrap();
+    fs::write(&private_file, FILE_CONTENT.as_bytes()).unwrap();
+    env::set_var("PUBLIC", "public");
+    env::set_var("PRIVATE", "private");
+
+    // Preparation errors leave the process untouched.
+    //
+    // Network host exceptions are rejected by `lock` on Linux and by
+    // `add_exception` on macOS.
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::Environment("PUBLIC".into())).unwrap();
+    let host = Exception::NetworkHost { host: "localhost".into(), port: 80 };
+    if birdcage.add_exception(host).is_ok() {
+        assert!(birdcage.prepare().is_err());
+    }
+    assert_eq!(env::var("PRIVATE").unwrap(), "private");
+
+    // Prepared sandboxes are not applied before they are committed.
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::Read(public_file.path().into())).unwrap();
+    birdcage.add_exception(Exception::Environment("PUBLIC".into())).unwrap();
+    let prepared = birdcage.prepare().unwrap();
+
+    let content = fs::read_to_string(private_file.path()).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+    assert_eq!(env::var("PRIVATE").unwrap(), "private");
+
+    // Committing applies all restrictions.
+    prepared.commit().unwrap();
+
+    let content = fs::read_to_string(public_file.path()).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+    let result = fs::read_to_string(private_file.path());
+    assert!(result.is_err());
+    assert_eq!(env::var("PUBLIC").unwrap(), "public");
+    assert!(env::var("PRIVATE").is_err());
+}
@@ -0,0 +1,58 @@
+#[cfg(target_os = "linux")]
+use std::fs;
+
+#[cfg(target_os = "linux")]
+use birdcage::probe::SeccompMode;
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::NamedTempFile;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Setup our test files.
+    let public_file = NamedTempFile::new().unwrap();
+    fs::write(&public_file, FILE_CONTENT.as_bytes()).unwrap();
+    let private_file = NamedTempFile::new().unwrap();
+    fs::write(&private_file, FILE_CONTENT.as_bytes()).unwrap();
+
+    // Probing reports the supported layers without modifying the process.
+    let capabilities = Birdcage::probe();
+    assert!(capabilities.isolates_filesystem());
+    assert!(capabilities.seccomp_filter);
+    assert_eq!(capabilities.seccomp_mode, seccomp_mode());
+    assert_eq!(Birdcage::probe(), capabilities);
+
+    // Sandbox can still be activated after probing.
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::Read(public_file.path().into())).unwrap();
+    birdcage.lock().unwrap();
+
+    let content = fs::read_to_string(public_file.path()).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+    let result = fs::read_to_string(private_file.path());
+    assert!(result.is_err());
+
+    // Active seccomp filters are reported.
+    let capabilities = Birdcage::probe();
+    assert_eq!(capabilities.seccomp_mode, SeccompMode::Filter);
+    assert_eq!(seccomp_mode(), SeccompMode::Filter);
+}
+
+/// Read the seccomp mode of the current process from procfs.
+#[cfg(target_os = "linux")]
+fn seccomp_mode() -> SeccompMode {
+    let status = fs::read_to_string("/proc/self/status").unwrap();
+    let mode = status.lines().find_map(|line| line.strip_prefix("Seccomp:"));
+    match mode.map(str::trim) {
+        Some("0") => SeccompMode::Disabled,
+        Some("1") => SeccompMode::Strict,
+        Some("2") => SeccompMode::Filter,
+        _ => SeccompMode::Unsupported,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
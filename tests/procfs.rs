@@ -0,0 +1,151 @@
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "linux")]
+use std::process::{self, Command};
+#[cfg(target_os = "linux")]
+use std::{env, fs};
+
+#[cfg(target_os = "linux")]
+use birdcage::{Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+/// Devices bind mounted into the sandbox's `/dev`.
+#[cfg(target_os = "linux")]
+const DEVICES: &[&str] =
+    &["/dev/null", "/dev/zero", "/dev/full", "/dev/random", "/dev/urandom", "/dev/tty"];
+
+/// Library directories allowed by [`common::sandbox`].
+#[cfg(target_os = "linux")]
+const LIBRARIES: &[&str] = &["/usr/lib", "/lib64", "/lib"];
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Check the procfs from inside the sandbox.
+    let mut args = env::args().skip(1);
+    match args.next().as_deref() {
+        Some("full") => return full_procfs(),
+        Some("private") => {
+            let host_pid = args.next().unwrap();
+            let expected = args.map(PathBuf::from).collect();
+            return private_procfs(&host_pid, expected);
+        },
+        _ => (),
+    }
+
+    // Setup our test files.
+    let tempdir = TempDir::new().unwrap();
+    let host_pid = process::id().to_string();
+    assert!(Path::new("/proc").join(&host_pid).exists());
+
+    // Only the sandbox's mounts are expected inside of it.
+    let mut expected: Vec<_> = ["/", "/proc", "/dev", "/dev/pts", "/dev/shm", "/dev/mqueue"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    expected.extend(DEVICES.iter().map(PathBuf::from).filter(|device| device.exists()));
+    expected.push(tempdir.path().into());
+    expected.push(env::current_exe().unwrap().canonicalize().unwrap());
+    let libraries = LIBRARIES.iter().filter_map(|library| Path::new(library).canonicalize().ok());
+    expected.extend(libraries);
+    expected.sort();
+    expected.dedup();
+
+    // Processes are isolated in spawned sandboxes.
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::Read(tempdir.path().into())).unwrap();
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg("private").arg(&host_pid).args(&expected);
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // All procfs entries are exposed with an exception.
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::FullProcfs).unwrap();
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg("full");
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+}
+
+/// Check the default procfs, which only exposes the sandbox's processes.
+#[cfg(target_os = "linux")]
+fn private_procfs(host_pid: &str, expected: Vec<PathBuf>) {
+    // Processes inside the sandbox are visible.
+    assert!(Path::new("/proc/self/status").exists());
+    assert!(fs::read_to_string("/proc/1/cmdline").is_ok());
+
+    // Host processes are not visible.
+    assert!(!Path::new("/proc").join(host_pid).exists());
+
+    // Entries unrelated to processes are hidden by default.
+    assert!(!Path::new("/proc/sys").exists());
+    assert!(!Path::new("/proc/cpuinfo").exists());
+
+    // Host mounts are not visible.
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").unwrap();
+    let mut mount_points: Vec<_> =
+        mountinfo.lines().map(|line| PathBuf::from(line.split(' ').nth(4).unwrap())).collect();
+    mount_points.sort();
+    assert_eq!(mount_points, expected);
+
+    // Kernel settings cannot be modified.
+    assert!(fs::write("/proc/sys/kernel/hostname", "sandbox").is_err());
+    assert!(fs::write("/proc/sysrq-trigger", "h").is_err());
+
+    // Kernel memory cannot be read.
+    assert!(fs::read("/proc/kcore").map_or(true, |kcore| kcore.is_empty()));
+}
+
+/// Check the procfs exposed by [`Exception::FullProcfs`].
+#[cfg(target_os = "linux")]
+fn full_procfs() {
+    // System-wide entries are visible.
+    assert!(fs::read_to_string("/proc/cpuinfo").is_ok());
+    assert!(Path::new("/proc/sys/kernel/hostname").exists());
+
+    // Kernel settings are mounted read-only.
+    let sys = mount("/proc/sys").expect("missing /proc/sys mount");
+    assert!(sys.options.split(',').any(|option| option == "ro"), "{sys:?}");
+    assert!(fs::write("/proc/sys/kernel/hostname", "sandbox").is_err());
+    assert!(fs::write("/proc/sysrq-trigger", "h").is_err());
+
+    // Kernel state is masked by read-only bind mounts of `/dev/null`.
+    //
+    // Entries like `/proc/kcore` depend on the kernel configuration, so only
+    // existing entries are checked.
+    let masked = ["/proc/kcore", "/proc/keys", "/proc/timer_list", "/proc/sched_debug"];
+    let masked: Vec<_> = masked.into_iter().filter(|path| Path::new(path).exists()).collect();
+    assert!(!masked.is_empty());
+    for path in masked {
+        let mount = mount(path).unwrap_or_else(|| panic!("missing {path} mount"));
+        assert_eq!(mount.root, "/null");
+        assert!(mount.options.split(',').any(|option| option == "ro"), "{mount:?}");
+        assert!(fs::read(path).map_or(true, |contents| contents.is_empty()));
+    }
+}
+
+/// Mount point in `/proc/self/mountinfo`.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+struct MountInfo {
+    root: String,
+    options: String,
+}
+
+/// Find the topmost mount at `mount_point`.
+#[cfg(target_os = "linux")]
+fn mount(mount_point: &str) -> Option<MountInfo> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").unwrap();
+    mountinfo.lines().rev().find_map(|line| {
+        let fields: Vec<_> = line.split(' ').collect();
+        (fields[4] == mount_point)
+            .then(|| MountInfo { root: fields[3].into(), options: fields[5].into() })
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
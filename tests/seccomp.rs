@@ -20,9 +20,17 @@ fn main() {
 
     // Ensure `clone` is blocked with `CLONE_NEWUSER`.
     let stack = unsafe { libc::malloc(4096) };
-    let flags = libc::CLONE_NEWUSER as libc::c_ulong;
+    let flags = libc::CLONE_NEWUSER as std::os::raw::c_ulong;
     let result = unsafe { libc::syscall(libc::SYS_clone, flags, stack) };
     assert_eq!(result, -1);
+
+    // Ensure terminal input cannot be injected.
+    for request in [libc::TIOCSTI, libc::TIOCLINUX] {
+        let byte = 0u8;
+        let result = unsafe { libc::ioctl(0, request, &byte) };
+        assert_eq!(result, -1);
+        assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EACCES));
+    }
 }
 
 #[cfg(not(target_os = "linux"))]
@@ -0,0 +1,86 @@
+use std::fs;
+
+use birdcage::config::SandboxConfig;
+#[cfg(target_os = "linux")]
+use birdcage::Clock;
+use birdcage::{Birdcage, Exception, Sandbox, Strictness};
+use tempfile::NamedTempFile;
+
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Setup our test files.
+    let public_file = NamedTempFile::new().unwrap();
+    fs::write(&public_file, FILE_CONTENT.as_bytes()).unwrap();
+    let private_file = NamedTempFile::new().unwrap();
+    fs::write(&private_file, FILE_CONTENT.as_bytes()).unwrap();
+    let public_path = public_file.path().to_str().unwrap();
+
+    let mut birdcage = Birdcage::new();
+    birdcage.add_exception(Exception::Read(public_file.path().into())).unwrap();
+    birdcage.add_exception(Exception::Environment("PATH".into())).unwrap();
+    birdcage.add_exception(Exception::ReadAll).unwrap();
+    birdcage
+        .add_exception(Exception::NetworkHost { host: "example.com".into(), port: 443 })
+        .unwrap();
+
+    // Invalid exceptions are not part of the configuration.
+    let result = birdcage.add_exception(Exception::Read("/nonexistent/path".into()));
+    assert!(result.is_err());
+
+    // Configurations use a stable format.
+    let config = birdcage.config();
+    let json = serde_json::to_string(&config).unwrap();
+    let expected = format!(
+        "{{\"version\":1,\"exceptions\":[{{\"read\":\"{public_path}\"}},{{\"environment\":\"PATH\"\
+         }},\"read_all\",{{\"network_host\":{{\"host\":\"example.com\",\"port\":443}}}}]}}"
+    );
+    assert_eq!(json, expected);
+
+    // Configurations round-trip.
+    let deserialized: SandboxConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, config);
+    assert_eq!(deserialized.sandbox().unwrap().config(), config);
+
+    // Settings besides exceptions are part of the configuration.
+    let mut birdcage = Birdcage::new();
+    birdcage.strictness(Strictness::BestEffort);
+    let config = birdcage.config();
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(json, "{\"version\":1,\"exceptions\":[],\"strictness\":\"best_effort\"}");
+    assert_eq!(serde_json::from_str::<SandboxConfig>(&json).unwrap(), config);
+    assert_eq!(config.sandbox().unwrap().config(), config);
+
+    #[cfg(target_os = "linux")]
+    {
+        let mut birdcage = Birdcage::new();
+        birdcage
+            .require_exceptions()
+            .hostname("sandbox")
+            .domainname("example.com")
+            .clock_offset(Clock::Boottime, 60);
+        let config = birdcage.config();
+        let json = serde_json::to_string(&config).unwrap();
+        let expected = concat!(
+            r#"{"version":1,"exceptions":[],"require_exceptions":true,"hostname":"sandbox","#,
+            r#""domainname":"example.com","clock_offsets":[["boottime",60]]}"#,
+        );
+        assert_eq!(json, expected);
+        assert_eq!(serde_json::from_str::<SandboxConfig>(&json).unwrap(), config);
+        assert_eq!(config.sandbox().unwrap().config(), config);
+    }
+
+    // Unknown versions are rejected.
+    let result = serde_json::from_str::<SandboxConfig>("{\"version\":2,\"exceptions\":[]}");
+    assert!(result.is_err());
+
+    // Deserialized configurations can be applied.
+    let json = format!("{{\"version\":1,\"exceptions\":[{{\"read\":\"{public_path}\"}}]}}");
+    let config: SandboxConfig = serde_json::from_str(&json).unwrap();
+    config.sandbox().unwrap().lock().unwrap();
+
+    let content = fs::read_to_string(public_file.path()).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+    let result = fs::read_to_string(private_file.path());
+    assert!(result.is_err());
+}
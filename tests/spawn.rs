@@ -0,0 +1,63 @@
+use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc;
+use std::{env, fs, thread};
+
+use birdcage::{Exception, Sandbox};
+use tempfile::NamedTempFile;
+
+mod common;
+
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Run the sandboxed part of the test.
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("sandboxee") {
+        let content = fs::read_to_string(args.next().unwrap()).ok();
+        assert_eq!(content.as_deref(), args.next().as_deref());
+        return;
+    }
+
+    // Setup our test files.
+    let private_path = NamedTempFile::new().unwrap();
+    fs::write(&private_path, FILE_CONTENT.as_bytes()).unwrap();
+    let public_path = NamedTempFile::new().unwrap();
+    fs::write(&public_path, FILE_CONTENT.as_bytes()).unwrap();
+
+    // Ensure the parent process is multi-threaded.
+    let (tx, rx) = mpsc::channel::<()>();
+    let thread = thread::spawn(move || rx.recv());
+
+    // Access to the public file is allowed.
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::Read(public_path.path().into())).unwrap();
+    let mut command = sandboxee(public_path.path());
+    command.arg(FILE_CONTENT);
+    let (mut child, report) = birdcage.spawn(command).unwrap();
+    let status = child.wait().unwrap();
+    assert!(status.success());
+
+    // The child's sandboxing layers are reported.
+    assert!(report.is_complete());
+    assert!(!report.applied().is_empty());
+
+    // Access to the private file is prohibited.
+    let command = sandboxee(private_path.path());
+    let status = common::sandbox().spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // The parent process itself is not sandboxed.
+    let content = fs::read_to_string(&private_path).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+
+    tx.send(()).unwrap();
+    thread.join().unwrap().unwrap();
+}
+
+/// Create a command which reads `path` from within the sandbox.
+fn sandboxee(path: &Path) -> Command {
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg("sandboxee").arg(path);
+    command
+}
@@ -0,0 +1,88 @@
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{env, fs};
+
+#[cfg(target_os = "linux")]
+use birdcage::error::Error;
+#[cfg(target_os = "linux")]
+use birdcage::report::Layer;
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox, Strictness};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    const FILE_CONTENT: &str = "expected content";
+
+    // Run the sandboxed part of the test.
+    if env::args().nth(1).as_deref() == Some("sandboxee") {
+        return;
+    }
+
+    // Setup our test files.
+    let tempdir = TempDir::new().unwrap();
+    let root = tempdir.path();
+    fs::create_dir_all(root.join("public/nested")).unwrap();
+    fs::write(root.join("public/file"), FILE_CONTENT).unwrap();
+    fs::write(root.join("public/nested/private"), FILE_CONTENT).unwrap();
+    fs::write(root.join("private"), FILE_CONTENT).unwrap();
+
+    // Strict sandboxes report all applied layers.
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(Exception::ReadAll).unwrap();
+    let report = birdcage.lock().unwrap();
+    for layer in [Layer::NetworkNamespace, Layer::MountNamespace, Layer::Seccomp] {
+        assert!(report.applied().contains(&layer), "{report:?}");
+    }
+
+    // Namespaces are blocked inside the sandbox, so denying nested paths fails.
+    let result = nested_sandbox(root, Strictness::Strict).lock();
+    assert!(matches!(result, Err(Error::ActivationFailed(_))), "{result:?}");
+
+    // Layers skipped by spawned children are reported to the parent.
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg("sandboxee");
+    let sandbox = nested_sandbox(root, Strictness::BestEffort);
+    let (mut child, report) = sandbox.spawn(command).unwrap();
+    assert!(child.wait().unwrap().success());
+    let skipped: Vec<_> = report.skipped().iter().map(|skipped| skipped.layer).collect();
+    assert_eq!(skipped, [Layer::NetworkNamespace, Layer::MountNamespace, Layer::IpcNamespace]);
+
+    // Best effort sandboxes skip the missing layers instead.
+    let report = nested_sandbox(root, Strictness::BestEffort).lock().unwrap();
+    assert!(!report.is_complete());
+    let skipped: Vec<_> = report.skipped().iter().map(|skipped| skipped.layer).collect();
+    assert_eq!(skipped, [Layer::NetworkNamespace, Layer::MountNamespace, Layer::IpcNamespace]);
+    for skipped in report.skipped() {
+        assert!(skipped.reason.starts_with("failed to create user namespace"), "{skipped}");
+    }
+    for layer in [Layer::NoNewPrivileges, Layer::Landlock, Layer::LandlockNetwork, Layer::Seccomp] {
+        assert!(report.applied().contains(&layer), "{report:?}");
+    }
+
+    // Landlock restrictions are still applied.
+    let content = fs::read_to_string(root.join("public/file")).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+    let result = fs::read_to_string(root.join("private"));
+    assert!(result.is_err());
+}
+
+/// Create a sandbox which requires namespaces to deny a nested path.
+#[cfg(target_os = "linux")]
+fn nested_sandbox(root: &Path, strictness: Strictness) -> Birdcage {
+    let mut birdcage = common::sandbox();
+    birdcage.strictness(strictness);
+    birdcage.add_exception(Exception::Read(root.join("public"))).unwrap();
+    birdcage.add_exception(Exception::Deny(root.join("public/nested/private"))).unwrap();
+    birdcage
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
@@ -0,0 +1,97 @@
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "linux")]
+use std::process::{self, Command};
+#[cfg(target_os = "linux")]
+use std::{env, fs};
+
+#[cfg(target_os = "linux")]
+use birdcage::error::Error;
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+const FILE_CONTENT: &str = "expected content";
+
+#[cfg(target_os = "linux")]
+const TMPFS_SIZE: u64 = 1024 * 1024;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Check the tmpfs from inside the sandbox.
+    let mut args = env::args().skip(1);
+    if let Some(root) = args.next() {
+        let host_shm = args.next().unwrap();
+        sandboxee(Path::new(&root), Path::new(&host_shm));
+        return;
+    }
+
+    // Setup our test files.
+    let tempdir = TempDir::new().unwrap();
+    let root = tempdir.path();
+    fs::create_dir_all(root.join("scratch/nested")).unwrap();
+    fs::write(root.join("scratch/file"), FILE_CONTENT).unwrap();
+    fs::write(root.join("scratch/nested/file"), FILE_CONTENT).unwrap();
+    let host_shm = PathBuf::from(format!("/dev/shm/birdcage-tmpfs-{}", process::id()));
+    fs::write(&host_shm, FILE_CONTENT).unwrap();
+
+    let mut birdcage = common::sandbox();
+    let tmpfs = Exception::Tmpfs { path: root.join("scratch"), size: TMPFS_SIZE, mode: 0o700 };
+    birdcage.add_exception(tmpfs).unwrap();
+    birdcage.add_exception(Exception::Read(root.join("scratch/nested"))).unwrap();
+
+    // Tmpfs exceptions require a directory.
+    let file_tmpfs = Exception::Tmpfs { path: host_shm.clone(), size: TMPFS_SIZE, mode: 0o700 };
+    assert!(Birdcage::new().add_exception(file_tmpfs).is_err());
+
+    // Tmpfs exceptions require a size limit.
+    let unlimited_tmpfs = Exception::Tmpfs { path: root.join("scratch"), size: 0, mode: 0o700 };
+    let result = Birdcage::new().add_exception(unlimited_tmpfs).map(|_| ());
+    assert!(matches!(result, Err(Error::InvalidException(_))), "{result:?}");
+
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.args([root.as_os_str(), host_shm.as_os_str()]);
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    fs::remove_file(&host_shm).unwrap();
+    assert!(status.success());
+
+    // Tmpfs content is discarded, without modifying the host.
+    assert!(!root.join("scratch/sandbox-file").exists());
+    assert_eq!(fs::read_to_string(root.join("scratch/file")).unwrap(), FILE_CONTENT);
+}
+
+/// Assertions run inside the sandbox.
+#[cfg(target_os = "linux")]
+fn sandboxee(root: &Path, host_shm: &Path) {
+    let scratch = root.join("scratch");
+
+    // Host content is hidden.
+    assert!(!scratch.join("file").exists());
+
+    // Tmpfs is writable with the requested mode.
+    fs::write(scratch.join("sandbox-file"), FILE_CONTENT).unwrap();
+    let mode = scratch.metadata().unwrap().permissions().mode();
+    assert_eq!(mode & 0o7777, 0o700);
+
+    // Tmpfs size is limited.
+    let result = fs::write(scratch.join("large-file"), vec![0; 2 * TMPFS_SIZE as usize]);
+    assert!(result.is_err());
+
+    // Nested exceptions are mounted on top of the tmpfs.
+    assert_eq!(fs::read_to_string(scratch.join("nested/file")).unwrap(), FILE_CONTENT);
+    assert!(fs::write(scratch.join("nested/file"), FILE_CONTENT).is_err());
+
+    // Shared memory is private and writable.
+    assert!(!host_shm.exists());
+    fs::write("/dev/shm/birdcage-sandbox", FILE_CONTENT).unwrap();
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
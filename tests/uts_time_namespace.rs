@@ -0,0 +1,94 @@
+#[cfg(target_os = "linux")]
+use std::ffi::CStr;
+#[cfg(target_os = "linux")]
+use std::os::raw::c_char;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{env, mem};
+
+#[cfg(target_os = "linux")]
+use birdcage::error::Error;
+#[cfg(target_os = "linux")]
+use birdcage::report::Layer;
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Clock, Sandbox};
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+const OFFSET: i64 = 1_000_000;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Check the hostname and clocks of a sandboxed child.
+    if let Some(boottime) = env::args().nth(1) {
+        assert_eq!(uts_names(), ("birdcage".into(), "example".into()));
+        assert!(clock(libc::CLOCK_BOOTTIME) >= boottime.parse::<i64>().unwrap() + OFFSET);
+        return;
+    }
+
+    let host_names = uts_names();
+    let monotonic = clock(libc::CLOCK_MONOTONIC);
+    let boottime = clock(libc::CLOCK_BOOTTIME);
+
+    // Hostname and clocks are changed for spawned processes.
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg(boottime.to_string());
+    let status = sandbox().spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+
+    // The host is not modified.
+    assert_eq!(uts_names(), host_names);
+    assert!(clock(libc::CLOCK_BOOTTIME) < boottime + OFFSET);
+
+    // Errors while activating the sandbox in the child are returned.
+    let mut birdcage = sandbox();
+    birdcage.hostname("x".repeat(65));
+    let error = birdcage.spawn(Command::new(env::current_exe().unwrap())).unwrap_err();
+    assert!(matches!(error, Error::Hostname(_)), "{error:?}");
+    assert_eq!(error.errno(), Some(libc::EINVAL));
+
+    // Hostname and clocks are changed for the current process.
+    let report = sandbox().lock().unwrap();
+    assert!(report.applied().contains(&Layer::UtsNamespace));
+    assert!(report.applied().contains(&Layer::TimeNamespace));
+    assert_eq!(uts_names(), ("birdcage".into(), "example".into()));
+    assert!(clock(libc::CLOCK_MONOTONIC) >= monotonic + OFFSET);
+    assert!(clock(libc::CLOCK_BOOTTIME) >= boottime + OFFSET);
+}
+
+/// Create a sandbox with a custom hostname and offset clocks.
+#[cfg(target_os = "linux")]
+fn sandbox() -> Birdcage {
+    let mut birdcage = common::sandbox();
+    birdcage.hostname("birdcage").domainname("example");
+    birdcage.clock_offset(Clock::Monotonic, OFFSET).clock_offset(Clock::Boottime, OFFSET);
+    birdcage
+}
+
+/// Get the hostname and domain name.
+#[cfg(target_os = "linux")]
+fn uts_names() -> (String, String) {
+    let mut uts: libc::utsname = unsafe { mem::zeroed() };
+    assert_eq!(unsafe { libc::uname(&mut uts) }, 0);
+    (c_string(&uts.nodename), c_string(&uts.domainname))
+}
+
+/// Convert a nul-terminated C string buffer to a string.
+#[cfg(target_os = "linux")]
+fn c_string(buffer: &[c_char]) -> String {
+    unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_string_lossy().into_owned()
+}
+
+/// Get a clock's time in seconds.
+#[cfg(target_os = "linux")]
+fn clock(clock: libc::clockid_t) -> i64 {
+    let mut time: libc::timespec = unsafe { mem::zeroed() };
+    assert_eq!(unsafe { libc::clock_gettime(clock, &mut time) }, 0);
+    time.tv_sec as i64
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
@@ -0,0 +1,131 @@
+#[cfg(target_os = "linux")]
+use std::ffi::OsString;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::{env, fs};
+
+#[cfg(target_os = "linux")]
+use birdcage::error::Error;
+#[cfg(target_os = "linux")]
+use birdcage::{Birdcage, Exception, Sandbox};
+#[cfg(target_os = "linux")]
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+mod common;
+
+#[cfg(target_os = "linux")]
+const FILE_CONTENT: &str = "expected content";
+
+#[cfg(target_os = "linux")]
+fn main() {
+    // Check the home directory from inside the sandbox.
+    let mut args = env::args().skip(1);
+    if let Some(mode) = args.next() {
+        let home = env::var_os("HOME").unwrap();
+        sandboxee(&mode, Path::new(&home));
+        return;
+    }
+
+    // Setup our test files.
+    let tempdir = TempDir::new().unwrap();
+    let home = tempdir.path().join("home");
+    fs::create_dir_all(home.join(".cache")).unwrap();
+    fs::write(home.join(".cache/file"), FILE_CONTENT).unwrap();
+    fs::create_dir_all(home.join(".cargo/registry")).unwrap();
+    fs::write(home.join(".cargo/registry/file"), FILE_CONTENT).unwrap();
+    fs::create_dir(home.join("project")).unwrap();
+    let persistent = tempdir.path().join("persistent");
+    fs::create_dir(&persistent).unwrap();
+
+    // Virtual home directories require `HOME`.
+    let host_home = env::var_os("HOME");
+    env::remove_var("HOME");
+    let virtual_home = Exception::VirtualHome { persistent: None };
+    let result = Birdcage::new().add_exception(virtual_home.clone()).map(|_| ());
+    assert!(matches!(result, Err(Error::InvalidException(_))), "{result:?}");
+    env::set_var("HOME", &home);
+
+    // Real subdirectories are passed through to an empty home directory.
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(virtual_home).unwrap();
+    birdcage.add_exception(Exception::Read(home.join(".cargo/registry"))).unwrap();
+    birdcage.add_exception(Exception::WriteAndRead(home.join("project"))).unwrap();
+    run(birdcage, "tmpfs");
+    assert!(!home.join(".config").exists());
+    assert_eq!(fs::read_to_string(home.join("project/file")).unwrap(), FILE_CONTENT);
+
+    // Passed through paths must exist in persistent home directories.
+    let mut birdcage = common::sandbox();
+    let persistent_home = Exception::VirtualHome { persistent: Some(persistent.clone()) };
+    birdcage.add_exception(persistent_home.clone()).unwrap();
+    birdcage.add_exception(Exception::Read(home.join(".cargo/registry"))).unwrap();
+    assert!(birdcage.spawn(Command::new(env::current_exe().unwrap())).is_err());
+    assert!(!persistent.join(".cargo").exists());
+
+    // Persistent home directories keep their changes.
+    fs::create_dir_all(persistent.join(".cargo/registry")).unwrap();
+    let mut birdcage = common::sandbox();
+    birdcage.add_exception(persistent_home).unwrap();
+    birdcage.add_exception(Exception::Read(home.join(".cargo/registry"))).unwrap();
+    run(birdcage, "persistent");
+    assert!(!home.join(".npm").exists());
+    assert_eq!(fs::read_to_string(persistent.join(".npm/file")).unwrap(), FILE_CONTENT);
+    assert!(!persistent.join(".cargo/registry/file").exists());
+
+    if let Some(host_home) = host_home {
+        env::set_var("HOME", host_home);
+    }
+}
+
+/// Run this test inside the sandbox.
+#[cfg(target_os = "linux")]
+fn run(birdcage: Birdcage, mode: &str) {
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg(mode);
+    let status = birdcage.spawn(command).unwrap().0.wait().unwrap();
+    assert!(status.success());
+}
+
+/// Assertions run inside the sandbox.
+#[cfg(target_os = "linux")]
+fn sandboxee(mode: &str, home: &Path) {
+    if mode == "persistent" {
+        let registry_file = home.join(".cargo/registry/file");
+        assert_eq!(fs::read_to_string(registry_file).unwrap(), FILE_CONTENT);
+        fs::create_dir(home.join(".npm")).unwrap();
+        fs::write(home.join(".npm/file"), FILE_CONTENT).unwrap();
+        return;
+    }
+
+    // The home directory's size is limited.
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").unwrap();
+    let home_mount = mountinfo
+        .lines()
+        .find(|line| Path::new(line.split(' ').nth(4).unwrap()) == home)
+        .expect("missing home mount");
+    let options = home_mount.rsplit(' ').next().unwrap();
+    assert!(options.split(',').any(|option| option == "size=1048576k"), "{home_mount}");
+
+    // Only passed through directories are visible.
+    let mut entries: Vec<OsString> =
+        fs::read_dir(home).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    entries.sort();
+    assert_eq!(entries, [".cargo", "project"]);
+
+    // Home directory is writable.
+    fs::create_dir(home.join(".config")).unwrap();
+    fs::write(home.join(".config/file"), FILE_CONTENT).unwrap();
+
+    // Passed through directories keep their access level.
+    let registry_file = home.join(".cargo/registry/file");
+    assert_eq!(fs::read_to_string(&registry_file).unwrap(), FILE_CONTENT);
+    assert!(fs::write(&registry_file, "").is_err());
+    fs::write(home.join("project/file"), FILE_CONTENT).unwrap();
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}